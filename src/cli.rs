@@ -0,0 +1,292 @@
+use shiplift::Docker;
+use std::path::PathBuf;
+use crate::container::{self,ContainerSetup,ContainerSummary,ExecSetup,WorkingDirectorySetup};
+use crate::workspace;
+use crate::images;
+use crate::config::{Config,Profile};
+
+const USAGE: &str = "Usage:
+  dde                               Start the interactive interface
+  dde list [--all] [--json]         List all development environments, with --all every container
+  dde attach <name>                 Start the environment and attach to it
+  dde exec <name> [options] [cmd]   Start the environment and run a new shell or [cmd] in it,
+                                    the arguments following [cmd] are passed on to it unchanged
+      --user <user>                 User to run the command as (default: user of the container)
+      --workdir <path>              Working directory of the command (default: of the container)
+  dde new <name> [options] [--json] Create a new environment
+      --profile <profile>           Use the image, env vars, mounts, ports and entry command of a profile
+      --image <image>               Image to use, pulled if missing (default: image from the config)
+      --cmd <command>               Entry command (default: entry_command from the config)
+      --mount <path>                Mount <path> into the container (default: current directory)
+      --copy <path>                 Copy <path> into the container
+      --no-workdir                  Neither mount nor copy a working directory
+      --no-ssh                      Do not import the SSH keys of the host
+      --no-git-config               Do not import the git config of the host
+      --rm                          Remove the container once it stopped
+      --keep                        Keep the container once it stopped
+  dde adopt <name>                  Manage a container which was not created by dde
+  dde stop <name> [--json]          Stop an environment
+  dde rm <name> [--json]            Stop and remove an environment
+  dde help                          Show this help
+
+Defaults are read from ~/.config/dde/config.toml and ./.dde.toml";
+
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+
+enum CliError {
+    Usage(String),
+    Failed(String),
+}
+
+impl From<String> for CliError {
+    fn from(error: String) -> CliError {
+        CliError::Failed(error)
+    }
+}
+
+/// The arguments of a subcommand split into positional arguments and flags.
+struct Arguments {
+    positional: Vec<String>,
+    json: bool,
+    flags: Vec<(String,Option<String>)>,
+}
+
+impl Arguments {
+    /// Parses `args`, `with_value` lists all flags that expect a value. Everything
+    /// after `--` is taken as positional argument.
+    fn parse(args: &[String], allowed: &[&str], with_value: &[&str]) -> Result<Arguments,CliError> {
+        let mut result = Arguments {
+            positional: Vec::new(),
+            json: false,
+            flags: Vec::new(),
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                result.positional.extend(iter.cloned());
+                break;
+            }
+            else if arg == "--json" {
+                result.json = true;
+            }
+            else if with_value.contains(&&arg[..]) {
+                let value = iter.next().ok_or_else(|| CliError::Usage(format!("Missing value for {}",arg)))?;
+                result.flags.push((arg.clone(),Some(value.clone())));
+            }
+            else if allowed.contains(&&arg[..]) {
+                result.flags.push((arg.clone(),None));
+            }
+            else if arg.starts_with("--") {
+                return Err(CliError::Usage(format!("Unknown option {}",arg)));
+            }
+            else {
+                result.positional.push(arg.clone());
+            }
+        }
+        Ok(result)
+    }
+
+    fn has(&self, flag: &str) -> bool {
+        self.flags.iter().any(|(x,_)| x == flag)
+    }
+
+    fn value(&self, flag: &str) -> Option<&str> {
+        self.flags.iter().rev().find(|(x,_)| x == flag).and_then(|(_,y)| y.as_deref())
+    }
+
+    /// The single positional argument naming the container.
+    fn name(&self) -> Result<&str,CliError> {
+        match &self.positional[..] {
+            [name] => Ok(name),
+            [] => Err(CliError::Usage(String::from("Missing container name"))),
+            _ => Err(CliError::Usage(String::from("Too many arguments"))),
+        }
+    }
+}
+
+fn print_json<T: serde::Serialize>(value: &T) {
+    println!("{}",serde_json::to_string_pretty(value).unwrap());
+}
+
+async fn list(docker: &Docker, args: &[String]) -> Result<(),CliError> {
+    let args = Arguments::parse(args,&["--all"],&[])?;
+    if !args.positional.is_empty() {
+        return Err(CliError::Usage(String::from("list does not take any arguments")));
+    }
+    let containers = container::list(docker,args.has("--all")).await?;
+    if args.json {
+        let adopted = container::adopted();
+        print_json(&containers.iter().map(|x| ContainerSummary::new(x,&adopted)).collect::<Vec<ContainerSummary>>());
+    }
+    else {
+        for x in containers.iter() {
+            println!("{}\t{}\t{}",container::display_name(x),x.state,x.image);
+        }
+    }
+    Ok(())
+}
+
+async fn attach(docker: &Docker, config: &Config, args: &[String]) -> Result<(),CliError> {
+    let args = Arguments::parse(args,&[],&[])?;
+    let found = container::find(docker,args.name()?).await?;
+    container::start_and_attach(docker,&found.id,&config.detach_sequence()).await?;
+    Ok(())
+}
+
+/// Index of the first argument after the container name that is not an option,
+/// it starts the command which keeps all the following arguments like with
+/// `docker exec`.
+fn command_start(args: &[String], with_value: &[&str]) -> usize {
+    let mut positional = 0;
+    let mut idx = 0;
+    while idx < args.len() {
+        let arg = &args[idx];
+        if arg == "--" {
+            break;
+        }
+        if with_value.contains(&&arg[..]) {
+            idx += 1;
+        }
+        else if !arg.starts_with("--") {
+            positional += 1;
+            if positional == 2 {
+                return idx;
+            }
+        }
+        idx += 1;
+    }
+    args.len()
+}
+
+async fn exec(docker: &Docker, config: &Config, args: &[String]) -> Result<(),CliError> {
+    let start = command_start(args,&["--user","--workdir"]);
+    let command = &args[start..];
+    let args = Arguments::parse(&args[..start],&[],&["--user","--workdir"])?;
+    let name = args.positional.first().ok_or_else(|| CliError::Usage(String::from("Missing container name")))?;
+    let found = container::find(docker,name).await?;
+    let setup = ExecSetup {
+        command: args.positional[1..].iter().chain(command.iter()).cloned().collect(),
+        user: args.value("--user").unwrap_or_default().to_owned(),
+        working_dir: args.value("--workdir").unwrap_or_default().to_owned(),
+    };
+    container::start_and_exec(docker,&found.id,&setup,&config.detach_sequence()).await?;
+    Ok(())
+}
+
+async fn new(docker: &Docker, config: &Config, args: &[String]) -> Result<(),CliError> {
+    let args = Arguments::parse(args,&["--no-workdir","--no-ssh","--no-git-config","--rm","--keep"],&["--profile","--image","--cmd","--mount","--copy"])?;
+    let name = args.name()?;
+    container::validate_name(name).map_err(CliError::Usage)?;
+    let profile = match args.value("--profile") {
+        Some(x) => config.profile(x)?.clone(),
+        None => Profile::default(),
+    };
+    let current_dir = std::env::current_dir().map_err(|e| format!("Could not read the current directory: {}",e))?.to_string_lossy().to_string();
+    let working_dir = match (args.value("--mount"),args.value("--copy"),args.has("--no-workdir")) {
+        (None,None,false) => WorkingDirectorySetup::MountDirectory(current_dir),
+        (Some(x),None,false) => WorkingDirectorySetup::MountDirectory(x.to_owned()),
+        (None,Some(x),false) => WorkingDirectorySetup::CopyDirectory(x.to_owned()),
+        (None,None,true) => WorkingDirectorySetup::DontUse,
+        _ => return Err(CliError::Usage(String::from("--mount, --copy and --no-workdir are mutually exclusive"))),
+    };
+    let setup = ContainerSetup {
+        name: name.to_owned(),
+        image: args.value("--image").or(profile.image.as_deref()).unwrap_or(&config.image).to_owned(),
+        entry_command: args.value("--cmd").or(profile.entry_command.as_deref()).unwrap_or(&config.entry_command).to_owned(),
+        auto_remove: (config.auto_remove || args.has("--rm")) && !args.has("--keep"),
+        import_keys: config.import_ssh_keys && !args.has("--no-ssh"),
+        git_config: config.import_git_config && !args.has("--no-git-config"),
+        working_dir,
+        mount_target: config.mount_target.clone(),
+        env: profile.env.clone(),
+        mounts: profile.mounts.clone(),
+        ports: profile.ports.clone(),
+        profile: args.value("--profile").map(String::from),
+    };
+    if !images::exists(docker,&setup.image).await {
+        eprintln!("Pulling {}...",setup.image);
+        eprintln!("{}",images::pull(docker,&setup.image,|_| {}).await?);
+    }
+    let id = container::create(docker,&setup).await?;
+    if let WorkingDirectorySetup::CopyDirectory(x) = &setup.working_dir {
+        let copied = match workspace::pack_directory(&PathBuf::from(x),&setup.mount_target,|_| {}) {
+            Ok(archive) => workspace::upload(docker,&id,archive).await,
+            Err(error) => Err(error),
+        };
+        match copied {
+            Ok(stats) => eprintln!("Copied {} to {}",stats.describe(),setup.mount_target),
+            Err(error) => {
+                let _ = container::remove(docker,&id).await;
+                return Err(CliError::Failed(error));
+            }
+        }
+    }
+    let created = container::find(docker,&id).await?;
+    if args.json {
+        print_json(&ContainerSummary::new(&created,&container::adopted()));
+    }
+    else {
+        println!("{}",container::display_name(&created));
+    }
+    Ok(())
+}
+
+async fn adopt(docker: &Docker, args: &[String]) -> Result<(),CliError> {
+    let args = Arguments::parse(args,&[],&[])?;
+    let found = container::find(docker,args.name()?).await?;
+    container::adopt(&found.id)?;
+    println!("{}",container::display_name(&found));
+    Ok(())
+}
+
+async fn stop(docker: &Docker, args: &[String], remove: bool) -> Result<(),CliError> {
+    let args = Arguments::parse(args,&[],&[])?;
+    let found = container::find(docker,args.name()?).await?;
+    if remove {
+        container::remove(docker,&found.id).await?;
+    }
+    else {
+        container::stop(docker,&found.id).await?;
+    }
+    if args.json {
+        let mut summary = ContainerSummary::new(&found,&container::adopted());
+        summary.state = String::from(if remove { "removed" } else { "exited" });
+        print_json(&summary);
+    }
+    Ok(())
+}
+
+/// Runs the subcommand given in `args` and returns the exit code of the process.
+pub async fn run(docker: &Docker, config: &Config, args: &[String]) -> i32 {
+    let result = match args[0].as_str() {
+        "list" | "ls" => list(docker,&args[1..]).await,
+        "attach" => attach(docker,config,&args[1..]).await,
+        "exec" => exec(docker,config,&args[1..]).await,
+        "new" => new(docker,config,&args[1..]).await,
+        "adopt" => adopt(docker,&args[1..]).await,
+        "stop" => stop(docker,&args[1..],false).await,
+        "rm" => stop(docker,&args[1..],true).await,
+        "help" | "--help" | "-h" => {
+            println!("{}",USAGE);
+            return EXIT_SUCCESS;
+        },
+        "version" | "--version" | "-V" => {
+            println!("{}",crate::VERSION);
+            return EXIT_SUCCESS;
+        },
+        x => Err(CliError::Usage(format!("Unknown command \"{}\"",x))),
+    };
+    match result {
+        Ok(_) => EXIT_SUCCESS,
+        Err(CliError::Usage(error)) => {
+            eprintln!("{}\n\n{}",error,USAGE);
+            EXIT_USAGE
+        },
+        Err(CliError::Failed(error)) => {
+            eprintln!("{}",error);
+            EXIT_FAILURE
+        },
+    }
+}
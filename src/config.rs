@@ -0,0 +1,261 @@
+use std::collections::BTreeMap;
+use std::path::{Path,PathBuf};
+use serde::Deserialize;
+use crate::container;
+use crate::session;
+
+/// Name of the per-project configuration file looked up in the working directory.
+pub const PROJECT_FILE: &str = ".dde.toml";
+
+/// Defaults used when creating new containers.
+#[derive(Clone)]
+pub struct Config {
+    pub image: String,
+    pub entry_command: String,
+    pub import_ssh_keys: bool,
+    pub import_git_config: bool,
+    pub auto_remove: bool,
+    pub mount_target: String,
+    /// Make searches case sensitive if they contain an uppercase character.
+    pub smart_case: bool,
+    /// The key sequence to detach from a session in docker's format, e.g. `ctrl-p,ctrl-q`.
+    pub detach_keys: String,
+    pub profiles: BTreeMap<String,Profile>,
+}
+
+/// A named environment template, e.g. `[profiles.rust]`. Unset values fall back
+/// to the defaults of the configuration.
+#[derive(Deserialize,Clone,Default)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub image: Option<String>,
+    pub entry_command: Option<String>,
+    /// Environment variables in the form `NAME=value`.
+    #[serde(default)]
+    pub env: Vec<String>,
+    /// Additional volumes in the form `host:container[:options]`.
+    #[serde(default)]
+    pub mounts: Vec<String>,
+    /// Published ports in the form `[host:]container[/protocol]`.
+    #[serde(default)]
+    pub ports: Vec<String>,
+}
+
+impl Profile {
+    /// One line overview of everything the profile adds.
+    pub fn describe(&self) -> String {
+        format!("{} env vars, {} mounts, {} ports",self.env.len(),self.mounts.len(),self.ports.len())
+    }
+
+    fn validate(&self) -> Result<(),String> {
+        if let Some(image) = &self.image {
+            validate_image(image)?;
+        }
+        if let Some(command) = &self.entry_command {
+            if command.trim().is_empty() {
+                return Err(String::from("\"entry_command\" must not be empty"));
+            }
+        }
+        for env in self.env.iter() {
+            if !env.contains('=') || env.starts_with('=') {
+                return Err(format!("\"{}\" is not of the form NAME=value",env));
+            }
+        }
+        for mount in self.mounts.iter() {
+            container::parse_mount(mount)?;
+        }
+        for port in self.ports.iter() {
+            container::parse_port(port)?;
+        }
+        Ok(())
+    }
+}
+
+fn validate_image(image: &str) -> Result<(),String> {
+    if image.trim().is_empty() || image.contains(char::is_whitespace) {
+        return Err(format!("\"image\" must be a valid image name, got \"{}\"",image));
+    }
+    Ok(())
+}
+
+/// The contents of a single configuration file, every key is optional so that
+/// `.dde.toml` only has to contain what it overrides.
+#[derive(Deserialize,Default)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    image: Option<String>,
+    entry_command: Option<String>,
+    import_ssh_keys: Option<bool>,
+    import_git_config: Option<bool>,
+    auto_remove: Option<bool>,
+    mount_target: Option<String>,
+    smart_case: Option<bool>,
+    detach_keys: Option<String>,
+    profiles: Option<BTreeMap<String,Profile>>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            image: String::from("shadowitaly/neovim_arch:latest"),
+            entry_command: String::from("/bin/zsh"),
+            import_ssh_keys: true,
+            import_git_config: true,
+            auto_remove: false,
+            mount_target: String::from("/root/mounted"),
+            smart_case: false,
+            detach_keys: String::from(session::DEFAULT_DETACH_KEYS),
+            profiles: BTreeMap::new(),
+        }
+    }
+}
+
+impl ConfigFile {
+    fn read(path: &Path) -> Result<Option<ConfigFile>,String> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content).map(Some).map_err(|e| format!("Invalid configuration file \"{}\": {}",path.display(),e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Could not read configuration file \"{}\": {}",path.display(),e)),
+        }
+    }
+}
+
+impl Config {
+    /// Location of the global configuration file, `~/.config/dde/config.toml`.
+    pub fn global_path() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(x) if !x.is_empty() => PathBuf::from(x),
+            _ => home::home_dir()?.join(".config"),
+        };
+        Some(base.join("dde").join("config.toml"))
+    }
+
+    /// Directory of the state dde keeps between sessions, `~/.local/share/dde`.
+    pub fn data_dir() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_DATA_HOME") {
+            Some(x) if !x.is_empty() => PathBuf::from(x),
+            _ => home::home_dir()?.join(".local").join("share"),
+        };
+        Some(base.join("dde"))
+    }
+
+    /// Loads the global configuration and applies the overrides of the project
+    /// file in the current working directory.
+    pub fn load() -> Result<Config,String> {
+        let mut files = Vec::new();
+        if let Some(path) = Config::global_path() {
+            files.push(path);
+        }
+        if let Ok(dir) = std::env::current_dir() {
+            files.push(dir.join(PROJECT_FILE));
+        }
+        Config::load_from(&files)
+    }
+
+    /// Applies the existing files of `files` in order over the defaults.
+    fn load_from(files: &[PathBuf]) -> Result<Config,String> {
+        let mut config = Config::default();
+        for path in files.iter() {
+            if let Some(file) = ConfigFile::read(path)? {
+                config.apply(file);
+                config.validate().map_err(|e| format!("Invalid configuration file \"{}\": {}",path.display(),e))?;
+            }
+        }
+        Ok(config)
+    }
+
+    fn apply(&mut self, file: ConfigFile) {
+        if let Some(x) = file.image { self.image = x; }
+        if let Some(x) = file.entry_command { self.entry_command = x; }
+        if let Some(x) = file.import_ssh_keys { self.import_ssh_keys = x; }
+        if let Some(x) = file.import_git_config { self.import_git_config = x; }
+        if let Some(x) = file.auto_remove { self.auto_remove = x; }
+        if let Some(x) = file.mount_target { self.mount_target = x; }
+        if let Some(x) = file.smart_case { self.smart_case = x; }
+        if let Some(x) = file.detach_keys { self.detach_keys = x; }
+        if let Some(x) = file.profiles { self.profiles.extend(x); }
+    }
+
+    fn validate(&self) -> Result<(),String> {
+        validate_image(&self.image)?;
+        if self.entry_command.trim().is_empty() {
+            return Err(String::from("\"entry_command\" must not be empty"));
+        }
+        let target = Path::new(&self.mount_target);
+        if !target.is_absolute() || target.parent().is_none() {
+            return Err(format!("\"mount_target\" must be an absolute path below \"/\", got \"{}\"",self.mount_target));
+        }
+        session::parse_detach_keys(&self.detach_keys).map_err(|e| format!("\"detach_keys\": {}",e))?;
+        for (name,profile) in self.profiles.iter() {
+            profile.validate().map_err(|e| format!("profile \"{}\": {}",name,e))?;
+        }
+        Ok(())
+    }
+
+    /// The bytes of the detach sequence, see `detach_keys`.
+    pub fn detach_sequence(&self) -> Vec<u8> {
+        session::parse_detach_keys(&self.detach_keys).unwrap_or_default()
+    }
+
+    pub fn profile(&self, name: &str) -> Result<&Profile,String> {
+        self.profiles.get(name).ok_or_else(|| format!("Unknown profile \"{}\"",name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, content: &str) -> PathBuf {
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path,content).unwrap();
+        path
+    }
+
+    fn load(name: &str, content: &str) -> Result<Config,String> {
+        let dir = std::env::temp_dir().join(format!("dde-config-{}-{}",name,std::process::id()));
+        let result = Config::load_from(&[write(&dir,"config.toml",content)]);
+        std::fs::remove_dir_all(&dir).unwrap();
+        result
+    }
+
+    fn error(name: &str, content: &str) -> String {
+        load(name,content).err().expect("the configuration should be rejected")
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(error("unknown","imgae = \"ubuntu\"").contains("unknown field"));
+        assert!(error("unknown-profile","[profiles.rust]\nport = [\"80\"]").contains("unknown field"));
+        assert!(load("type","auto_remove = \"yes\"").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(error("image","image = \"my image\"").contains("\"image\""));
+        assert!(error("command","entry_command = \" \"").contains("\"entry_command\""));
+        assert!(error("target","mount_target = \"mounted\"").contains("\"mount_target\""));
+        assert!(error("target-root","mount_target = \"/\"").contains("\"mount_target\""));
+        assert!(error("detach","detach_keys = \"ctrl-\"").contains("\"detach_keys\""));
+        assert!(error("env","[profiles.rust]\nenv = [\"=1\"]").contains("profile \"rust\""));
+        assert!(error("ports","[profiles.rust]\nports = [\"http\"]").contains("profile \"rust\""));
+    }
+
+    #[test]
+    fn project_file_overrides_global_config() {
+        let dir = std::env::temp_dir().join(format!("dde-config-merge-{}",std::process::id()));
+        let global = write(&dir,"config.toml","image = \"ubuntu\"\nauto_remove = true\nsmart_case = true\n[profiles.rust]\nimage = \"rust\"\n[profiles.go]\nimage = \"golang\"\n");
+        let project = write(&dir,PROJECT_FILE,"image = \"debian\"\nsmart_case = false\n[profiles.rust]\nimage = \"rust:slim\"\n");
+        let missing = dir.join("missing.toml");
+        let config = Config::load_from(&[global,project,missing]).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(config.image,"debian");
+        assert!(config.auto_remove);
+        assert!(!config.smart_case);
+        assert_eq!(config.entry_command,Config::default().entry_command);
+        assert_eq!(config.profile("rust").unwrap().image.as_deref(),Some("rust:slim"));
+        assert_eq!(config.profile("go").unwrap().image.as_deref(),Some("golang"));
+    }
+}
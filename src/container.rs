@@ -0,0 +1,379 @@
+use shiplift::{Docker,ContainerListOptions,ContainerOptions,RmContainerOptions,rep};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use crate::session;
+use crate::config::Config;
+
+/// Prefix of the names of all containers created by dde.
+pub const NAME_PREFIX: &str = "dde_";
+
+/// Labels stamped on every container created by dde.
+pub const LABEL_MANAGED: &str = "dde.managed";
+pub const LABEL_VERSION: &str = "dde.version";
+pub const LABEL_WORKSPACE: &str = "dde.workspace";
+pub const LABEL_PROFILE: &str = "dde.profile";
+
+#[derive(Clone)]
+pub enum WorkingDirectorySetup {
+    MountDirectory(String),
+    CopyDirectory(String),
+    DontUse,
+}
+
+/// Everything needed to create a new development environment.
+pub struct ContainerSetup {
+    pub name: String,
+    pub image: String,
+    pub entry_command: String,
+    pub auto_remove: bool,
+    pub import_keys: bool,
+    pub git_config: bool,
+    pub working_dir: WorkingDirectorySetup,
+    pub mount_target: String,
+    pub env: Vec<String>,
+    pub mounts: Vec<String>,
+    pub ports: Vec<String>,
+    /// Name of the profile the setup is based on.
+    pub profile: Option<String>,
+}
+
+impl ContainerSetup {
+    /// The name of the container in docker.
+    pub fn container_name(&self) -> String {
+        String::from(NAME_PREFIX)+&self.name
+    }
+
+    /// The volumes of the container in the form `host:container[:options]`: the
+    /// mounted working directory, the SSH keys, the git config and the mounts of
+    /// the profile.
+    pub fn volumes(&self) -> Result<Vec<String>,String> {
+        let mut volumes = match &self.working_dir {
+            WorkingDirectorySetup::MountDirectory(x) => vec![x.clone()+":"+&self.mount_target+":Z"],
+            _ => Vec::new(),
+        };
+        if self.import_keys {
+            let home_dir = home::home_dir().ok_or("Could not find the home directory")?.join(".ssh").to_string_lossy().to_string();
+            volumes.push(home_dir+":/root/.ssh:Z");
+        }
+        if self.git_config {
+            let home_dir = home::home_dir().ok_or("Could not find the home directory")?.join(".gitconfig").to_string_lossy().to_string();
+            volumes.push(home_dir+":/root/.gitconfig:Z");
+        }
+        for mount in self.mounts.iter() {
+            volumes.push(parse_mount(mount)?);
+        }
+        Ok(volumes)
+    }
+
+    /// The entry command split into its arguments.
+    pub fn command(&self) -> Vec<&str> {
+        self.entry_command.split(' ').collect()
+    }
+
+    /// The labels which mark the container as managed by dde.
+    pub fn labels(&self) -> Vec<(&'static str,String)> {
+        let workspace = match &self.working_dir {
+            WorkingDirectorySetup::MountDirectory(x) | WorkingDirectorySetup::CopyDirectory(x) => std::fs::canonicalize(x).map(|y| y.to_string_lossy().to_string()).unwrap_or_else(|_| x.clone()),
+            WorkingDirectorySetup::DontUse => String::new(),
+        };
+        let mut labels = vec![
+            (LABEL_MANAGED,String::from("true")),
+            (LABEL_VERSION,String::from(env!("CARGO_PKG_VERSION"))),
+            (LABEL_WORKSPACE,workspace),
+        ];
+        if let Some(profile) = &self.profile {
+            labels.push((LABEL_PROFILE,profile.clone()));
+        }
+        labels
+    }
+
+    /// The environment of the container, the time zone of the host followed by
+    /// the variables of the profile.
+    pub fn env(&self) -> Vec<String> {
+        let mut env = match std::fs::read_to_string("/etc/timezone") {
+            Ok(x) => vec![format!("TZ={}",x.trim())],
+            Err(_) => Vec::new(),
+        };
+        env.extend(self.env.iter().cloned());
+        env
+    }
+}
+
+/// Machine readable description of a container, used for `--json` output.
+#[derive(Serialize)]
+pub struct ContainerSummary {
+    pub id: String,
+    pub name: String,
+    pub image: String,
+    pub state: String,
+    pub status: String,
+    pub managed: bool,
+    pub workspace: Option<String>,
+    pub profile: Option<String>,
+}
+
+impl ContainerSummary {
+    /// `adopted` are the ids returned by `adopted`, read once by the caller.
+    pub fn new(container: &rep::Container, adopted: &[String]) -> ContainerSummary {
+        ContainerSummary {
+            id: container.id.clone(),
+            name: display_name(container).to_owned(),
+            image: container.image.clone(),
+            state: container.state.clone(),
+            status: container.status.clone(),
+            managed: is_managed(container,adopted),
+            workspace: container.labels.get(LABEL_WORKSPACE).cloned(),
+            profile: container.labels.get(LABEL_PROFILE).cloned(),
+        }
+    }
+}
+
+/// Parses a volume of the form `host:container[:options]`, expanding a leading
+/// `~` of the host path. Returns the volume in the form docker expects.
+pub fn parse_mount(spec: &str) -> Result<String,String> {
+    let parts = spec.splitn(3,':').collect::<Vec<&str>>();
+    if parts.len() < 2 || parts[0].is_empty() || !parts[1].starts_with('/') {
+        return Err(format!("\"{}\" is not of the form host:container[:options]",spec));
+    }
+    if parts[0] == "~" || parts[0].starts_with("~/") {
+        let home = home::home_dir().ok_or("Could not find the home directory")?;
+        let rest = parts[0].trim_start_matches('~').trim_start_matches('/');
+        let host = if rest.is_empty() { home } else { home.join(rest) };
+        let mut result = host.to_string_lossy().to_string();
+        for part in parts[1..].iter() {
+            result += ":";
+            result += part;
+        }
+        return Ok(result);
+    }
+    Ok(spec.to_owned())
+}
+
+/// Checks a container name against the rules of docker: at least two letters,
+/// digits, `_`, `.` or `-`, starting with a letter or digit.
+pub fn validate_name(name: &str) -> Result<(),String> {
+    if name.is_empty() {
+        return Err(String::from("The container name cannot be empty"));
+    }
+    if !name.starts_with(|x: char| x.is_ascii_alphanumeric()) {
+        return Err(String::from("The container name has to start with a letter or digit"));
+    }
+    if let Some(x) = name.chars().find(|x| !x.is_ascii_alphanumeric() && *x != '_' && *x != '.' && *x != '-') {
+        return Err(format!("The container name cannot contain \"{}\"",x));
+    }
+    if name.len() < 2 {
+        return Err(String::from("The container name needs at least two characters"));
+    }
+    Ok(())
+}
+
+/// Parses a port of the form `[host:]container[/protocol]` into
+/// `(container port, protocol, host port)`. Ranges and host addresses are
+/// rejected as containers are created with one binding per port.
+pub fn parse_port(spec: &str) -> Result<(u32,String,u32),String> {
+    let error = || format!("\"{}\" is not of the form [host:]container[/protocol]",spec);
+    let (ports,protocol) = match spec.find('/') {
+        Some(x) => (&spec[..x],&spec[x+1..]),
+        None => (spec,"tcp"),
+    };
+    if protocol != "tcp" && protocol != "udp" {
+        return Err(error());
+    }
+    let ports = ports.split(':').collect::<Vec<&str>>();
+    let is_range = |x: &&str| matches!(x.split_once('-'),Some((a,b)) if !a.is_empty() && !b.is_empty() && a.chars().chain(b.chars()).all(|y| y.is_ascii_digit()));
+    if ports.iter().any(is_range) {
+        return Err(format!("\"{}\": port ranges are not supported",spec));
+    }
+    if ports.len() == 3 {
+        return Err(format!("\"{}\": binding to a host address is not supported",spec));
+    }
+    let ports = ports.iter().map(|x| x.parse::<u16>().ok().filter(|y| *y != 0).map(u32::from)).collect::<Option<Vec<u32>>>().ok_or_else(error)?;
+    match ports[..] {
+        [port] => Ok((port,protocol.to_owned(),port)),
+        [host,container] => Ok((container,protocol.to_owned(),host)),
+        _ => Err(error()),
+    }
+}
+
+/// The name of the container without the leading slash docker reports.
+pub fn display_name(container: &rep::Container) -> &str {
+    container.names.first().map(|x| x.trim_start_matches('/')).unwrap_or(&container.id)
+}
+
+/// Location of the list of adopted containers, `~/.local/share/dde/adopted`.
+fn adopted_path() -> Option<PathBuf> {
+    Some(Config::data_dir()?.join("adopted"))
+}
+
+/// Ids of the containers adopted with `adopt`.
+pub fn adopted() -> Vec<String> {
+    adopted_path().and_then(|x| std::fs::read_to_string(x).ok()).map(|x| x.lines().map(String::from).collect()).unwrap_or_default()
+}
+
+/// Whether the container was created by dde, either labelled or from a version
+/// that only prefixed the name, or was adopted.
+pub fn is_managed(container: &rep::Container, adopted: &[String]) -> bool {
+    container.labels.get(LABEL_MANAGED).map(|x| x == "true").unwrap_or(false)
+        || container.names.iter().any(|x| x.trim_start_matches('/').starts_with(NAME_PREFIX))
+        || adopted.contains(&container.id)
+}
+
+/// Lists the containers managed by dde or, with `all`, every container on the host.
+pub async fn list(docker: &Docker, all: bool) -> Result<Vec<rep::Container>,String> {
+    let opts = ContainerListOptions::builder().all().build();
+    let mut containers = docker.containers().list(&opts).await.map_err(|e| format!("Could not list containers: {}",e))?;
+    if !all {
+        let adopted = adopted();
+        containers.retain(|x| is_managed(x,&adopted));
+    }
+    Ok(containers)
+}
+
+/// Marks a container which was not created by dde as managed. Docker cannot
+/// change the labels of existing containers, so the id is recorded locally.
+pub fn adopt(id: &str) -> Result<(),String> {
+    let path = adopted_path().ok_or("Could not find the home directory")?;
+    let mut ids = adopted();
+    if ids.iter().any(|x| x == id) {
+        return Ok(());
+    }
+    ids.push(id.to_owned());
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Could not create \"{}\": {}",parent.display(),e))?;
+    }
+    std::fs::write(&path,ids.join("\n")+"\n").map_err(|e| format!("Could not write \"{}\": {}",path.display(),e))
+}
+
+/// Looks up a container by its exact name, with or without the `dde_` prefix, or
+/// else by a prefix of its id matching no other container. Containers not
+/// managed by dde are found as well so that they can be adopted.
+pub async fn find(docker: &Docker, name: &str) -> Result<rep::Container,String> {
+    let name = name.trim_start_matches('/');
+    let prefixed = String::from(NAME_PREFIX)+name;
+    let mut containers = list(docker,true).await?;
+    let named = |exact: &str| containers.iter().position(|x| x.names.iter().any(|y| y.trim_start_matches('/') == exact));
+    if let Some(idx) = named(name).or_else(|| named(&prefixed)) {
+        return Ok(containers.swap_remove(idx));
+    }
+    containers.retain(|x| !name.is_empty() && x.id.starts_with(name));
+    match containers.len() {
+        0 => Err(format!("No such container \"{}\"",name)),
+        1 => Ok(containers.remove(0)),
+        count => Err(format!("\"{}\" is ambiguous, it is the beginning of {} container ids",name,count)),
+    }
+}
+
+/// Creates the container described by `setup` and returns its id.
+pub async fn create(docker: &Docker, setup: &ContainerSetup) -> Result<String,String> {
+    let container_name = setup.container_name();
+    let volumes = setup.volumes()?;
+    let env = setup.env();
+    let labels = setup.labels();
+    let labels = labels.iter().map(|(name,value)| (*name,value.as_str())).collect::<HashMap<&str,&str>>();
+    let mut builder = ContainerOptions::builder(&setup.image);
+    builder.labels(&labels).auto_remove(setup.auto_remove).name(&container_name).cmd(setup.command()).tty(true).env(&env).attach_stdin(true).attach_stderr(true).attach_stdout(true).volumes(volumes.iter().map(|x| &x[..]).collect());
+    for port in setup.ports.iter() {
+        let (container_port,protocol,host_port) = parse_port(port)?;
+        builder.expose(container_port,&protocol,host_port);
+    }
+    let opts = builder.build();
+
+    let info = docker.containers().create(&opts).await.map_err(|e| format!("Could not create container \"{}\": {}",container_name,e))?;
+    Ok(info.id)
+}
+
+/// An additional process to run in a container, empty values fall back to the
+/// entry command, user and working directory of the container.
+#[derive(Clone,Default)]
+pub struct ExecSetup {
+    pub command: Vec<String>,
+    pub user: String,
+    pub working_dir: String,
+}
+
+/// Starts the container unless it is already running.
+async fn ensure_running(docker: &Docker, id: &str) -> Result<rep::ContainerDetails,String> {
+    let container = docker.containers().get(id);
+    let inspection = container.inspect().await.map_err(|e| format!("Could not inspect container: {}",e))?;
+    if !inspection.state.running {
+        container.start().await.map_err(|e| format!("Could not start container: {}",e))?;
+    }
+    Ok(inspection)
+}
+
+/// Starts the container if necessary and attaches the current terminal to it
+/// until the user types the `detach` sequence.
+pub async fn start_and_attach(docker: &Docker, id: &str, detach: &[u8]) -> Result<(),String> {
+    let inspection = ensure_running(docker,id).await?;
+    session::attach(id,inspection.config.tty,detach).await
+}
+
+/// Starts the container if necessary and runs a new process with its own TTY in
+/// it, the process is terminated once the session ends.
+pub async fn start_and_exec(docker: &Docker, id: &str, setup: &ExecSetup, detach: &[u8]) -> Result<(),String> {
+    let inspection = ensure_running(docker,id).await?;
+    let command = if setup.command.is_empty() {
+        inspection.config.cmd.unwrap_or_default()
+    }
+    else {
+        setup.command.clone()
+    };
+    if command.is_empty() {
+        return Err(String::from("No command to execute"));
+    }
+    session::exec(id,&command,&setup.user,&setup.working_dir,detach).await
+}
+
+pub async fn stop(docker: &Docker, id: &str) -> Result<(),String> {
+    docker.containers().get(id).stop(None).await.map_err(|e| format!("Could not stop container: {}",e))
+}
+
+/// Stops and removes the container.
+pub async fn remove(docker: &Docker, id: &str) -> Result<(),String> {
+    let _ = docker.containers().get(id).stop(None).await;
+    let opts = RmContainerOptions::builder().build();
+    docker.containers().get(id).remove(opts).await.map_err(|e| format!("Could not remove container: {}",e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ports() {
+        assert_eq!(parse_port("8080"),Ok((8080,String::from("tcp"),8080)));
+        assert_eq!(parse_port("8080:80"),Ok((80,String::from("tcp"),8080)));
+        assert_eq!(parse_port("5353:53/udp"),Ok((53,String::from("udp"),5353)));
+        assert!(parse_port("127.0.0.1:8080:80").unwrap_err().contains("host address"));
+        assert!(parse_port("8000-8010:8000-8010").unwrap_err().contains("ranges"));
+        assert!(parse_port("3000-3001/udp").unwrap_err().contains("ranges"));
+        for spec in ["","http","80/sctp","80/","0","65536",":80","80:","-80","80:http"].iter() {
+            assert!(parse_port(spec).unwrap_err().contains("[host:]container[/protocol]"),"{} is not a valid port",spec);
+        }
+    }
+
+    #[test]
+    fn parses_mounts() {
+        assert_eq!(parse_mount("/data:/data"),Ok(String::from("/data:/data")));
+        assert_eq!(parse_mount("cache:/root/.cache:ro"),Ok(String::from("cache:/root/.cache:ro")));
+        let home = home::home_dir().unwrap();
+        assert_eq!(parse_mount("~/.cargo:/root/.cargo:ro"),Ok(format!("{}:/root/.cargo:ro",home.join(".cargo").display())));
+        assert_eq!(parse_mount("~:/home"),Ok(format!("{}:/home",home.display())));
+        for spec in ["","/data",":/data","/data:data","/data:"].iter() {
+            assert!(parse_mount(spec).is_err(),"{} is not a valid mount",spec);
+        }
+    }
+
+    #[test]
+    fn validates_names() {
+        for name in ["ab","my-app_2.0","0x"].iter() {
+            assert_eq!(validate_name(name),Ok(()));
+        }
+        assert!(validate_name("").unwrap_err().contains("empty"));
+        assert!(validate_name("a").unwrap_err().contains("two characters"));
+        assert!(validate_name("-app").unwrap_err().contains("start with"));
+        assert!(validate_name("my app").unwrap_err().contains("\" \""));
+        assert!(validate_name("app/x").unwrap_err().contains("\"/\""));
+    }
+}
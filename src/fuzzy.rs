@@ -0,0 +1,190 @@
+//! Fuzzy matching with fzf style scoring.
+//!
+//! The pattern matches a text if all its characters appear in the text in the
+//! same order. Among the possible matches the shortest one ending at the first
+//! complete match is scored, rewarding consecutive characters and characters at
+//! the start of words and penalizing gaps.
+
+const SCORE_MATCH: i64 = 16;
+const SCORE_GAP_START: i64 = -3;
+const SCORE_GAP_EXTENSION: i64 = -1;
+const BONUS_BOUNDARY: i64 = SCORE_MATCH/2;
+const BONUS_NON_WORD: i64 = SCORE_MATCH/2;
+const BONUS_CAMEL: i64 = BONUS_BOUNDARY+SCORE_GAP_EXTENSION;
+const BONUS_CONSECUTIVE: i64 = -(SCORE_GAP_START+SCORE_GAP_EXTENSION);
+const BONUS_FIRST_CHAR_MULTIPLIER: i64 = 2;
+
+#[derive(Clone,Copy,PartialEq)]
+enum CharClass {
+    Lower,
+    Upper,
+    Number,
+    Letter,
+    NonWord,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c.is_lowercase() {
+        CharClass::Lower
+    }
+    else if c.is_uppercase() {
+        CharClass::Upper
+    }
+    else if c.is_numeric() {
+        CharClass::Number
+    }
+    else if c.is_alphabetic() {
+        CharClass::Letter
+    }
+    else {
+        CharClass::NonWord
+    }
+}
+
+fn bonus(previous: CharClass, current: CharClass) -> i64 {
+    match (previous,current) {
+        (CharClass::NonWord,x) if x != CharClass::NonWord => BONUS_BOUNDARY,
+        (CharClass::Lower,CharClass::Upper) => BONUS_CAMEL,
+        (x,CharClass::Number) if x != CharClass::Number => BONUS_CAMEL,
+        (_,CharClass::NonWord) => BONUS_NON_WORD,
+        _ => 0,
+    }
+}
+
+fn normalize(c: char, case_sensitive: bool) -> char {
+    if case_sensitive { c } else { c.to_lowercase().next().unwrap_or(c) }
+}
+
+/// Whether a search for `pattern` is case sensitive. Searches ignore the case
+/// unless `smart_case` is set and the pattern contains an uppercase character.
+pub fn is_case_sensitive(pattern: &str, smart_case: bool) -> bool {
+    smart_case && pattern.chars().any(char::is_uppercase)
+}
+
+pub struct Match {
+    pub score: i64,
+    /// Indices of the matched characters in the text.
+    pub positions: Vec<usize>,
+}
+
+/// Matches `pattern` against `text`, returns `None` if the text does not
+/// contain all characters of the pattern in order.
+pub fn fuzzy_match(pattern: &str, text: &str, case_sensitive: bool) -> Option<Match> {
+    let pattern = pattern.chars().map(|x| normalize(x,case_sensitive)).collect::<Vec<char>>();
+    let original = text.chars().collect::<Vec<char>>();
+    let text = original.iter().map(|x| normalize(*x,case_sensitive)).collect::<Vec<char>>();
+    if pattern.is_empty() {
+        return Some(Match { score: 0, positions: Vec::new() });
+    }
+
+    // Find the first complete match, then walk backwards to make it as short as possible.
+    let mut pattern_idx = 0;
+    let mut start = None;
+    let mut end = 0;
+    for (idx,c) in text.iter().enumerate() {
+        if *c == pattern[pattern_idx] {
+            start = start.or(Some(idx));
+            pattern_idx += 1;
+            if pattern_idx == pattern.len() {
+                end = idx+1;
+                break;
+            }
+        }
+    }
+    if pattern_idx < pattern.len() {
+        return None;
+    }
+    let mut start = start?;
+    let mut pattern_idx = pattern.len()-1;
+    for idx in (start..end).rev() {
+        if text[idx] == pattern[pattern_idx] {
+            if pattern_idx == 0 {
+                start = idx;
+                break;
+            }
+            pattern_idx -= 1;
+        }
+    }
+
+    let mut score = 0;
+    let mut positions = Vec::new();
+    let mut in_gap = false;
+    let mut consecutive = 0;
+    let mut first_bonus = 0;
+    let mut previous = if start > 0 { char_class(original[start-1]) } else { CharClass::NonWord };
+    let mut pattern_idx = 0;
+    for idx in start..end {
+        let class = char_class(original[idx]);
+        if pattern_idx < pattern.len() && text[idx] == pattern[pattern_idx] {
+            let mut char_bonus = bonus(previous,class);
+            if consecutive == 0 {
+                first_bonus = char_bonus;
+            }
+            else {
+                if char_bonus == BONUS_BOUNDARY {
+                    first_bonus = char_bonus;
+                }
+                char_bonus = char_bonus.max(first_bonus).max(BONUS_CONSECUTIVE);
+            }
+            score += SCORE_MATCH+if pattern_idx == 0 { char_bonus*BONUS_FIRST_CHAR_MULTIPLIER } else { char_bonus };
+            positions.push(idx);
+            in_gap = false;
+            consecutive += 1;
+            pattern_idx += 1;
+        }
+        else {
+            score += if in_gap { SCORE_GAP_EXTENSION } else { SCORE_GAP_START };
+            in_gap = true;
+            consecutive = 0;
+            first_bonus = 0;
+        }
+        previous = class;
+    }
+    Some(Match { score, positions })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(pattern: &str, text: &str) -> i64 {
+        fuzzy_match(pattern,text,false).map(|x| x.score).unwrap()
+    }
+
+    #[test]
+    fn requires_all_characters_in_order() {
+        assert!(fuzzy_match("ba","abc",false).is_none());
+        assert!(fuzzy_match("abcd","abc",false).is_none());
+        assert!(fuzzy_match("ac","abc",false).is_some());
+    }
+
+    #[test]
+    fn empty_pattern_matches_everything() {
+        let matched = fuzzy_match("","abc",false).unwrap();
+        assert_eq!(matched.score,0);
+        assert!(matched.positions.is_empty());
+    }
+
+    #[test]
+    fn positions_of_the_shortest_match() {
+        assert_eq!(fuzzy_match("ab","a_ab",false).unwrap().positions,vec![2,3]);
+        // Positions count characters, not bytes.
+        assert_eq!(fuzzy_match("üb","aüb",false).unwrap().positions,vec![1,2]);
+    }
+
+    #[test]
+    fn prefers_word_boundaries_and_consecutive_characters() {
+        assert!(score("api","my-api") > score("api","rapid"));
+        assert!(score("ab","abx") > score("ab","axb"));
+        assert!(score("fb","fooBar") > score("fb","foobar"));
+    }
+
+    #[test]
+    fn case_sensitivity() {
+        assert!(fuzzy_match("API","my-api",false).is_some());
+        assert!(fuzzy_match("API","my-api",true).is_none());
+        assert!(is_case_sensitive("Api",true));
+        assert!(!is_case_sensitive("api",true));
+        assert!(!is_case_sensitive("Api",false));
+    }
+}
@@ -0,0 +1,389 @@
+//! Listing, pulling, tagging and removing images, pulls report the progress of
+//! each layer and use the registry credentials of the docker CLI.
+use std::collections::HashMap;
+use std::path::PathBuf;
+use chrono::{DateTime,Utc};
+use futures::StreamExt;
+use serde_json::Value;
+use shiplift::{Docker,ImageListOptions,PullOptions,RegistryAuth,TagOptions,rep};
+use crate::session;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// The key of Docker Hub in `config.json`.
+const DOCKER_HUB: &str = "https://index.docker.io/v1/";
+const DOCKER_HUB_HOSTS: [&str; 3] = ["index.docker.io","docker.io","registry-1.docker.io"];
+
+/// Splits `image:tag` into its parts, the colon of a registry port is not a tag
+/// and images referenced by digest have none.
+pub fn split_tag(image: &str) -> (&str,Option<&str>) {
+    match image.rfind(':') {
+        Some(x) if !image[x..].contains('/') && !image.contains('@') => (&image[..x],Some(&image[x+1..])),
+        _ => (image,None),
+    }
+}
+
+/// The registry `image` is pulled from, `None` for Docker Hub.
+pub fn registry(image: &str) -> Option<&str> {
+    match image.find('/') {
+        Some(x) if image[..x].contains('.') || image[..x].contains(':') || &image[..x] == "localhost" => Some(&image[..x]),
+        _ => None,
+    }
+}
+
+/// Whether `component` is a valid path component of a repository: lowercase
+/// letters and digits, separated by a single `.` or `_`, two `_` or any number of `-`.
+fn valid_path_component(component: &str) -> bool {
+    let alphanumeric = |x: char| x.is_ascii_lowercase() || x.is_ascii_digit();
+    let valid_separator = |x: &str| x == "." || x == "_" || x == "__" || x.chars().all(|y| y == '-');
+    component.starts_with(alphanumeric) && component.ends_with(alphanumeric)
+        && component.split(alphanumeric).filter(|x| !x.is_empty()).all(valid_separator)
+}
+
+/// Whether `domain` is a valid registry host with an optional port.
+fn valid_domain(domain: &str) -> bool {
+    let (host,port) = match domain.rfind(':') {
+        Some(x) => (&domain[..x],Some(&domain[x+1..])),
+        None => (domain,None),
+    };
+    let valid_label = |x: &str| !x.is_empty() && !x.starts_with('-') && !x.ends_with('-') && x.chars().all(|y| y.is_ascii_alphanumeric() || y == '-');
+    host.split('.').all(valid_label) && port.map(|x| !x.is_empty() && x.chars().all(|y| y.is_ascii_digit())).unwrap_or(true)
+}
+
+/// Checks that `image` is a well-formed reference like `registry:5000/team/app:1.0`
+/// or `ubuntu@sha256:<digest>`, returns the reason if it is not.
+pub fn validate_reference(image: &str) -> Result<(),String> {
+    if image.is_empty() {
+        return Err(String::from("The image name cannot be empty"));
+    }
+    let (image,digest) = match image.find('@') {
+        Some(x) => (&image[..x],Some(&image[x+1..])),
+        None => (image,None),
+    };
+    if let Some(digest) = digest {
+        let (algorithm,hex) = digest.split_at(digest.find(':').ok_or_else(|| format!("Invalid digest \"{}\", expected <algorithm>:<hex>",digest))?);
+        if algorithm.is_empty() || hex.len() < 33 || !hex[1..].chars().all(|x| x.is_ascii_hexdigit()) {
+            return Err(format!("Invalid digest \"{}\", expected <algorithm>:<hex>",digest));
+        }
+    }
+    let (name,tag) = split_tag(image);
+    if let Some(tag) = tag {
+        let valid = !tag.is_empty() && tag.len() <= 128 && !tag.starts_with(['.','-']) && tag.chars().all(|x| x.is_ascii_alphanumeric() || x == '_' || x == '.' || x == '-');
+        if !valid {
+            return Err(format!("Invalid tag \"{}\", tags consist of up to 128 letters, digits, _, . and -",tag));
+        }
+    }
+    if name.len() > 255 {
+        return Err(String::from("The repository name is longer than 255 characters"));
+    }
+    let path = match registry(name) {
+        Some(domain) if !valid_domain(domain) => return Err(format!("Invalid registry \"{}\"",domain)),
+        Some(domain) => &name[domain.len()+1..],
+        None => name,
+    };
+    match path.split('/').find(|x| !valid_path_component(x)) {
+        Some(x) if x.chars().any(|y| y.is_ascii_uppercase()) => Err(format!("Invalid repository name \"{}\", it must be lowercase",name)),
+        Some(_) => Err(format!("Invalid repository name \"{}\"",name)),
+        None => Ok(()),
+    }
+}
+
+/// Asks the registry of `image` whether it can be pulled, with the credentials
+/// of the docker CLI. Returns the reason if it cannot.
+pub async fn check_pullable(image: &str) -> Result<(),String> {
+    let auth = registry_auth(image).await.map(|x| x.serialize());
+    session::get_registry_json(&format!("/distribution/{}/json",image),auth.as_deref()).await
+        .map(|_| ()).map_err(|e| format!("{} cannot be pulled: {}",image,e))
+}
+
+/// Whether the image is available locally.
+pub async fn exists(docker: &Docker, image: &str) -> bool {
+    docker.images().get(image).inspect().await.is_ok()
+}
+
+fn config_path() -> Option<PathBuf> {
+    match std::env::var_os("DOCKER_CONFIG") {
+        Some(x) if !x.is_empty() => Some(PathBuf::from(x).join("config.json")),
+        _ => Some(home::home_dir()?.join(".docker").join("config.json")),
+    }
+}
+
+/// The host of a registry as written in `config.json`, e.g. `https://ghcr.io/v2/`.
+fn host(server: &str) -> &str {
+    let server = server.trim_start_matches("https://").trim_start_matches("http://");
+    let host = server.split('/').next().unwrap_or(server);
+    if DOCKER_HUB_HOSTS.contains(&host) { "docker.io" } else { host }
+}
+
+fn auth(username: &str, password: &str, server: &str) -> RegistryAuth {
+    RegistryAuth::builder().username(username).password(password).server_address(server).build()
+}
+
+/// Asks the credential helper `docker-credential-<helper>` for the credentials
+/// of `server`.
+async fn from_helper(helper: &str, server: &str) -> Option<RegistryAuth> {
+    let mut child = Command::new(format!("docker-credential-{}",helper)).arg("get")
+        .stdin(std::process::Stdio::piped()).stdout(std::process::Stdio::piped()).stderr(std::process::Stdio::null())
+        .spawn().ok()?;
+    child.stdin.take()?.write_all(server.as_bytes()).await.ok()?;
+    let output = child.wait_with_output().await.ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let credentials = serde_json::from_slice::<Value>(&output.stdout).ok()?;
+    let (username,secret) = (credentials["Username"].as_str()?,credentials["Secret"].as_str()?);
+    if username == "<token>" {
+        return Some(RegistryAuth::token(secret));
+    }
+    Some(auth(username,secret,server))
+}
+
+/// The credentials for the registry of `image` from `~/.docker/config.json`,
+/// either stored inline or by a credential helper. `None` pulls anonymously.
+pub async fn registry_auth(image: &str) -> Option<RegistryAuth> {
+    let config = std::fs::read_to_string(config_path()?).ok()?;
+    let config = serde_json::from_str::<Value>(&config).ok()?;
+    let wanted = registry(image).map(host).unwrap_or("docker.io");
+    let server = config["auths"].as_object().and_then(|x| x.keys().find(|y| host(y) == wanted).cloned())
+        .unwrap_or_else(|| if wanted == "docker.io" { String::from(DOCKER_HUB) } else { wanted.to_owned() });
+
+    let helper = config["credHelpers"].as_object().and_then(|x| x.iter().find(|(y,_)| host(y) == wanted)).and_then(|(_,y)| y.as_str())
+        .or_else(|| config["credsStore"].as_str());
+    if let Some(helper) = helper {
+        if let Some(auth) = from_helper(helper,&server).await {
+            return Some(auth);
+        }
+    }
+
+    let entry = &config["auths"][&server];
+    if let Some(token) = entry["identitytoken"].as_str() {
+        return Some(RegistryAuth::token(token));
+    }
+    if let (Some(username),Some(password)) = (entry["username"].as_str(),entry["password"].as_str()) {
+        return Some(auth(username,password,&server));
+    }
+    let decoded = base64::decode(entry["auth"].as_str()?).ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (username,password) = decoded.split_at(decoded.find(':')?);
+    Some(auth(username,&password[1..],&server))
+}
+
+/// The state of one layer of an image being pulled.
+struct Layer {
+    id: String,
+    status: String,
+    /// The progress bar docker renders for the layer.
+    bar: String,
+    /// Completed fraction of downloading and extracting the layer.
+    fraction: f64,
+}
+
+/// The progress of a pull, built from the events of the pull stream.
+#[derive(Default)]
+pub struct PullProgress {
+    layers: Vec<Layer>,
+    status: String,
+}
+
+impl PullProgress {
+    fn update(&mut self, event: &Value) {
+        let status = event["status"].as_str().unwrap_or("").to_owned();
+        let id = match event["id"].as_str() {
+            Some(x) if !status.starts_with("Pulling from") => x,
+            _ => {
+                self.status = status;
+                return;
+            }
+        };
+        let idx = match self.layers.iter().position(|x| x.id == id) {
+            Some(x) => x,
+            None => {
+                self.layers.push(Layer { id: id.to_owned(), status: String::new(), bar: String::new(), fraction: 0.0 });
+                self.layers.len()-1
+            }
+        };
+        let layer = &mut self.layers[idx];
+        let detail = &event["progressDetail"];
+        let part = match (detail["current"].as_u64(),detail["total"].as_u64()) {
+            (Some(current),Some(total)) if total > 0 => current as f64/total as f64,
+            _ => 0.0,
+        };
+        // Downloading makes up the first half of a layer, extracting the second.
+        layer.fraction = match status.as_str() {
+            "Downloading" => part/2.0,
+            "Verifying Checksum" | "Download complete" => 0.5,
+            "Extracting" => 0.5+part/2.0,
+            "Pull complete" | "Already exists" => 1.0,
+            _ => layer.fraction,
+        };
+        layer.bar = event["progress"].as_str().unwrap_or("").to_owned();
+        layer.status = status;
+    }
+
+    /// The completed fraction of all layers, `None` until the layers are known.
+    pub fn fraction(&self) -> Option<f64> {
+        if self.layers.is_empty() {
+            return None;
+        }
+        Some(self.layers.iter().map(|x| x.fraction).sum::<f64>()/self.layers.len() as f64)
+    }
+
+    /// A summary line followed by the state of every layer.
+    pub fn lines(&self) -> Vec<String> {
+        let complete = self.layers.iter().filter(|x| x.fraction >= 1.0).count();
+        let mut lines = vec![format!("{} ({}/{} layers complete)",self.status,complete,self.layers.len())];
+        lines.extend(self.layers.iter().map(|x| format!("{}: {} {}",x.id,x.status,x.bar).trim_end().to_owned()));
+        lines
+    }
+}
+
+/// Pulls `image`, with the credentials of the docker CLI if there are any, and
+/// reports the progress to `on_progress`.
+pub async fn pull<F: FnMut(&PullProgress)>(docker: &Docker, image: &str, mut on_progress: F) -> Result<String,String> {
+    let (name,tag) = split_tag(image);
+    let tag = match tag {
+        Some(x) => Some(x),
+        None if image.contains('@') => None,
+        None => Some("latest"),
+    };
+    let reference = match tag {
+        Some(x) => format!("{}:{}",name,x),
+        None => name.to_owned(),
+    };
+    let mut builder = PullOptions::builder();
+    builder.image(name);
+    if let Some(tag) = tag {
+        builder.tag(tag);
+    }
+    if let Some(auth) = registry_auth(image).await {
+        builder.auth(auth);
+    }
+    let opts = builder.build();
+    let mut stream = docker.images().pull(&opts);
+    let mut progress = PullProgress::default();
+    while let Some(event) = stream.next().await {
+        let event = event.map_err(|e| format!("Could not pull {}: {}",reference,e))?;
+        if let Some(error) = event["error"].as_str() {
+            return Err(format!("Could not pull {}: {}",reference,error));
+        }
+        progress.update(&event);
+        on_progress(&progress);
+    }
+    Ok(format!("Pulled {}",reference))
+}
+
+/// A tag of a local image, untagged images are listed once as `<none>:<none>`.
+pub struct ImageEntry {
+    pub name: String,
+    pub id: String,
+    pub size: u64,
+    pub created: DateTime<Utc>,
+    /// Number of containers, running or not, created from the image.
+    pub containers: usize,
+}
+
+impl ImageEntry {
+    pub fn short_id(&self) -> &str {
+        let id = self.id.trim_start_matches("sha256:");
+        &id[..std::cmp::min(12,id.len())]
+    }
+
+    pub fn is_tagged(&self) -> bool {
+        self.name != "<none>:<none>"
+    }
+}
+
+/// Lists the local images, one entry per tag.
+pub async fn list(docker: &Docker) -> Result<Vec<ImageEntry>,String> {
+    let images = docker.images().list(&ImageListOptions::default()).await.map_err(|e| format!("Could not list images: {}",e))?;
+    let containers = crate::container::list(docker,true).await.unwrap_or_default();
+    let mut usage = HashMap::new();
+    for container in containers.iter() {
+        *usage.entry(container.image_id.clone()).or_insert(0) += 1;
+    }
+    let mut entries = Vec::new();
+    for image in images {
+        let mut tags = image.repo_tags.clone().unwrap_or_default();
+        if tags.is_empty() {
+            tags.push(String::from("<none>:<none>"));
+        }
+        for name in tags {
+            entries.push(ImageEntry {
+                name,
+                id: image.id.clone(),
+                size: image.virtual_size,
+                created: image.created,
+                containers: usage.get(&image.id).copied().unwrap_or(0),
+            });
+        }
+    }
+    Ok(entries)
+}
+
+/// Removes a tag of an image, or the image itself if given its id or last tag.
+pub async fn remove(docker: &Docker, name: &str) -> Result<String,String> {
+    let statuses = docker.images().get(name).delete().await.map_err(|e| format!("Could not remove {}: {}",name,e))?;
+    Ok(format!("Removed {} ({} layers deleted)",name,statuses.iter().filter(|x| matches!(x,shiplift::rep::Status::Deleted(_))).count()))
+}
+
+/// Tags the image `id` as `target`, a `repo:tag` reference.
+pub async fn tag(docker: &Docker, id: &str, target: &str) -> Result<String,String> {
+    let (repo,tag) = split_tag(target);
+    let mut builder = TagOptions::builder();
+    builder.repo(repo).tag(tag.unwrap_or("latest"));
+    docker.images().get(id).tag(&builder.build()).await.map_err(|e| format!("Could not tag {}: {}",target,e))?;
+    Ok(format!("Tagged {}:{}",repo,tag.unwrap_or("latest")))
+}
+
+/// The layers of the image `id`, the newest first.
+pub async fn history(docker: &Docker, id: &str) -> Result<Vec<rep::History>,String> {
+    docker.images().get(id).history().await.map_err(|e| format!("Could not load the history of {}: {}",id,e))
+}
+
+/// Removes all dangling images, those without a tag and not used by others.
+pub async fn prune() -> Result<String,String> {
+    // filters={"dangling":["true"]}
+    let result = session::post_json("/images/prune?filters=%7B%22dangling%22%3A%5B%22true%22%5D%7D",None).await?;
+    let deleted = result["ImagesDeleted"].as_array().map(|x| x.len()).unwrap_or(0);
+    Ok(format!("Pruned {} images, reclaimed {}",deleted,crate::workspace::format_size(result["SpaceReclaimed"].as_u64().unwrap_or(0))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST: &str = "sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn accepts_well_formed_references() {
+        assert_eq!(validate_reference("ubuntu"),Ok(()));
+        assert_eq!(validate_reference("localhost/app"),Ok(()));
+        assert_eq!(validate_reference(&format!("registry.example.com:5000/team/my_app-2:1.0.0-rc.1@{}",DIGEST)),Ok(()));
+        assert_eq!(validate_reference(&format!("ubuntu@{}",DIGEST)),Ok(()));
+    }
+
+    #[test]
+    fn rejects_uppercase_repositories() {
+        assert!(validate_reference("Ubuntu").unwrap_err().contains("lowercase"));
+        assert!(validate_reference("registry:5000/Team/app").unwrap_err().contains("lowercase"));
+        // The registry host is not part of the repository name.
+        assert_eq!(validate_reference("Registry.example.com/app"),Ok(()));
+    }
+
+    #[test]
+    fn rejects_empty_and_invalid_tags() {
+        assert!(validate_reference("ubuntu:").unwrap_err().contains("Invalid tag"));
+        assert!(validate_reference("ubuntu:-rc").unwrap_err().contains("Invalid tag"));
+        assert!(validate_reference(&format!("ubuntu:{}","x".repeat(129))).unwrap_err().contains("Invalid tag"));
+        assert!(validate_reference("ubuntu@sha256:123").unwrap_err().contains("Invalid digest"));
+    }
+
+    #[test]
+    fn rejects_leading_and_trailing_separators() {
+        for image in ["-app","app-","_app","app.","team//app","/app","app/","team/.app","app___x","registry:5000/"].iter() {
+            assert!(validate_reference(image).is_err(),"{} is not a valid reference",image);
+        }
+        assert!(validate_reference("-registry.com/app").unwrap_err().contains("Invalid registry"));
+        assert!(validate_reference("").is_err());
+    }
+}
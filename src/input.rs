@@ -0,0 +1,136 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::mpsc;
+use termion::event::{parse_event,Event,Key,MouseEvent};
+
+/// Reads the raw bytes typed on stdin on a background thread.
+///
+/// Unlike a thread blocking in `read`, the reader stops as soon as it is dropped,
+/// so it never swallows a key press meant for whoever reads stdin next.
+pub struct StdinReader {
+    receiver: mpsc::UnboundedReceiver<Vec<u8>>,
+    running: Arc<AtomicBool>,
+}
+
+impl StdinReader {
+    pub fn new() -> StdinReader {
+        let (sender,receiver) = mpsc::unbounded_channel();
+        let running = Arc::new(AtomicBool::new(true));
+        let flag = running.clone();
+        std::thread::spawn(move || {
+            let mut buffer = [0u8; 1024];
+            while flag.load(Ordering::Relaxed) {
+                let mut fd = libc::pollfd {
+                    fd: libc::STDIN_FILENO,
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+                // Wake up regularly to notice when the reader got dropped.
+                let ready = unsafe { libc::poll(&mut fd, 1, 50) };
+                if ready <= 0 || !flag.load(Ordering::Relaxed) {
+                    continue;
+                }
+                let read = unsafe { libc::read(libc::STDIN_FILENO, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len()) };
+                if read <= 0 || sender.send(buffer[..read as usize].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+        StdinReader {
+            receiver,
+            running,
+        }
+    }
+
+    /// Waits for the next chunk of input, `None` once stdin was closed.
+    pub async fn recv(&mut self) -> Option<Vec<u8>> {
+        self.receiver.recv().await
+    }
+}
+
+impl Drop for StdinReader {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Makes the terminal mark pasted text so that it arrives as `Input::Paste`
+/// instead of single key presses.
+pub const ENABLE_BRACKETED_PASTE: &str = "\x1b[?2004h";
+pub const DISABLE_BRACKETED_PASTE: &str = "\x1b[?2004l";
+/// Makes the terminal report mouse clicks, enabled only by screens which use
+/// the mouse so that text can still be selected elsewhere.
+pub const ENABLE_MOUSE: &str = "\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h";
+pub const DISABLE_MOUSE: &str = "\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l";
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+pub enum Input {
+    Key(Key),
+    Mouse(MouseEvent),
+    Paste(String),
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|x| x == needle)
+}
+
+/// Splits a chunk read by `StdinReader` into the keys and mouse events it contains.
+fn parse_events(bytes: &[u8]) -> Vec<Input> {
+    // A lone escape byte is the escape key rather than the start of a sequence.
+    if bytes == [0x1b] {
+        return vec![Input::Key(Key::Esc)];
+    }
+    let mut iter = bytes.iter().map(|x| Ok(*x));
+    let mut inputs = Vec::new();
+    while let Some(Ok(byte)) = iter.next() {
+        match parse_event(byte,&mut iter) {
+            Ok(Event::Key(key)) => inputs.push(Input::Key(key)),
+            Ok(Event::Mouse(event)) => inputs.push(Input::Mouse(event)),
+            _ => {},
+        }
+    }
+    inputs
+}
+
+/// Parses the chunks read by `StdinReader` into keys, mouse events and pasted
+/// text, a paste may span several chunks.
+#[derive(Default)]
+pub struct InputParser {
+    /// The text pasted so far while inside a bracketed paste.
+    paste: Option<Vec<u8>>,
+}
+
+impl InputParser {
+    pub fn parse(&mut self, mut bytes: &[u8]) -> Vec<Input> {
+        let mut inputs = Vec::new();
+        while !bytes.is_empty() {
+            if let Some(paste) = &mut self.paste {
+                match find(bytes,PASTE_END) {
+                    Some(end) => {
+                        paste.extend_from_slice(&bytes[..end]);
+                        inputs.push(Input::Paste(String::from_utf8_lossy(paste).to_string()));
+                        self.paste = None;
+                        bytes = &bytes[end+PASTE_END.len()..];
+                    },
+                    None => {
+                        paste.extend_from_slice(bytes);
+                        break;
+                    },
+                }
+            }
+            else {
+                let start = find(bytes,PASTE_START);
+                inputs.extend(parse_events(&bytes[..start.unwrap_or(bytes.len())]));
+                match start {
+                    Some(start) => {
+                        self.paste = Some(Vec::new());
+                        bytes = &bytes[start+PASTE_START.len()..];
+                    },
+                    None => break,
+                }
+            }
+        }
+        inputs
+    }
+}
@@ -8,6 +8,17 @@ use tui::{
 use termion::color;
 
 mod ui;
+mod workspace;
+mod container;
+mod cli;
+mod config;
+mod input;
+mod session;
+mod stats;
+mod fuzzy;
+mod query;
+mod images;
+mod run_command;
 
 const VERSION: &'static str = concat!("Docker development environment version v",env!("CARGO_PKG_VERSION"));
 
@@ -15,12 +26,26 @@ const VERSION: &'static str = concat!("Docker development environment version v"
 #[tokio::main]
 async fn main() {
     let docker = Docker::new();
+    let config = config::Config::load();
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    if !args.is_empty() {
+        let config = match config {
+            Ok(x) => x,
+            Err(error) => {
+                eprintln!("{}",error);
+                std::process::exit(cli::EXIT_FAILURE);
+            }
+        };
+        std::process::exit(cli::run(&docker,&config,&args).await);
+    }
     println!("{}{}{}",color::Fg(color::LightMagenta),VERSION,color::Fg(color::Reset));
     let stdout = io::stdout().into_raw_mode().unwrap();
     let stdout = AlternateScreen::from(stdout);
     let backend = TermionBackend::new(stdout);
     let mut terminal = Terminal::new(backend).unwrap();
     terminal.clear().unwrap();
-    let mut app = ui::App::new(docker).await;
+    print!("{}",input::ENABLE_BRACKETED_PASTE);
+    let mut app = ui::App::new(docker,config).await;
     app.event_loop(&mut terminal).await;
+    print!("{}",input::DISABLE_BRACKETED_PASTE);
 }
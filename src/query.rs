@@ -0,0 +1,235 @@
+//! Filter queries for the container list, e.g.
+//! `status:running image:rust path:~/work/api label:team=infra -name:old api`.
+//!
+//! A query consists of whitespace separated terms which all have to match. A
+//! term is either a `field:value` filter or free text which is fuzzy matched
+//! against the name, image and labels. A leading `-` negates a term and values
+//! containing spaces can be quoted.
+use shiplift::rep;
+use crate::container::{self,LABEL_WORKSPACE};
+use crate::fuzzy;
+
+pub const FIELDS: [&str; 5] = ["status","image","name","path","label"];
+
+enum Filter {
+    Status(String),
+    Image(String),
+    Name(String),
+    Path(String),
+    Label(String,Option<String>),
+    Text(String),
+}
+
+struct Term {
+    negated: bool,
+    filter: Filter,
+}
+
+pub struct Query {
+    terms: Vec<Term>,
+}
+
+/// The result of matching a container, `positions` are the characters of its
+/// name matched by free text.
+pub struct QueryMatch {
+    pub score: i64,
+    pub positions: Vec<usize>,
+}
+
+/// Splits the input into words, keeping quoted parts together.
+pub fn split(input: &str) -> Result<Vec<String>,String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in input.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+            },
+            c => current.push(c),
+        }
+    }
+    if quoted {
+        return Err(String::from("Missing closing quote"));
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    Ok(words)
+}
+
+/// Expands a leading `~` to the home directory.
+fn expand_home(path: &str) -> String {
+    match home::home_dir() {
+        Some(home) if path == "~" || path.starts_with("~/") => home.join(path.trim_start_matches('~').trim_start_matches('/')).to_string_lossy().to_string(),
+        _ => path.to_owned(),
+    }
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Query,String> {
+        let mut terms = Vec::new();
+        for word in split(input)? {
+            let (negated,word) = match word.strip_prefix('-') {
+                Some(x) if !x.is_empty() => (true,x),
+                _ => (false,&word[..]),
+            };
+            let filter = match word.find(':') {
+                Some(x) => {
+                    let (field,value) = (&word[..x],&word[x+1..]);
+                    if value.is_empty() {
+                        return Err(format!("Missing value for \"{}:\"",field));
+                    }
+                    match field {
+                        "status" => Filter::Status(value.to_lowercase()),
+                        "image" => Filter::Image(value.to_lowercase()),
+                        "name" => Filter::Name(value.to_lowercase()),
+                        "path" => Filter::Path(expand_home(value)),
+                        "label" => match value.find('=') {
+                            Some(0) => return Err(format!("Missing label name in \"{}\"",word)),
+                            Some(y) => Filter::Label(value[..y].to_owned(),Some(value[y+1..].to_owned())),
+                            None => Filter::Label(value.to_owned(),None),
+                        },
+                        _ => return Err(format!("Unknown field \"{}\", expected one of {}",field,FIELDS.join(", "))),
+                    }
+                },
+                None => Filter::Text(word.to_owned()),
+            };
+            terms.push(Term { negated, filter });
+        }
+        Ok(Query { terms })
+    }
+
+    /// Whether the query contains free text, which ranks the matches.
+    pub fn has_text(&self) -> bool {
+        self.terms.iter().any(|x| !x.negated && matches!(x.filter,Filter::Text(_)))
+    }
+
+    /// Matches all terms against the container, `None` if it is filtered out.
+    pub fn matches(&self, container: &rep::Container, smart_case: bool) -> Option<QueryMatch> {
+        let mut result = QueryMatch { score: 0, positions: Vec::new() };
+        for term in self.terms.iter() {
+            let matched = match &term.filter {
+                Filter::Status(x) if x == "stopped" => container.state != "running",
+                Filter::Status(x) => container.state.to_lowercase().starts_with(x),
+                Filter::Image(x) => container.image.to_lowercase().contains(x),
+                Filter::Name(x) => container::display_name(container).to_lowercase().contains(x),
+                Filter::Path(x) => container.labels.get(LABEL_WORKSPACE).map(|y| y.starts_with(x)).unwrap_or(false),
+                Filter::Label(name,value) => match (container.labels.get(name),value) {
+                    (Some(x),Some(y)) => x == y,
+                    (Some(_),None) => true,
+                    (None,_) => false,
+                },
+                Filter::Text(x) => {
+                    let case_sensitive = fuzzy::is_case_sensitive(x,smart_case);
+                    let name_match = fuzzy::fuzzy_match(x,container::display_name(container),case_sensitive);
+                    let mut fields = vec![container.image.clone()];
+                    fields.extend(container.labels.iter().map(|(name,value)| format!("{}={}",name,value)));
+                    let score = fields.iter().filter_map(|y| fuzzy::fuzzy_match(x,y,case_sensitive)).map(|y| y.score)
+                        .chain(name_match.as_ref().map(|y| y.score)).max();
+                    if let (Some(score),false) = (score,term.negated) {
+                        result.score += score;
+                        if let Some(name_match) = name_match {
+                            result.positions.extend(name_match.positions);
+                        }
+                    }
+                    score.is_some()
+                },
+            };
+            if matched == term.negated {
+                return None;
+            }
+        }
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn container(name: &str, state: &str) -> rep::Container {
+        let mut labels = HashMap::new();
+        labels.insert(String::from("team"),String::from("infra"));
+        rep::Container {
+            created: chrono::Utc::now(),
+            command: String::new(),
+            id: String::from("0123456789ab"),
+            image: String::from("rust:latest"),
+            image_id: String::new(),
+            labels,
+            names: if name.is_empty() { Vec::new() } else { vec![format!("/{}",name)] },
+            ports: Vec::new(),
+            state: state.to_owned(),
+            status: String::new(),
+            size_rw: None,
+            size_root_fs: None,
+        }
+    }
+
+    fn matches(query: &str, container: &rep::Container) -> bool {
+        Query::parse(query).unwrap().matches(container,true).is_some()
+    }
+
+    #[test]
+    fn split_keeps_quoted_parts_together() {
+        assert_eq!(split(r#"name:"my app"  api"#).unwrap(),vec!["name:my app","api"]);
+        assert_eq!(split(r#"a"b c"d"#).unwrap(),vec!["ab cd"]);
+        assert!(split("").unwrap().is_empty());
+        assert!(split(r#"name:"my app"#).is_err());
+    }
+
+    #[test]
+    fn filters() {
+        let running = container("dde_api","running");
+        assert!(matches("status:running image:rust name:API",&running));
+        assert!(!matches("status:stopped",&running));
+        assert!(matches("label:team label:team=infra",&running));
+        assert!(!matches("label:team=web",&running));
+        assert!(matches("status:stopped",&container("dde_api","exited")));
+    }
+
+    #[test]
+    fn negation() {
+        assert!(!matches("-status:running",&container("dde_api","running")));
+        assert!(matches("-status:running",&container("dde_api","exited")));
+        assert!(!matches("-api",&container("dde_api","running")));
+        assert!(matches("-web",&container("dde_api","running")));
+        assert!(!Query::parse("-api").unwrap().has_text());
+        // A lone dash is free text.
+        assert!(Query::parse("-").unwrap().has_text());
+    }
+
+    #[test]
+    fn quoted_values() {
+        let mut spaced = container("dde_api","running");
+        spaced.labels.insert(String::from("owner"),String::from("jane doe"));
+        assert!(matches(r#"label:"owner=jane doe""#,&spaced));
+        assert!(matches(r#"-label:"owner=john doe""#,&spaced));
+    }
+
+    #[test]
+    fn free_text_highlights_the_name() {
+        let matched = Query::parse("api").unwrap().matches(&container("dde_api","running"),true).unwrap();
+        assert_eq!(matched.positions,vec![4,5,6]);
+        assert!(matched.score > 0);
+    }
+
+    #[test]
+    fn container_without_names() {
+        let nameless = container("","running");
+        assert!(matches("0123",&nameless));
+        assert!(matches("name:0123",&nameless));
+    }
+
+    #[test]
+    fn invalid_queries() {
+        assert!(Query::parse("status:").is_err());
+        assert!(Query::parse("size:big").is_err());
+        assert!(Query::parse("label:=x").is_err());
+    }
+}
@@ -0,0 +1,384 @@
+//! The `docker run` command line and the docker-compose service equivalent to a
+//! container, so that an environment can be recreated without dde. Built either
+//! from the setup of a new container or from the inspection of an existing one.
+use serde_json::Value;
+use crate::container::{self,ContainerSetup,WorkingDirectorySetup};
+use crate::session;
+
+/// Substrings of environment variable names whose values are not shown.
+pub const SECRET_NAMES: [&str; 7] = ["SECRET","TOKEN","PASSWORD","PASSWD","KEY","CREDENTIAL","AUTH"];
+
+/// Whether the variable `name` looks like it contains credentials.
+pub fn is_secret(name: &str) -> bool {
+    SECRET_NAMES.iter().any(|x| name.to_uppercase().contains(x))
+}
+
+/// The options of a container which `docker run` can reproduce.
+#[derive(Default)]
+pub struct RunCommand {
+    pub name: String,
+    pub image: String,
+    pub entrypoint: Option<String>,
+    pub command: Vec<String>,
+    pub interactive: bool,
+    pub tty: bool,
+    pub auto_remove: bool,
+    pub user: String,
+    pub working_dir: String,
+    pub restart: String,
+    pub volumes: Vec<String>,
+    pub env: Vec<String>,
+    pub ports: Vec<String>,
+    pub labels: Vec<(String,String)>,
+    /// Steps `docker run` cannot express, printed as comments.
+    pub notes: Vec<String>,
+}
+
+fn string(value: &Value) -> String {
+    value.as_str().unwrap_or("").to_owned()
+}
+
+fn strings(value: &Value) -> Vec<String> {
+    value.as_array().map(|x| x.iter().map(string).collect()).unwrap_or_default()
+}
+
+/// A published port in the form `docker run -p` expects, tcp is the default.
+fn format_port(host_ip: &str, host: &str, container: u32, protocol: &str) -> String {
+    let mut port = if host_ip.is_empty() { format!("{}:{}",host,container) } else { format!("{}:{}:{}",host_ip,host,container) };
+    if protocol != "tcp" {
+        port += "/";
+        port += protocol;
+    }
+    port
+}
+
+/// Quotes `arg` for a POSIX shell unless it only contains harmless characters.
+pub fn quote(arg: &str) -> String {
+    if !arg.is_empty() && arg.chars().all(|x| x.is_ascii_alphanumeric() || "_@%+=:,./-".contains(x)) {
+        return arg.to_owned();
+    }
+    format!("'{}'",arg.replace('\'',"'\\''"))
+}
+
+/// A double quoted YAML string.
+fn yaml(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+impl RunCommand {
+    /// The container the new container form is about to create.
+    pub fn from_setup(setup: &ContainerSetup) -> Result<RunCommand,String> {
+        let mut ports = Vec::new();
+        for port in setup.ports.iter() {
+            let (container_port,protocol,host_port) = container::parse_port(port)?;
+            ports.push(format_port("",&host_port.to_string(),container_port,&protocol));
+        }
+        let mut notes = Vec::new();
+        if let WorkingDirectorySetup::CopyDirectory(x) = &setup.working_dir {
+            notes.push(format!("Copy the working directory into the container: docker cp {}/. {}:{}",quote(x),setup.container_name(),quote(&setup.mount_target)));
+        }
+        Ok(RunCommand {
+            name: setup.container_name(),
+            image: setup.image.clone(),
+            entrypoint: None,
+            command: setup.command().into_iter().map(String::from).collect(),
+            interactive: true,
+            tty: true,
+            auto_remove: setup.auto_remove,
+            volumes: setup.volumes()?,
+            env: setup.env(),
+            ports,
+            labels: setup.labels().into_iter().map(|(name,value)| (name.to_owned(),value)).collect(),
+            notes,
+            ..RunCommand::default()
+        })
+    }
+
+    /// An existing container, settings equal to the defaults of its image are
+    /// left out.
+    pub fn from_inspection(inspection: &Value, image: &Value) -> RunCommand {
+        let config = &inspection["Config"];
+        let host = &inspection["HostConfig"];
+        let image_config = &image["Config"];
+
+        let mut entrypoint = None;
+        let mut command = Vec::new();
+        if config["Entrypoint"] != image_config["Entrypoint"] {
+            let mut parts = strings(&config["Entrypoint"]).into_iter();
+            // An empty entry point resets the one of the image.
+            entrypoint = Some(parts.next().unwrap_or_default());
+            command.extend(parts);
+        }
+        if entrypoint.is_some() || config["Cmd"] != image_config["Cmd"] {
+            command.extend(strings(&config["Cmd"]));
+        }
+
+        let image_env = strings(&image_config["Env"]);
+        let env = strings(&config["Env"]).into_iter().filter(|x| !image_env.contains(x)).collect();
+
+        let mut ports = host["PortBindings"].as_object().map(|x| x.iter().flat_map(|(container,hosts)| {
+            let mut parts = container.splitn(2,'/');
+            let port = parts.next().and_then(|x| x.parse::<u32>().ok()).unwrap_or(0);
+            let protocol = parts.next().unwrap_or("tcp").to_owned();
+            hosts.as_array().map(|x| x.iter().map(|y| format_port(&string(&y["HostIp"]),&string(&y["HostPort"]),port,&protocol)).collect::<Vec<String>>()).unwrap_or_default()
+        }).collect::<Vec<String>>()).unwrap_or_default();
+        ports.sort();
+
+        let image_labels = &image_config["Labels"];
+        let mut labels = config["Labels"].as_object().map(|x| x.iter()
+            .filter(|(name,value)| image_labels[name.as_str()] != **value)
+            .map(|(name,value)| (name.clone(),string(value))).collect::<Vec<(String,String)>>()).unwrap_or_default();
+        labels.sort();
+
+        let restart = &host["RestartPolicy"];
+        let restart = match (string(&restart["Name"]).as_str(),restart["MaximumRetryCount"].as_u64().unwrap_or(0)) {
+            ("",_) | ("no",_) => String::new(),
+            ("on-failure",x) if x > 0 => format!("on-failure:{}",x),
+            (x,_) => x.to_owned(),
+        };
+
+        let differs = |key: &str| if config[key] != image_config[key] { string(&config[key]) } else { String::new() };
+        RunCommand {
+            name: string(&inspection["Name"]).trim_start_matches('/').to_owned(),
+            image: string(&config["Image"]),
+            entrypoint,
+            command,
+            interactive: config["OpenStdin"].as_bool().unwrap_or(false),
+            tty: config["Tty"].as_bool().unwrap_or(false),
+            auto_remove: host["AutoRemove"].as_bool().unwrap_or(false),
+            user: differs("User"),
+            working_dir: differs("WorkingDir"),
+            restart,
+            volumes: strings(&host["Binds"]),
+            env,
+            ports,
+            labels,
+            notes: Vec::new(),
+        }
+    }
+
+    /// Inspects the container `id` and its image.
+    pub async fn load(id: &str) -> Result<RunCommand,String> {
+        let inspection = session::get_json(&format!("/containers/{}/json",id)).await?;
+        let image = session::get_json(&format!("/images/{}/json",string(&inspection["Image"]))).await.unwrap_or(Value::Null);
+        Ok(RunCommand::from_inspection(&inspection,&image))
+    }
+
+    /// The variables in the form `NAME=value`, credentials are passed through
+    /// from the environment of the caller as `NAME`.
+    fn env_entries(&self) -> Vec<String> {
+        self.env.iter().map(|x| match x.find('=') {
+            Some(idx) if is_secret(&x[..idx]) => x[..idx].to_owned(),
+            _ => x.clone(),
+        }).collect()
+    }
+
+    /// The `docker run` command line, one option per line.
+    pub fn docker_run(&self) -> String {
+        let mut first = String::from("docker run");
+        match (self.interactive,self.tty) {
+            (true,true) => first += " -it",
+            (true,false) => first += " -i",
+            (false,true) => first += " -t",
+            (false,false) => first += " -d",
+        }
+        if self.auto_remove {
+            first += " --rm";
+        }
+        let mut lines = vec![first,format!("--name {}",quote(&self.name))];
+        if let Some(entrypoint) = &self.entrypoint {
+            lines.push(format!("--entrypoint {}",quote(entrypoint)));
+        }
+        if !self.user.is_empty() {
+            lines.push(format!("--user {}",quote(&self.user)));
+        }
+        if !self.working_dir.is_empty() {
+            lines.push(format!("--workdir {}",quote(&self.working_dir)));
+        }
+        if !self.restart.is_empty() {
+            lines.push(format!("--restart {}",quote(&self.restart)));
+        }
+        lines.extend(self.volumes.iter().map(|x| format!("-v {}",quote(x))));
+        lines.extend(self.env_entries().iter().map(|x| format!("-e {}",quote(x))));
+        lines.extend(self.ports.iter().map(|x| format!("-p {}",quote(x))));
+        lines.extend(self.labels.iter().map(|(name,value)| format!("--label {}",quote(&format!("{}={}",name,value)))));
+        let mut last = quote(&self.image);
+        for arg in self.command.iter() {
+            last += " ";
+            last += &quote(arg);
+        }
+        lines.push(last);
+        let mut text = lines.join(" \\\n    ");
+        for note in self.notes.iter() {
+            text += "\n# ";
+            text += note;
+        }
+        text
+    }
+
+    /// A docker-compose file with the container as its only service.
+    pub fn compose(&self) -> String {
+        let service = self.name.trim_start_matches(container::NAME_PREFIX).chars()
+            .map(|x| if x.is_ascii_alphanumeric() || x == '-' || x == '_' { x.to_ascii_lowercase() } else { '_' }).collect::<String>();
+        let mut lines = self.notes.iter().map(|x| format!("# {}",x)).collect::<Vec<String>>();
+        lines.push(String::from("services:"));
+        lines.push(format!("  {}:",service));
+        lines.push(format!("    image: {}",yaml(&self.image)));
+        lines.push(format!("    container_name: {}",yaml(&self.name)));
+        let list = |values: &[String]| format!("[{}]",values.iter().map(|x| yaml(x)).collect::<Vec<String>>().join(", "));
+        if let Some(entrypoint) = &self.entrypoint {
+            lines.push(format!("    entrypoint: {}",list(std::slice::from_ref(entrypoint))));
+        }
+        if !self.command.is_empty() {
+            lines.push(format!("    command: {}",list(&self.command)));
+        }
+        if self.interactive {
+            lines.push(String::from("    stdin_open: true"));
+        }
+        if self.tty {
+            lines.push(String::from("    tty: true"));
+        }
+        for (key,value) in [("user",&self.user),("working_dir",&self.working_dir),("restart",&self.restart)].iter() {
+            if !value.is_empty() {
+                lines.push(format!("    {}: {}",key,yaml(value)));
+            }
+        }
+        for (key,values) in [("volumes",&self.volumes),("environment",&self.env_entries()),("ports",&self.ports)].iter() {
+            if !values.is_empty() {
+                lines.push(format!("    {}:",key));
+                lines.extend(values.iter().map(|x| format!("      - {}",yaml(x))));
+            }
+        }
+        if !self.labels.is_empty() {
+            lines.push(String::from("    labels:"));
+            lines.extend(self.labels.iter().map(|(name,value)| format!("      {}: {}",name,yaml(value))));
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn inspected() -> RunCommand {
+        let inspection = json!({
+            "Name": "/dde_My.App",
+            "Config": {
+                "Image": "rust:latest",
+                "Entrypoint": null,
+                "Cmd": ["sh","-c","echo it's ok"],
+                "OpenStdin": true,
+                "Tty": true,
+                "User": "dev",
+                "WorkingDir": "/",
+                "Env": ["PATH=/usr/bin","MSG=it's ok","API_TOKEN=hunter2"],
+                "Labels": {"dde": "true","maintainer": "someone"},
+            },
+            "HostConfig": {
+                "AutoRemove": false,
+                "Binds": ["/home/dev/work:/root/work"],
+                "RestartPolicy": {"Name": "on-failure","MaximumRetryCount": 3},
+                "PortBindings": {
+                    "80/tcp": [{"HostIp": "","HostPort": "8080"}],
+                    "53/udp": [{"HostIp": "127.0.0.1","HostPort": "53"}],
+                },
+            },
+        });
+        let image = json!({
+            "Config": {
+                "Entrypoint": ["/entrypoint.sh"],
+                "Cmd": ["bash"],
+                "WorkingDir": "/",
+                "Env": ["PATH=/usr/bin"],
+                "Labels": {"maintainer": "someone"},
+            },
+        });
+        RunCommand::from_inspection(&inspection,&image)
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("rust:1.54"),"rust:1.54");
+        assert_eq!(quote(""),"''");
+        assert_eq!(quote("a b"),"'a b'");
+        assert_eq!(quote("it's"),"'it'\\''s'");
+        assert_eq!(quote("$HOME"),"'$HOME'");
+    }
+
+    #[test]
+    fn leaves_out_the_defaults_of_the_image() {
+        let command = inspected();
+        assert_eq!(command.name,"dde_My.App");
+        assert_eq!(command.env,vec!["MSG=it's ok","API_TOKEN=hunter2"]);
+        assert_eq!(command.labels,vec![(String::from("dde"),String::from("true"))]);
+        assert_eq!(command.user,"dev");
+        assert_eq!(command.working_dir,"");
+        assert_eq!(command.restart,"on-failure:3");
+        assert_eq!(command.ports,vec!["127.0.0.1:53:53/udp","8080:80"]);
+    }
+
+    #[test]
+    fn empty_entrypoint_resets_the_image() {
+        let command = inspected();
+        assert_eq!(command.entrypoint.as_deref(),Some(""));
+        assert_eq!(command.command,vec!["sh","-c","echo it's ok"]);
+        let inspection = json!({"Config": {"Entrypoint": ["/entrypoint.sh"],"Cmd": ["bash"]}});
+        let image = json!({"Config": {"Entrypoint": ["/entrypoint.sh"],"Cmd": ["bash"]}});
+        let command = RunCommand::from_inspection(&inspection,&image);
+        assert_eq!(command.entrypoint,None);
+        assert!(command.command.is_empty());
+    }
+
+    #[test]
+    fn docker_run() {
+        assert_eq!(inspected().docker_run(),[
+            "docker run -it",
+            "--name dde_My.App",
+            "--entrypoint ''",
+            "--user dev",
+            "--restart on-failure:3",
+            "-v /home/dev/work:/root/work",
+            "-e 'MSG=it'\\''s ok'",
+            "-e API_TOKEN",
+            "-p 127.0.0.1:53:53/udp",
+            "-p 8080:80",
+            "--label dde=true",
+            "rust:latest sh -c 'echo it'\\''s ok'",
+        ].join(" \\\n    "));
+    }
+
+    #[test]
+    fn compose() {
+        assert_eq!(inspected().compose(),[
+            "services:",
+            "  my_app:",
+            "    image: \"rust:latest\"",
+            "    container_name: \"dde_My.App\"",
+            "    entrypoint: [\"\"]",
+            "    command: [\"sh\", \"-c\", \"echo it's ok\"]",
+            "    stdin_open: true",
+            "    tty: true",
+            "    user: \"dev\"",
+            "    restart: \"on-failure:3\"",
+            "    volumes:",
+            "      - \"/home/dev/work:/root/work\"",
+            "    environment:",
+            "      - \"MSG=it's ok\"",
+            "      - \"API_TOKEN\"",
+            "    ports:",
+            "      - \"127.0.0.1:53:53/udp\"",
+            "      - \"8080:80\"",
+            "    labels:",
+            "      dde: \"true\"",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn notes_become_comments() {
+        let command = RunCommand { name: String::from("dde_x"), image: String::from("x"), notes: vec![String::from("Copy it")], ..RunCommand::default() };
+        assert_eq!(command.docker_run(),"docker run -d \\\n    --name dde_x \\\n    x\n# Copy it");
+        assert!(command.compose().starts_with("# Copy it\nservices:\n  x:"));
+    }
+}
@@ -0,0 +1,499 @@
+//! Interactive terminal sessions with containers over the Docker API.
+//!
+//! shiplift decodes attach streams as multiplexed stdout/stderr frames, which
+//! docker only sends for containers without a TTY, and cannot write to exec
+//! instances. Sessions therefore speak to the same endpoint `Docker::new()`
+//! resolves directly and upgrade the HTTP connection to a raw stream.
+use std::io::Write;
+use std::path::{Path,PathBuf};
+use std::pin::Pin;
+use openssl::ssl::{SslConnector,SslFiletype,SslMethod,SslVerifyMode};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, UnixStream};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio_openssl::SslStream;
+use termion::raw::IntoRawMode;
+use crate::input::StdinReader;
+
+/// The key sequence to detach from a session unless configured otherwise, the
+/// same as docker's.
+pub const DEFAULT_DETACH_KEYS: &str = "ctrl-p,ctrl-q";
+
+/// The docker daemon endpoint, resolved the same way `shiplift::Docker::new()` does.
+pub enum Endpoint {
+    Unix(PathBuf),
+    Tcp(String),
+    /// A TCP endpoint protected by the certificates in `certs`, the server is
+    /// only verified with `DOCKER_TLS_VERIFY`.
+    Tls { address: String, certs: PathBuf, verify: bool },
+}
+
+trait Connection: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Connection for T {}
+
+impl Endpoint {
+    pub fn from_env() -> Result<Endpoint,String> {
+        match std::env::var("DOCKER_HOST") {
+            Ok(host) => {
+                if let Some(path) = host.strip_prefix("unix://") {
+                    Ok(Endpoint::Unix(PathBuf::from(path)))
+                }
+                else if let Some(address) = host.strip_prefix("tcp://") {
+                    let address = address.trim_end_matches('/').to_owned();
+                    match std::env::var_os("DOCKER_CERT_PATH") {
+                        Some(certs) => Ok(Endpoint::Tls {
+                            address,
+                            certs: PathBuf::from(certs),
+                            verify: std::env::var_os("DOCKER_TLS_VERIFY").is_some(),
+                        }),
+                        None => Ok(Endpoint::Tcp(address)),
+                    }
+                }
+                else {
+                    Err(format!("Unsupported DOCKER_HOST \"{}\"",host))
+                }
+            },
+            Err(_) => Ok(Endpoint::Unix(PathBuf::from("/var/run/docker.sock"))),
+        }
+    }
+
+    async fn connect(&self) -> Result<Box<dyn Connection>,String> {
+        match self {
+            Endpoint::Unix(path) => UnixStream::connect(path).await.map(|x| Box::new(x) as Box<dyn Connection>).map_err(|e| format!("Could not connect to \"{}\": {}",path.display(),e)),
+            Endpoint::Tcp(address) => TcpStream::connect(address).await.map(|x| Box::new(x) as Box<dyn Connection>).map_err(|e| format!("Could not connect to \"{}\": {}",address,e)),
+            Endpoint::Tls { address, certs, verify } => {
+                let tcp = TcpStream::connect(address).await.map_err(|e| format!("Could not connect to \"{}\": {}",address,e))?;
+                let stream = connect_tls(tcp,address,certs,*verify).await.map_err(|e| format!("Could not connect to \"{}\": {}",address,e))?;
+                Ok(Box::new(stream))
+            },
+        }
+    }
+}
+
+/// Sets up TLS on `tcp` with the client certificate docker expects in `certs`.
+async fn connect_tls(tcp: TcpStream, address: &str, certs: &Path, verify: bool) -> Result<SslStream<TcpStream>,String> {
+    let mut builder = SslConnector::builder(SslMethod::tls()).map_err(|e| e.to_string())?;
+    builder.set_certificate_file(certs.join("cert.pem"),SslFiletype::PEM).map_err(|e| format!("Invalid certificate in \"{}\": {}",certs.display(),e))?;
+    builder.set_private_key_file(certs.join("key.pem"),SslFiletype::PEM).map_err(|e| format!("Invalid key in \"{}\": {}",certs.display(),e))?;
+    if verify {
+        builder.set_ca_file(certs.join("ca.pem")).map_err(|e| format!("Invalid CA certificate in \"{}\": {}",certs.display(),e))?;
+    }
+    else {
+        builder.set_verify(SslVerifyMode::NONE);
+    }
+    let host = address.rsplit_once(':').map(|x| x.0).unwrap_or(address).trim_start_matches('[').trim_end_matches(']');
+    let ssl = builder.build().configure().and_then(|x| x.verify_hostname(verify).into_ssl(host)).map_err(|e| e.to_string())?;
+    let mut stream = SslStream::new(ssl,tcp).map_err(|e| e.to_string())?;
+    Pin::new(&mut stream).connect().await.map_err(|e| e.to_string())?;
+    Ok(stream)
+}
+
+/// The status code and headers of a response together with the part of the
+/// body that was read along with them.
+struct ResponseHead {
+    status: u16,
+    chunked: bool,
+    rest: Vec<u8>,
+}
+
+async fn send_request(io: &mut Box<dyn Connection>, method: &str, path: &str, body: Option<&serde_json::Value>, auth: Option<&str>, upgrade: bool) -> Result<(),String> {
+    let body = body.map(|x| x.to_string()).unwrap_or_default();
+    let connection = if upgrade { "Connection: Upgrade\r\nUpgrade: tcp\r\n" } else { "Connection: close\r\n" };
+    let auth = auth.map(|x| format!("X-Registry-Auth: {}\r\n",x)).unwrap_or_default();
+    let request = format!("{} {} HTTP/1.1\r\nHost: docker\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}{}\r\n{}",method,path,body.len(),auth,connection,body);
+    io.write_all(request.as_bytes()).await.map_err(|e| format!("Could not send request to docker: {}",e))
+}
+
+async fn read_head(io: &mut Box<dyn Connection>) -> Result<ResponseHead,String> {
+    let mut data = Vec::new();
+    let mut buffer = [0u8; 1024];
+    let end = loop {
+        if let Some(x) = data.windows(4).position(|x| x == b"\r\n\r\n") {
+            break x;
+        }
+        let read = io.read(&mut buffer).await.map_err(|e| format!("Could not read response from docker: {}",e))?;
+        if read == 0 {
+            return Err(String::from("Docker closed the connection unexpectedly"));
+        }
+        data.extend_from_slice(&buffer[..read]);
+    };
+    let head = String::from_utf8_lossy(&data[..end]).to_lowercase();
+    let status = head.split_whitespace().nth(1).and_then(|x| x.parse::<u16>().ok()).ok_or("Invalid response from docker")?;
+    Ok(ResponseHead {
+        status,
+        chunked: head.lines().any(|x| x.starts_with("transfer-encoding:") && x.contains("chunked")),
+        rest: data[end+4..].to_vec(),
+    })
+}
+
+/// Moves the payload of all complete chunks of a body sent with
+/// `Transfer-Encoding: chunked` from `raw` to `body`. Returns true once the
+/// terminating chunk was read.
+fn take_chunks(raw: &mut Vec<u8>, body: &mut Vec<u8>) -> bool {
+    while let Some(line_end) = raw.windows(2).position(|x| x == b"\r\n") {
+        let size = usize::from_str_radix(String::from_utf8_lossy(&raw[..line_end]).trim(),16).unwrap_or(0);
+        let start = line_end+2;
+        if size == 0 {
+            return true;
+        }
+        if start+size+2 > raw.len() {
+            break;
+        }
+        body.extend_from_slice(&raw[start..start+size]);
+        raw.drain(..start+size+2);
+    }
+    false
+}
+
+/// Decodes a body sent with `Transfer-Encoding: chunked`.
+fn dechunk(data: &[u8]) -> Vec<u8> {
+    let mut raw = data.to_vec();
+    let mut body = Vec::new();
+    take_chunks(&mut raw,&mut body);
+    body
+}
+
+fn error_message(status: u16, body: &[u8]) -> String {
+    match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(value) if value["message"].is_string() => format!("Docker error {}: {}",status,value["message"].as_str().unwrap()),
+        _ => format!("Docker error {}: {}",status,String::from_utf8_lossy(body).trim()),
+    }
+}
+
+/// Sends a plain request and returns the body of the response.
+async fn request(endpoint: &Endpoint, method: &str, path: &str, body: Option<&serde_json::Value>, auth: Option<&str>) -> Result<Vec<u8>,String> {
+    let mut io = endpoint.connect().await?;
+    send_request(&mut io,method,path,body,auth,false).await?;
+    let mut head = read_head(&mut io).await?;
+    io.read_to_end(&mut head.rest).await.map_err(|e| format!("Could not read response from docker: {}",e))?;
+    let body = if head.chunked { dechunk(&head.rest) } else { head.rest };
+    if head.status >= 300 {
+        return Err(error_message(head.status,&body));
+    }
+    Ok(body)
+}
+
+/// Sends a GET request for a JSON document, e.g. the raw result of an inspection
+/// with all the fields shiplift does not know about.
+pub async fn get_json(path: &str) -> Result<serde_json::Value,String> {
+    let endpoint = Endpoint::from_env()?;
+    let body = request(&endpoint,"GET",path,None,None).await?;
+    serde_json::from_slice(&body).map_err(|e| format!("Invalid response from docker: {}",e))
+}
+
+/// Sends a GET request for a JSON document to an endpoint which talks to a
+/// registry, `auth` are the encoded credentials for it.
+pub async fn get_registry_json(path: &str, auth: Option<&str>) -> Result<serde_json::Value,String> {
+    let endpoint = Endpoint::from_env()?;
+    let body = request(&endpoint,"GET",path,None,auth).await?;
+    serde_json::from_slice(&body).map_err(|e| format!("Invalid response from docker: {}",e))
+}
+
+/// Sends a POST request and returns the JSON document of the response, if any.
+pub async fn post_json(path: &str, body: Option<&serde_json::Value>) -> Result<serde_json::Value,String> {
+    let endpoint = Endpoint::from_env()?;
+    let body = request(&endpoint,"POST",path,body,None).await?;
+    if body.is_empty() {
+        return Ok(serde_json::Value::Null);
+    }
+    serde_json::from_slice(&body).map_err(|e| format!("Invalid response from docker: {}",e))
+}
+
+/// Sends a request that upgrades the connection to a raw stream.
+async fn upgrade(endpoint: &Endpoint, path: &str, body: Option<&serde_json::Value>) -> Result<(Box<dyn Connection>,Vec<u8>),String> {
+    let mut io = endpoint.connect().await?;
+    send_request(&mut io,"POST",path,body,None,true).await?;
+    let head = read_head(&mut io).await?;
+    if head.status != 101 && head.status != 200 {
+        return Err(error_message(head.status,&head.rest));
+    }
+    Ok((io,head.rest))
+}
+
+/// The stream a piece of container output was written to.
+#[derive(Clone,Copy,PartialEq)]
+pub enum Output {
+    Stdout,
+    Stderr,
+}
+
+/// Extracts the payload of all complete frames of a multiplexed stream.
+fn demux(buffer: &mut Vec<u8>) -> Vec<(Output,Vec<u8>)> {
+    let mut result = Vec::new();
+    while buffer.len() >= 8 {
+        let size = u32::from_be_bytes([buffer[4],buffer[5],buffer[6],buffer[7]]) as usize;
+        if buffer.len() < 8+size {
+            break;
+        }
+        let output = if buffer[0] == 2 { Output::Stderr } else { Output::Stdout };
+        result.push((output,buffer.drain(..8+size).skip(8).collect()));
+    }
+    result
+}
+
+/// Parses a detach sequence in docker's format, comma separated keys which are
+/// either single characters or `ctrl-<key>`, e.g. `ctrl-p,ctrl-q`.
+pub fn parse_detach_keys(keys: &str) -> Result<Vec<u8>,String> {
+    keys.split(',').map(|key| {
+        let mut chars = key.strip_prefix("ctrl-").unwrap_or(key).chars();
+        match (chars.next(),chars.next(),key.starts_with("ctrl-")) {
+            (Some(c),None,false) if c.is_ascii() => Ok(c as u8),
+            (Some(c @ 'a'..='z'),None,true) => Ok(c as u8-b'a'+1),
+            (Some(c @ ('@' | '[' | '\\' | ']' | '^' | '_')),None,true) => Ok(c as u8-b'@'),
+            _ => Err(format!("Invalid detach key \"{}\"",key)),
+        }
+    }).collect()
+}
+
+/// Finds the detach sequence in the keys typed during a session. Keys which
+/// might start the sequence are held back until it is complete or broken.
+struct DetachKeys<'a> {
+    sequence: &'a [u8],
+    matched: usize,
+}
+
+impl<'a> DetachKeys<'a> {
+    fn new(sequence: &'a [u8]) -> DetachKeys<'a> {
+        DetachKeys { sequence, matched: 0 }
+    }
+
+    /// Returns the keys to send on and whether the sequence is complete.
+    fn feed(&mut self, keys: &[u8]) -> (Vec<u8>,bool) {
+        let mut forward = Vec::new();
+        for key in keys.iter() {
+            if self.matched > 0 && self.sequence[self.matched] != *key {
+                forward.extend_from_slice(&self.sequence[..self.matched]);
+                self.matched = 0;
+            }
+            if self.sequence.get(self.matched) == Some(key) {
+                self.matched += 1;
+                if self.matched == self.sequence.len() {
+                    self.matched = 0;
+                    return (forward,true);
+                }
+            }
+            else {
+                forward.push(*key);
+            }
+        }
+        (forward,false)
+    }
+}
+
+/// Connects the terminal to a raw session stream until the stream ends or the
+/// user types the `detach` sequence. `resize_path` is the endpoint informing
+/// docker about the terminal size, `tty` tells whether the output is multiplexed.
+async fn run(endpoint: &Endpoint, io: Box<dyn Connection>, initial: Vec<u8>, resize_path: &str, tty: bool, detach: &[u8]) -> Result<(),String> {
+    let raw = std::io::stdout().into_raw_mode().map_err(|e| format!("Could not switch the terminal to raw mode: {}",e))?;
+    let (mut reader,mut writer) = tokio::io::split(io);
+    let mut input = StdinReader::new();
+    let mut resized = signal(SignalKind::window_change()).map_err(|e| format!("Could not listen for terminal resizes: {}",e))?;
+    let mut pending = Vec::new();
+    let mut buffer = [0u8; 4096];
+    let mut output = initial;
+    let mut detach = DetachKeys::new(detach);
+
+    resize(endpoint,resize_path).await;
+    loop {
+        if !output.is_empty() {
+            let data = if tty {
+                output
+            }
+            else {
+                pending.extend(output);
+                demux(&mut pending).into_iter().flat_map(|(_,x)| x).collect()
+            };
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(&data);
+            let _ = stdout.flush();
+        }
+        output = Vec::new();
+        tokio::select! {
+            read = reader.read(&mut buffer) => {
+                match read {
+                    Ok(0) | Err(_) => break,
+                    Ok(x) => output = buffer[..x].to_vec(),
+                }
+            },
+            keys = input.recv() => {
+                let keys = match keys {
+                    Some(x) => x,
+                    None => break,
+                };
+                let (keys,detached) = detach.feed(&keys);
+                writer.write_all(&keys).await.map_err(|e| format!("Could not write to the container: {}",e))?;
+                if detached {
+                    break;
+                }
+            },
+            _ = resized.recv() => {
+                resize(endpoint,resize_path).await;
+            },
+        }
+    }
+    drop(raw);
+    Ok(())
+}
+
+/// Tells docker about the current size of the terminal.
+async fn resize(endpoint: &Endpoint, resize_path: &str) {
+    if let Ok((width,height)) = termion::terminal_size() {
+        let _ = request(endpoint,"POST",&format!("{}?h={}&w={}",resize_path,height,width),None,None).await;
+    }
+}
+
+/// Attaches the terminal to the main process of the running container `id`
+/// until it ends or the user types the `detach` sequence.
+pub async fn attach(id: &str, tty: bool, detach: &[u8]) -> Result<(),String> {
+    let endpoint = Endpoint::from_env()?;
+    let (io,initial) = upgrade(&endpoint,&format!("/containers/{}/attach?stream=1&stdin=1&stdout=1&stderr=1",id),None).await?;
+    run(&endpoint,io,initial,&format!("/containers/{}/resize",id),tty,detach).await
+}
+
+/// Creates an exec instance in the container `id` and returns its id.
+async fn create_exec(endpoint: &Endpoint, id: &str, command: &[String], user: &str, working_dir: &str, tty: bool) -> Result<String,String> {
+    let mut body = serde_json::json!({
+        "AttachStdin": tty,
+        "AttachStdout": tty,
+        "AttachStderr": tty,
+        "Tty": tty,
+        "Cmd": command,
+    });
+    if !user.is_empty() {
+        body["User"] = serde_json::Value::from(user);
+    }
+    if !working_dir.is_empty() {
+        body["WorkingDir"] = serde_json::Value::from(working_dir);
+    }
+    let response = request(endpoint,"POST",&format!("/containers/{}/exec",id),Some(&body),None).await?;
+    let value = serde_json::from_slice::<serde_json::Value>(&response).map_err(|e| format!("Invalid response from docker: {}",e))?;
+    value["Id"].as_str().map(String::from).ok_or_else(|| String::from("Invalid response from docker: missing exec id"))
+}
+
+/// Runs `command` as a new process with its own TTY in the running container
+/// `id` and connects the terminal to it until it ends or the user types the
+/// `detach` sequence. Empty `user` and `working_dir` values fall back to the
+/// settings of the container.
+///
+/// Docker keeps exec processes running when the connection goes away, so the
+/// process records its pid and is sent a SIGHUP once the session ends.
+pub async fn exec(id: &str, command: &[String], user: &str, working_dir: &str, detach: &[u8]) -> Result<(),String> {
+    let endpoint = Endpoint::from_env()?;
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|x| x.subsec_nanos()).unwrap_or(0);
+    let pid_file = format!("/tmp/.dde-exec-{}-{}",std::process::id(),nanos);
+    let mut wrapped = vec![String::from("/bin/sh"),String::from("-c"),String::from("echo $$ > \"$0\"; exec \"$@\""),pid_file.clone()];
+    wrapped.extend(command.iter().cloned());
+
+    let exec_id = create_exec(&endpoint,id,&wrapped,user,working_dir,true).await?;
+    let (io,initial) = upgrade(&endpoint,&format!("/exec/{}/start",exec_id),Some(&serde_json::json!({"Detach": false, "Tty": true}))).await?;
+    let result = run(&endpoint,io,initial,&format!("/exec/{}/resize",exec_id),true,detach).await;
+
+    // The process is gone already unless the user detached, so a failing
+    // cleanup must not hide how the session ended.
+    let cleanup = vec![String::from("/bin/sh"),String::from("-c"),String::from("kill -HUP $(cat \"$0\") 2>/dev/null; rm -f \"$0\""),pid_file];
+    if let Ok(cleanup_id) = create_exec(&endpoint,id,&cleanup,"root","",false).await {
+        let _ = request(&endpoint,"POST",&format!("/exec/{}/start",cleanup_id),Some(&serde_json::json!({"Detach": true, "Tty": false})),None).await;
+    }
+    result
+}
+
+/// The output of a container streamed from docker, see `logs`.
+pub struct OutputStream {
+    io: Box<dyn Connection>,
+    chunked: bool,
+    tty: bool,
+    raw: Vec<u8>,
+    body: Vec<u8>,
+    finished: bool,
+}
+
+impl OutputStream {
+    /// Waits for the next pieces of output, `None` once the stream ended.
+    pub async fn next(&mut self) -> Result<Option<Vec<(Output,Vec<u8>)>>,String> {
+        let mut buffer = [0u8; 4096];
+        loop {
+            if self.chunked {
+                self.finished |= take_chunks(&mut self.raw,&mut self.body);
+            }
+            else {
+                self.body.append(&mut self.raw);
+            }
+            let output = if self.tty {
+                if self.body.is_empty() { Vec::new() } else { vec![(Output::Stdout,std::mem::take(&mut self.body))] }
+            }
+            else {
+                demux(&mut self.body)
+            };
+            if !output.is_empty() {
+                return Ok(Some(output));
+            }
+            if self.finished {
+                return Ok(None);
+            }
+            match self.io.read(&mut buffer).await {
+                Ok(0) => self.finished = true,
+                Ok(x) => self.raw.extend_from_slice(&buffer[..x]),
+                Err(e) => return Err(format!("Could not read from docker: {}",e)),
+            }
+        }
+    }
+}
+
+/// Sends a GET request whose response body is streamed by the returned stream.
+async fn open_stream(path: &str, tty: bool) -> Result<OutputStream,String> {
+    let endpoint = Endpoint::from_env()?;
+    let mut io = endpoint.connect().await?;
+    send_request(&mut io,"GET",path,None,None,false).await?;
+    let mut head = read_head(&mut io).await?;
+    if head.status >= 300 {
+        io.read_to_end(&mut head.rest).await.map_err(|e| format!("Could not read response from docker: {}",e))?;
+        let body = if head.chunked { dechunk(&head.rest) } else { head.rest };
+        return Err(error_message(head.status,&body));
+    }
+    Ok(OutputStream {
+        io,
+        chunked: head.chunked,
+        tty,
+        raw: head.rest,
+        body: Vec::new(),
+        finished: false,
+    })
+}
+
+/// Streams the last `tail` lines of output of the container `id` prefixed with
+/// their timestamps and keeps following new output while the container runs.
+pub async fn logs(id: &str, tty: bool, tail: usize) -> Result<OutputStream,String> {
+    open_stream(&format!("/containers/{}/logs?stdout=1&stderr=1&timestamps=1&follow=1&tail={}",id,tail),tty).await
+}
+
+/// Streams the resource usage of the container `id` as JSON documents, one per
+/// line. Without `stream` docker sends a single document and ends the stream.
+pub async fn stats(id: &str, stream: bool) -> Result<OutputStream,String> {
+    open_stream(&format!("/containers/{}/stats?stream={}",id,stream as u8),true).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_detach_keys() {
+        assert_eq!(parse_detach_keys("ctrl-p,ctrl-q"),Ok(vec![0x10,0x11]));
+        assert_eq!(parse_detach_keys("ctrl-@,ctrl-_,x"),Ok(vec![0x00,0x1f,b'x']));
+        assert!(parse_detach_keys("").is_err());
+        assert!(parse_detach_keys("ctrl-").is_err());
+        assert!(parse_detach_keys("ctrl-P").is_err());
+        assert!(parse_detach_keys("ab").is_err());
+    }
+
+    #[test]
+    fn detaches_on_the_whole_sequence_only() {
+        let sequence = [0x10,0x11];
+        let mut detach = DetachKeys::new(&sequence);
+        assert_eq!(detach.feed(b"ls\x04"),(b"ls\x04".to_vec(),false));
+        assert_eq!(detach.feed(&[0x10]),(Vec::new(),false));
+        assert_eq!(detach.feed(b"a"),(vec![0x10,b'a'],false));
+        assert_eq!(detach.feed(&[0x10,0x10]),(vec![0x10],false));
+        assert_eq!(detach.feed(&[0x11,b'b']),(Vec::new(),true));
+    }
+}
@@ -0,0 +1,216 @@
+//! Resource usage of containers.
+//!
+//! The samples are read from the docker stats endpoint directly, shiplift's
+//! `Stats` lacks `precpu_stats` and fails to parse the output of cgroup v2 hosts.
+use std::collections::{HashMap,VecDeque};
+use std::time::Instant;
+use serde::Deserialize;
+use crate::session::{self,OutputStream};
+
+/// Number of samples kept as history, docker sends one sample per second.
+pub const HISTORY_LENGTH: usize = 300;
+
+#[derive(Deserialize,Default)]
+#[serde(default)]
+struct CpuUsage {
+    total_usage: u64,
+    percpu_usage: Option<Vec<u64>>,
+}
+
+#[derive(Deserialize,Default)]
+#[serde(default)]
+struct CpuStats {
+    cpu_usage: CpuUsage,
+    system_cpu_usage: u64,
+    online_cpus: u32,
+}
+
+#[derive(Deserialize,Default)]
+#[serde(default)]
+struct MemoryStats {
+    usage: u64,
+    limit: u64,
+    stats: HashMap<String,u64>,
+}
+
+#[derive(Deserialize,Default)]
+#[serde(default)]
+struct NetworkStats {
+    rx_bytes: u64,
+    tx_bytes: u64,
+}
+
+#[derive(Deserialize,Default)]
+#[serde(default)]
+struct BlkioStat {
+    op: String,
+    value: u64,
+}
+
+#[derive(Deserialize,Default)]
+#[serde(default)]
+struct BlkioStats {
+    io_service_bytes_recursive: Option<Vec<BlkioStat>>,
+}
+
+#[derive(Deserialize,Default)]
+#[serde(default)]
+struct PidsStats {
+    current: u64,
+}
+
+/// A single sample of the stats stream.
+#[derive(Deserialize,Default)]
+#[serde(default)]
+pub struct Sample {
+    cpu_stats: CpuStats,
+    precpu_stats: CpuStats,
+    memory_stats: MemoryStats,
+    networks: Option<HashMap<String,NetworkStats>>,
+    blkio_stats: BlkioStats,
+    pids_stats: PidsStats,
+}
+
+impl Sample {
+    /// CPU usage since the previous sample in percent of one core, computed
+    /// like `docker stats` does.
+    pub fn cpu_percent(&self) -> f64 {
+        let cpu_delta = self.cpu_stats.cpu_usage.total_usage.saturating_sub(self.precpu_stats.cpu_usage.total_usage);
+        let system_delta = self.cpu_stats.system_cpu_usage.saturating_sub(self.precpu_stats.system_cpu_usage);
+        let cpus = match self.cpu_stats.online_cpus {
+            0 => self.cpu_stats.cpu_usage.percpu_usage.as_ref().map(|x| x.len()).unwrap_or(1).max(1) as f64,
+            x => x as f64,
+        };
+        if cpu_delta == 0 || system_delta == 0 {
+            return 0.0;
+        }
+        cpu_delta as f64 / system_delta as f64 * cpus * 100.0
+    }
+
+    /// Used memory without the page cache.
+    pub fn memory_usage(&self) -> u64 {
+        let stats = &self.memory_stats.stats;
+        let cache = stats.get("total_inactive_file").or_else(|| stats.get("inactive_file")).copied().unwrap_or(0);
+        self.memory_stats.usage.saturating_sub(cache)
+    }
+
+    pub fn memory_limit(&self) -> u64 {
+        self.memory_stats.limit
+    }
+
+    pub fn memory_percent(&self) -> f64 {
+        if self.memory_limit() == 0 {
+            return 0.0;
+        }
+        self.memory_usage() as f64 / self.memory_limit() as f64 * 100.0
+    }
+
+    /// Total bytes received and transmitted over all networks.
+    pub fn network(&self) -> (u64,u64) {
+        self.networks.iter().flat_map(|x| x.values()).fold((0,0),|(rx,tx),x| (rx+x.rx_bytes,tx+x.tx_bytes))
+    }
+
+    /// Total bytes read from and written to block devices.
+    pub fn block_io(&self) -> (u64,u64) {
+        self.blkio_stats.io_service_bytes_recursive.iter().flatten().fold((0,0),|(read,write),x| match x.op.to_lowercase().as_str() {
+            "read" => (read+x.value,write),
+            "write" => (read,write+x.value),
+            _ => (read,write),
+        })
+    }
+
+    pub fn pids(&self) -> u64 {
+        self.pids_stats.current
+    }
+}
+
+/// A continuous stream of samples of a single container.
+pub struct StatsStream {
+    stream: OutputStream,
+    buffer: Vec<u8>,
+}
+
+impl StatsStream {
+    pub async fn open(id: &str) -> Result<StatsStream,String> {
+        Ok(StatsStream {
+            stream: session::stats(id,true).await?,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Waits for the next sample, `None` once the container was removed.
+    pub async fn next(&mut self) -> Result<Option<Sample>,String> {
+        loop {
+            if let Some(end) = self.buffer.iter().position(|x| *x == b'\n') {
+                let line = self.buffer.drain(..end+1).collect::<Vec<u8>>();
+                if line.iter().all(|x| x.is_ascii_whitespace()) {
+                    continue;
+                }
+                return serde_json::from_slice(&line).map(Some).map_err(|e| format!("Invalid stats from docker: {}",e));
+            }
+            match self.stream.next().await? {
+                Some(output) => self.buffer.extend(output.into_iter().flat_map(|(_,x)| x)),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Takes a single sample of the container `id`.
+pub async fn sample(id: &str) -> Result<Sample,String> {
+    let mut stream = StatsStream {
+        stream: session::stats(id,false).await?,
+        buffer: Vec::new(),
+    };
+    stream.next().await?.ok_or_else(|| String::from("Docker did not send any stats"))
+}
+
+/// The values of a sample plotted over time, rates are in bytes per second.
+#[derive(Clone,Copy,Default)]
+pub struct Point {
+    pub cpu_percent: f64,
+    pub memory: u64,
+    pub rx_rate: u64,
+    pub tx_rate: u64,
+    pub read_rate: u64,
+    pub write_rate: u64,
+}
+
+/// The history of the last `HISTORY_LENGTH` samples of a container.
+#[derive(Default)]
+pub struct History {
+    pub points: VecDeque<Point>,
+    pub last: Option<Sample>,
+    last_at: Option<Instant>,
+}
+
+impl History {
+    pub fn push(&mut self, sample: Sample) {
+        let now = Instant::now();
+        let mut point = Point {
+            cpu_percent: sample.cpu_percent(),
+            memory: sample.memory_usage(),
+            ..Point::default()
+        };
+        if let (Some(last),Some(last_at)) = (&self.last,self.last_at) {
+            let seconds = now.duration_since(last_at).as_secs_f64().max(0.001);
+            let rate = |now: u64, before: u64| (now.saturating_sub(before) as f64 / seconds) as u64;
+            let ((rx,tx),(last_rx,last_tx)) = (sample.network(),last.network());
+            let ((read,write),(last_read,last_write)) = (sample.block_io(),last.block_io());
+            point.rx_rate = rate(rx,last_rx);
+            point.tx_rate = rate(tx,last_tx);
+            point.read_rate = rate(read,last_read);
+            point.write_rate = rate(write,last_write);
+        }
+        self.points.push_back(point);
+        while self.points.len() > HISTORY_LENGTH {
+            self.points.pop_front();
+        }
+        self.last = Some(sample);
+        self.last_at = Some(now);
+    }
+
+    pub fn current(&self) -> Point {
+        self.points.back().copied().unwrap_or_default()
+    }
+}
@@ -1,4 +1,7 @@
-use std::io;
+use std::future::Future;
+use std::path::PathBuf;
+use shiplift::{Docker,ImageListOptions};
+use percent_encoding::{utf8_percent_encode,NON_ALPHANUMERIC};
 use tui::{
     backend::Backend,
     layout::{Constraint, Direction, Layout,Alignment},
@@ -8,28 +11,140 @@ use tui::{
     Terminal,
 };
 use termion::event::Key;
-use termion::input::TermRead;
+use crate::ui::events::{Event,Events};
+use crate::ui::tasks::{Progress,TaskStatus,Tasks};
 use crate::ui::AppState;
+use crate::config::Config;
+use crate::container::{self,ExecSetup};
+use crate::images;
+use crate::query;
+use crate::ui::export;
+use crate::ui::popup::AppPopupContext;
+use crate::ui::search::attach_to_container;
+use crate::ui::text_input::TextInput;
 use crate::VERSION;
 
+/// Lines kept in the output panel.
+const MAX_OUTPUT: usize = 1000;
+/// Commands kept in the history file.
+const MAX_HISTORY: usize = 500;
+
+/// What the arguments of a command complete to.
+#[derive(Clone,Copy,PartialEq)]
+enum Completion {
+    Nothing,
+    Container,
+    Image,
+    Command,
+}
+
+struct CommandInfo {
+    name: &'static str,
+    usage: &'static str,
+    description: &'static str,
+    /// Completion of the first and of all further arguments.
+    arguments: (Completion,Completion),
+}
+
+const COMMANDS: [CommandInfo; 17] = [
+    CommandInfo { name: "start", usage: "start <container>...", description: "Start the containers", arguments: (Completion::Container,Completion::Container) },
+    CommandInfo { name: "stop", usage: "stop <container>...", description: "Stop the containers", arguments: (Completion::Container,Completion::Container) },
+    CommandInfo { name: "restart", usage: "restart <container>...", description: "Restart the containers", arguments: (Completion::Container,Completion::Container) },
+    CommandInfo { name: "rm", usage: "rm <container>...", description: "Stop and remove the containers", arguments: (Completion::Container,Completion::Container) },
+    CommandInfo { name: "rename", usage: "rename <container> <new name>", description: "Rename a container", arguments: (Completion::Container,Completion::Nothing) },
+    CommandInfo { name: "attach", usage: "attach <container>", description: "Start the container and attach to it", arguments: (Completion::Container,Completion::Nothing) },
+    CommandInfo { name: "exec", usage: "exec [--user <user>] [--workdir <dir>] <container> [command...]", description: "Run a command in the container, by default its own command", arguments: (Completion::Container,Completion::Nothing) },
+    CommandInfo { name: "logs", usage: "logs <container>", description: "Show the logs of the container", arguments: (Completion::Container,Completion::Nothing) },
+    CommandInfo { name: "new", usage: "new", description: "Create a new container", arguments: (Completion::Nothing,Completion::Nothing) },
+    CommandInfo { name: "pull", usage: "pull <image>[:<tag>]", description: "Pull an image from its registry", arguments: (Completion::Image,Completion::Nothing) },
+    CommandInfo { name: "commit", usage: "commit <container> <image>[:<tag>]", description: "Create an image from the changes of a container", arguments: (Completion::Container,Completion::Image) },
+    CommandInfo { name: "export", usage: "export <container> [host directory]", description: "Copy the working directory of the container back to the host", arguments: (Completion::Container,Completion::Nothing) },
+    CommandInfo { name: "images", usage: "images", description: "Manage the local images", arguments: (Completion::Nothing,Completion::Nothing) },
+    CommandInfo { name: "tasks", usage: "tasks", description: "Show the background tasks", arguments: (Completion::Nothing,Completion::Nothing) },
+    CommandInfo { name: "help", usage: "help [command]", description: "Show the commands or the usage of one", arguments: (Completion::Command,Completion::Nothing) },
+    CommandInfo { name: "clear", usage: "clear", description: "Clear the output", arguments: (Completion::Nothing,Completion::Nothing) },
+    CommandInfo { name: "quit", usage: "quit", description: "Quit the program", arguments: (Completion::Nothing,Completion::Nothing) },
+];
+
+fn command_info(name: &str) -> Option<&'static CommandInfo> {
+    COMMANDS.iter().find(|x| x.name == name)
+}
+
+fn usage(name: &str) -> String {
+    command_info(name).map(|x| format!("Usage: {}",x.usage)).unwrap_or_default()
+}
+
+/// The longest prefix all candidates share.
+fn common_prefix(candidates: &[String]) -> String {
+    let mut prefix = candidates[0].clone();
+    for candidate in candidates[1..].iter() {
+        let length = prefix.chars().zip(candidate.chars()).take_while(|(a,b)| a == b).map(|(a,_)| a.len_utf8()).sum();
+        prefix.truncate(length);
+    }
+    prefix
+}
+
+fn history_path() -> Option<PathBuf> {
+    Some(Config::data_dir()?.join("history"))
+}
+
+/// Pulls `image` and reports the state of its layers.
+async fn pull_image(docker: Docker, image: String, progress: Progress) -> Result<String,String> {
+    images::pull(&docker,&image,|pull| progress.set(pull.lines().join("\n"),pull.fraction())).await
+}
+
+/// Creates the image `image` from the changes of the container `name`.
+async fn commit_container(docker: Docker, name: String, image: String) -> Result<String,String> {
+    let id = container::find(&docker,&name).await?.id;
+    let (repo,tag) = images::split_tag(&image);
+    let tag = tag.unwrap_or("latest");
+    let query = [("container",&id[..]),("repo",repo),("tag",tag)].iter().map(|(key,value)| format!("{}={}",key,utf8_percent_encode(value,NON_ALPHANUMERIC))).collect::<Vec<String>>().join("&");
+    let result = crate::session::post_json(&format!("/commit?{}",query),Some(&serde_json::json!({}))).await
+        .map_err(|e| format!("Could not commit {}: {}",name,e))?;
+    let image = result["Id"].as_str().unwrap_or("");
+    Ok(format!("Committed {} as {}:{} ({})",name,repo,tag,image.trim_start_matches("sha256:").chars().take(12).collect::<String>()))
+}
+
 pub struct AppCommandContext {
-    command_term: String,
-    output_log: String,
+    command_term: TextInput,
+    output: Vec<String>,
+    /// Lines the output is scrolled up from the bottom.
+    scroll: usize,
+    output_height: usize,
+    history: Vec<String>,
+    history_idx: Option<usize>,
+    /// The unfinished command while browsing the history.
+    draft: String,
+    config: Config,
+    tasks: Tasks,
+    /// Background tasks whose result is yet to be printed.
+    pending: Vec<usize>,
 }
 
-impl AppCommandContext { 
-    pub fn new() -> AppCommandContext { 
-        AppCommandContext { 
-            command_term: String::new(),
-            output_log: String::new(),
+impl AppCommandContext {
+    pub fn new(config: Config, tasks: Tasks) -> AppCommandContext {
+        let history = history_path().and_then(|x| std::fs::read_to_string(x).ok())
+            .map(|x| x.lines().filter(|y| !y.trim().is_empty()).map(String::from).collect())
+            .unwrap_or_default();
+        AppCommandContext {
+            command_term: TextInput::default(),
+            output: Vec::new(),
+            scroll: 0,
+            output_height: 0,
+            history,
+            history_idx: None,
+            draft: String::new(),
+            config,
+            tasks,
+            pending: Vec::new(),
         }
     }
 
-    pub fn render<B: Backend>(&mut self,terminal: &mut Terminal<B>) {
+    pub fn render<B: Backend>(&mut self,terminal: &mut Terminal<B>, popup: Option<&mut AppPopupContext>) {
         terminal.draw(|f| {
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
-                .constraints([Constraint::Length(1),Constraint::Length(3),Constraint::Min(1),Constraint::Length(3)].as_ref())
+                .constraints([Constraint::Length(1),Constraint::Length(3),Constraint::Min(1),Constraint::Length(3),Constraint::Length(1)].as_ref())
                 .split(f.size());
 
             let style_non = Style::default().fg(Color::Blue);
@@ -38,60 +153,414 @@ impl AppCommandContext {
             let header_paragraph = Paragraph::new(Spans::from(vec![Span::styled(VERSION,Style::default().fg(Color::LightRed)),Span::raw(" | Command mode")]))
                 .style(Style::default().bg(Color::Black).fg(Color::LightYellow))
                 .alignment(Alignment::Center);
-            let paragraph = Paragraph::new(Span::raw(format!(">> {}",&self.command_term))).style(style_hi.clone())
-                                    .block(Block::default().borders(Borders::ALL).title("Command line"))
-                                    .alignment(Alignment::Left);
             let style_help = Style::default().fg(Color::Cyan);
             f.render_widget(header_paragraph,chunks[0]);
-            f.render_widget(paragraph,chunks[1]);
-            let log = Paragraph::new(Text::from(&self.output_log[..])).style(style_non)
-                                    .block(Block::default().borders(Borders::ALL).style(Style::default().fg(Color::Gray)).title("Command output"))
+            self.command_term.render_on(f,chunks[1],">> ",Block::default().borders(Borders::ALL).title("Command line"),style_hi,popup.is_none());
+
+            self.output_height = chunks[2].height.saturating_sub(2) as usize;
+            self.scroll = self.scroll.min(self.output.len().saturating_sub(self.output_height));
+            let end = self.output.len()-self.scroll;
+            let start = end.saturating_sub(self.output_height);
+            let mut title = String::from("Command output");
+            if self.scroll > 0 {
+                title.push_str(&format!(" [{} more lines below]",self.scroll));
+            }
+            let lines = self.output[start..end].iter().map(|x| Spans::from(x.clone())).collect::<Vec<Spans>>();
+            let log = Paragraph::new(Text::from(lines)).style(style_non)
+                                    .block(Block::default().borders(Borders::ALL).style(Style::default().fg(Color::Gray)).title(title))
                                     .alignment(Alignment::Left);
-            let help = Paragraph::new(Text::from("Ctrl+h - Show full help")).style(style_help)
+            let help = Paragraph::new(Text::from("Ctrl+h - Show full help; Ctrl+t - tasks; help - list commands; tab - complete; up/down - history; page up/down - scroll output")).style(style_help)
                                     .block(Block::default().borders(Borders::ALL).title("Help"))
                                     .alignment(Alignment::Left);
             f.render_widget(log,chunks[2]);
             f.render_widget(help,chunks[3]);
+            f.render_widget(self.tasks.status_bar(),chunks[4]);
+            if let Some(popup) = popup {
+                popup.render_on(f);
+            }
         }).unwrap();
-        terminal.show_cursor().unwrap();
-        terminal.set_cursor(self.command_term.len() as u16+4, 2).unwrap();
-    }
-
-    pub fn event_loop<B: Backend>(&mut self, term: &mut Terminal<B>) -> AppState {
-        let stdin = io::stdin();
-        self.render(term);
-        for evt in stdin.keys() {
-            match evt {
-                Ok(Key::Char(r)) => {
-                    if r == '\n' {
-                        if self.command_term == "quit" {
-                            return AppState::Exiting;
-                        }
-                        else {
-                            self.output_log = format!("Unkown command \"{}\"\n",self.command_term);
-                            self.command_term.clear();
+    }
+
+    /// Appends the lines of `text` to the output and scrolls to the bottom.
+    fn print(&mut self, text: &str) {
+        self.output.extend(text.lines().map(String::from));
+        if self.output.len() > MAX_OUTPUT {
+            self.output.drain(..self.output.len()-MAX_OUTPUT);
+        }
+        self.scroll = 0;
+    }
+
+    fn add_history(&mut self, command: &str) {
+        if self.history.last().map(|x| x == command).unwrap_or(false) {
+            return;
+        }
+        self.history.push(command.to_owned());
+        if self.history.len() > MAX_HISTORY {
+            self.history.drain(..self.history.len()-MAX_HISTORY);
+        }
+        if let Some(path) = history_path() {
+            if let Some(parent) = path.parent() {
+                let _ = std::fs::create_dir_all(parent);
+            }
+            if let Err(e) = std::fs::write(&path,self.history.join("\n")+"\n") {
+                self.print(&format!("Could not write the history to \"{}\": {}",path.display(),e));
+            }
+        }
+    }
+
+    fn history_previous(&mut self) {
+        let idx = match self.history_idx {
+            Some(x) => x.saturating_sub(1),
+            None if self.history.is_empty() => return,
+            None => {
+                self.draft = self.command_term.value().to_owned();
+                self.history.len()-1
+            },
+        };
+        self.history_idx = Some(idx);
+        self.command_term.set_value(self.history[idx].clone());
+    }
+
+    fn history_next(&mut self) {
+        match self.history_idx {
+            Some(x) if x+1 < self.history.len() => {
+                self.history_idx = Some(x+1);
+                self.command_term.set_value(self.history[x+1].clone());
+            },
+            Some(_) => {
+                self.history_idx = None;
+                self.command_term.set_value(std::mem::take(&mut self.draft));
+            },
+            None => {},
+        }
+    }
+
+    async fn container_names(docker: &Docker) -> Vec<String> {
+        container::list(docker,true).await.map(|x| x.iter().map(|y| container::display_name(y).to_owned()).collect()).unwrap_or_default()
+    }
+
+    async fn image_names(docker: &Docker) -> Vec<String> {
+        let images = docker.images().list(&ImageListOptions::default()).await.unwrap_or_default();
+        images.into_iter().flat_map(|x| x.repo_tags.unwrap_or_default()).filter(|x| x != "<none>:<none>").collect()
+    }
+
+    /// Completes the last word of the command line, lists the candidates if
+    /// there is more than one.
+    async fn complete(&mut self, docker: &Docker) {
+        let line = self.command_term.before_cursor().to_owned();
+        // Split the same way `execute` does, a quote left open belongs to the
+        // word being completed.
+        let (mut words,quoted) = match query::split(&line) {
+            Ok(x) => (x,false),
+            Err(_) => match query::split(&format!("{}\"",line)) {
+                Ok(x) => (x,true),
+                Err(_) => return,
+            },
+        };
+        if words.is_empty() || (quoted && line.ends_with('"')) || (!quoted && line.ends_with(char::is_whitespace)) {
+            words.push(String::new());
+        }
+        let word = words[words.len()-1].as_str();
+        let completion = if words.len() == 1 {
+            Completion::Command
+        }
+        else {
+            // Options and their values are not arguments.
+            let arguments = words[1..words.len()-1].iter().fold((0,false),|(count,value),x| {
+                if value { (count,false) } else if x.starts_with("--") { (count,true) } else { (count+1,false) }
+            });
+            match command_info(&words[0]) {
+                _ if word.starts_with("--") || arguments.1 => Completion::Nothing,
+                Some(info) if arguments.0 == 0 => info.arguments.0,
+                Some(info) => info.arguments.1,
+                None => Completion::Nothing,
+            }
+        };
+        let candidates = match completion {
+            Completion::Nothing => return,
+            Completion::Command => COMMANDS.iter().map(|x| x.name.to_owned()).collect(),
+            Completion::Container => AppCommandContext::container_names(docker).await,
+            Completion::Image => AppCommandContext::image_names(docker).await,
+        };
+        let mut candidates = candidates.into_iter().filter(|x| x.starts_with(word)).collect::<Vec<String>>();
+        candidates.sort();
+        candidates.dedup();
+        if candidates.is_empty() {
+            return;
+        }
+        let mut completed = common_prefix(&candidates);
+        if candidates.len() == 1 {
+            if quoted {
+                completed.push('"');
+            }
+            completed.push(' ');
+        }
+        else if completed == word {
+            self.print(&candidates.join("  "));
+        }
+        // The candidates all start with the word, only the rest is inserted.
+        self.command_term.insert_str(&completed[word.len()..]);
+    }
+
+    /// `export <container> [host directory]` - copies the working directory of a container back to the host.
+    async fn export<B: Backend>(&mut self, term: &mut Terminal<B>, docker: &Docker, events: &mut Events, args: &[String]) -> String {
+        if args.is_empty() || args.len() > 2 {
+            return usage("export");
+        }
+        let host = match args.get(1) {
+            Some(x) => PathBuf::from(x),
+            None => std::env::current_dir().unwrap(),
+        };
+        let target = self.config.mount_target.clone();
+        match export::export_workspace(docker,&args[0],&target,&host,events,|popup| self.render(term,Some(popup))).await {
+            Ok(message) => message,
+            Err(error) => error,
+        }
+    }
+
+    /// Runs `task` in the background, its result is printed once it finished.
+    fn run_task<F,T>(&mut self, title: String, task: F)
+        where F: FnOnce(Progress) -> T, T: Future<Output = Result<String,String>> + Send + 'static {
+        self.print(&format!("{} in the background",title));
+        let id = self.tasks.spawn(title,task);
+        self.pending.push(id);
+    }
+
+    /// Prints the results of the tasks started here which finished meanwhile.
+    fn report_tasks(&mut self) {
+        let mut finished = Vec::new();
+        for id in self.pending.iter() {
+            match self.tasks.get(*id).map(|x| x.status) {
+                Some(TaskStatus::Running) => {},
+                Some(TaskStatus::Succeeded(x)) | Some(TaskStatus::Failed(x)) => finished.push((*id,Some(x))),
+                None => finished.push((*id,None)),
+            }
+        }
+        for (id,message) in finished {
+            self.pending.retain(|x| *x != id);
+            if let Some(message) = message {
+                self.print(&message);
+            }
+        }
+    }
+
+    /// Runs `name` on every given container as a background task each.
+    fn for_each_container(&mut self, docker: &Docker, name: &str, args: &[String]) {
+        if args.is_empty() {
+            self.print(&usage(name));
+            return;
+        }
+        let (running,done) = match name {
+            "start" => ("Starting","Started"),
+            "stop" => ("Stopping","Stopped"),
+            "restart" => ("Restarting","Restarted"),
+            _ => ("Removing","Removed"),
+        };
+        for arg in args.iter() {
+            let (docker,name,arg) = (docker.clone(),name.to_owned(),arg.clone());
+            self.run_task(format!("{} {}",running,arg),move |_| async move {
+                let id = container::find(&docker,&arg).await?.id;
+                match name.as_str() {
+                    "start" => docker.containers().get(&id).start().await.map_err(|e| format!("Could not start container: {}",e)),
+                    "stop" => container::stop(&docker,&id).await,
+                    "restart" => docker.containers().get(&id).restart(None).await.map_err(|e| format!("Could not restart container: {}",e)),
+                    _ => container::remove(&docker,&id).await,
+                }.map(|_| format!("{} {}",done,arg)).map_err(|e| format!("{}: {}",arg,e))
+            });
+        }
+    }
+
+    fn help(&mut self, args: &[String]) {
+        match args.first() {
+            Some(name) => match command_info(name) {
+                Some(info) => self.print(&format!("{}\n    {}",info.usage,info.description)),
+                None => self.print(&format!("Unknown command \"{}\"",name)),
+            },
+            None => {
+                let width = COMMANDS.iter().map(|x| x.usage.len()).max().unwrap_or(0);
+                let text = COMMANDS.iter().map(|x| format!("{:width$}  {}",x.usage,x.description,width = width)).collect::<Vec<String>>().join("\n");
+                self.print(&text);
+            },
+        }
+    }
+
+    /// Attaches to the container or runs a command in it, returning to the
+    /// command line once the session ends.
+    async fn open<B: Backend>(&mut self, term: &mut Terminal<B>, docker: &Docker, events: &mut Events, name: &str, exec: Option<&ExecSetup>) {
+        let result = match container::find(docker,name).await {
+            Ok(x) => attach_to_container(docker,events,&x.id,exec,&self.config.detach_sequence()).await,
+            Err(e) => Err(e),
+        };
+        term.clear().unwrap();
+        if let Err(e) = result {
+            self.print(&e);
+        }
+    }
+
+    /// Parses `exec [--user <user>] [--workdir <dir>] <container> [command...]`.
+    fn parse_exec(args: &[String]) -> Result<(String,ExecSetup),String> {
+        let mut setup = ExecSetup::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--user" | "-u" => setup.user = args.next().ok_or_else(|| usage("exec"))?.clone(),
+                "--workdir" | "-w" => setup.working_dir = args.next().ok_or_else(|| usage("exec"))?.clone(),
+                x if x.starts_with('-') => return Err(format!("Unknown option \"{}\"\n{}",x,usage("exec"))),
+                x => {
+                    setup.command = args.cloned().collect();
+                    return Ok((x.to_owned(),setup));
+                },
+            }
+        }
+        Err(usage("exec"))
+    }
+
+    /// Runs the command line, returns the next state if the command leaves
+    /// command mode.
+    async fn execute<B: Backend>(&mut self, term: &mut Terminal<B>, docker: &Docker, events: &mut Events, line: &str) -> Option<AppState> {
+        let words = match query::split(line) {
+            Ok(x) => x,
+            Err(e) => {
+                self.print(&e);
+                return None;
+            },
+        };
+        let (name,args) = match words.split_first() {
+            Some(x) => x,
+            None => return None,
+        };
+        match name.as_str() {
+            "quit" | "exit" => return Some(AppState::Exiting),
+            "help" => self.help(args),
+            "clear" => self.output.clear(),
+            "rm" if !args.is_empty() => {
+                let result = AppPopupContext::decision(format!("Do you really want to delete {} (y) - yes, (n) - no?",args.join(", "))).style(Style::default().fg(Color::LightRed)).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+                if result == "yes" {
+                    self.for_each_container(docker,name,args);
+                }
+            },
+            "start" | "stop" | "restart" | "rm" => self.for_each_container(docker,name,args),
+            "rename" => {
+                if args.len() != 2 {
+                    self.print(&usage("rename"));
+                    return None;
+                }
+                let result = match container::find(docker,&args[0]).await {
+                    Ok(x) => docker.containers().get(&x.id).rename(&args[1]).await.map_err(|e| format!("Could not rename container: {}",e)),
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(_) => self.print(&format!("Renamed {} to {}",args[0],args[1])),
+                    Err(e) => self.print(&e),
+                }
+            },
+            "attach" => {
+                if args.len() != 1 {
+                    self.print(&usage("attach"));
+                    return None;
+                }
+                self.open(term,docker,events,&args[0],None).await;
+            },
+            "exec" => match AppCommandContext::parse_exec(args) {
+                Ok((container,setup)) => self.open(term,docker,events,&container,Some(&setup)).await,
+                Err(e) => self.print(&e),
+            },
+            "logs" => {
+                if args.len() != 1 {
+                    self.print(&usage("logs"));
+                    return None;
+                }
+                match container::find(docker,&args[0]).await {
+                    Ok(x) => return Some(AppState::Logs(x.id)),
+                    Err(e) => self.print(&e),
+                }
+            },
+            "new" => return Some(AppState::NewContainer),
+            "pull" => {
+                if args.len() != 1 {
+                    self.print(&usage("pull"));
+                    return None;
+                }
+                let (docker,image) = (docker.clone(),args[0].clone());
+                self.run_task(format!("Pulling {}",image),move |progress| pull_image(docker,image,progress));
+            },
+            "commit" => {
+                if args.len() != 2 {
+                    self.print(&usage("commit"));
+                    return None;
+                }
+                let (docker,name,image) = (docker.clone(),args[0].clone(),args[1].clone());
+                self.run_task(format!("Committing {}",name),move |_| commit_container(docker,name,image));
+            },
+            "tasks" => return Some(AppState::Tasks),
+            "images" => return Some(AppState::Images),
+            "export" => {
+                let message = self.export(term,docker,events,args).await;
+                self.print(&message);
+            },
+            _ => self.print(&format!("Unknown command \"{}\", type help for a list of commands",name)),
+        }
+        None
+    }
+
+    pub async fn event_loop<B: Backend>(&mut self, term: &mut Terminal<B>, docker: &Docker, events: &mut Events) -> AppState {
+        self.render(term,None);
+        loop {
+            match events.next().await {
+                Event::Key(Key::Char('\n')) => {
+                    let command = std::mem::take(&mut self.command_term).value().to_owned();
+                    self.history_idx = None;
+                    if !command.trim().is_empty() {
+                        self.print(&format!(">> {}",command));
+                        self.add_history(command.trim());
+                        self.render(term,None);
+                        if let Some(state) = self.execute(term,docker,events,&command).await {
+                            return state;
                         }
                     }
-                    else {
-                        self.command_term.push(r);
-                    }
                 },
-                Ok(Key::Backspace) => {
-                    self.command_term.pop();
+                Event::Key(Key::Char('\t')) => {
+                    self.complete(docker).await;
+                },
+                Event::Paste(text) => {
+                    self.command_term.insert_str(&text);
+                },
+                Event::Key(Key::Up) => {
+                    self.history_previous();
+                },
+                Event::Key(Key::Down) => {
+                    self.history_next();
+                },
+                Event::Key(Key::PageUp) => {
+                    self.scroll += self.output_height.max(1);
                 },
-                Ok(Key::Ctrl('c')) => {
+                Event::Key(Key::PageDown) => {
+                    self.scroll = self.scroll.saturating_sub(self.output_height.max(1));
+                },
+                Event::Key(Key::Ctrl('c')) => {
                     return AppState::Exiting;
                 }
-                Ok(Key::Ctrl('h')) => {
+                Event::Key(Key::Ctrl('h')) => {
                     return AppState::Help;
                 },
-                Ok(Key::Ctrl('s')) => {
+                Event::Key(Key::Ctrl('s')) => {
                     return AppState::Search;
                 },
+                Event::Key(Key::Ctrl('t')) => {
+                    return AppState::Tasks;
+                },
+                Event::Task => {
+                    self.report_tasks();
+                },
+                Event::InputClosed => {
+                    return AppState::Exiting;
+                },
+                Event::Key(key) => {
+                    self.command_term.handle_key(key);
+                },
                 _ => {}
             }
-            self.render(term);
+            self.render(term,None);
         }
-        return AppState::Exiting;
     }
 }
@@ -0,0 +1,156 @@
+use chrono::{DateTime,Utc};
+use serde_json::Value;
+use tui::{
+    style::{Modifier, Style},
+    text::{Span,Spans},
+};
+use crate::session;
+use crate::run_command::is_secret;
+
+/// The inspection of a container in a readable form, grouped into sections.
+pub struct ContainerDetails {
+    sections: Vec<(String,Vec<String>)>,
+}
+
+fn string(value: &Value) -> String {
+    value.as_str().unwrap_or("").to_owned()
+}
+
+fn strings(value: &Value) -> Vec<String> {
+    value.as_array().map(|x| x.iter().map(string).collect()).unwrap_or_default()
+}
+
+fn parse_time(value: &Value) -> Option<DateTime<Utc>> {
+    let time = DateTime::parse_from_rfc3339(value.as_str()?).ok()?.with_timezone(&Utc);
+    // Docker reports "0001-01-01T00:00:00Z" for events that never happened.
+    if time.timestamp() <= 0 {
+        return None;
+    }
+    Some(time)
+}
+
+pub(crate) fn format_duration(seconds: i64) -> String {
+    let (days,hours,minutes) = (seconds/86400,seconds%86400/3600,seconds%3600/60);
+    if days > 0 {
+        format!("{}d {}h {}m",days,hours,minutes)
+    }
+    else if hours > 0 {
+        format!("{}h {}m",hours,minutes)
+    }
+    else {
+        format!("{}m {}s",minutes,seconds%60)
+    }
+}
+
+/// Replaces the value of variables which look like they contain credentials.
+fn mask_env(env: &str) -> String {
+    match env.find('=') {
+        Some(x) if is_secret(&env[..x]) => format!("{}=********",&env[..x]),
+        _ => env.to_owned(),
+    }
+}
+
+/// Published ports as `host -> container`, taken from the network settings of a
+/// running container or else from the configured bindings.
+fn ports(inspection: &Value) -> Vec<String> {
+    let mut bindings = inspection["NetworkSettings"]["Ports"].as_object();
+    if bindings.map(|x| x.is_empty()).unwrap_or(true) {
+        bindings = inspection["HostConfig"]["PortBindings"].as_object();
+    }
+    let mut result = bindings.iter().flat_map(|x| x.iter()).map(|(container,hosts)| {
+        let hosts = hosts.as_array().map(|x| x.iter().map(|y| {
+            let ip = string(&y["HostIp"]);
+            let port = string(&y["HostPort"]);
+            if ip.is_empty() { port } else { format!("{}:{}",ip,port) }
+        }).collect::<Vec<String>>()).unwrap_or_default();
+        if hosts.is_empty() {
+            format!("{} (not published)",container)
+        }
+        else {
+            format!("{} -> {}",hosts.join(", "),container)
+        }
+    }).collect::<Vec<String>>();
+    result.sort();
+    result
+}
+
+impl ContainerDetails {
+    /// Inspects the container `id` and its image.
+    pub async fn load(id: &str) -> Result<ContainerDetails,String> {
+        let inspection = session::get_json(&format!("/containers/{}/json",id)).await?;
+        let image_id = string(&inspection["Image"]);
+        let digest = match session::get_json(&format!("/images/{}/json",image_id)).await {
+            Ok(image) => strings(&image["RepoDigests"]).into_iter().next().unwrap_or(image_id),
+            Err(_) => image_id,
+        };
+        let config = &inspection["Config"];
+        let state = &inspection["State"];
+
+        let mut general = vec![
+            format!("Name:     {}",string(&inspection["Name"]).trim_start_matches('/')),
+            format!("Image:    {}",string(&config["Image"])),
+            format!("Digest:   {}",digest),
+        ];
+        if let Some(created) = parse_time(&inspection["Created"]) {
+            general.push(format!("Created:  {}",created.format("%Y-%m-%d %H:%M:%S UTC")));
+        }
+        let status = string(&state["Status"]);
+        match parse_time(&state["StartedAt"]) {
+            Some(started) if state["Running"].as_bool().unwrap_or(false) => general.push(format!("Status:   {}, up {}",status,format_duration((Utc::now()-started).num_seconds()))),
+            _ => general.push(format!("Status:   {} (exit code {})",status,state["ExitCode"])),
+        }
+        let mut command = strings(&config["Entrypoint"]);
+        command.extend(strings(&config["Cmd"]));
+        general.push(format!("Command:  {}",command.join(" ")));
+        let restart = &inspection["HostConfig"]["RestartPolicy"];
+        let policy = match (string(&restart["Name"]).as_str(),restart["MaximumRetryCount"].as_u64().unwrap_or(0)) {
+            ("",_) | ("no",_) => String::from("no"),
+            ("on-failure",x) if x > 0 => format!("on-failure (max {} retries)",x),
+            (x,_) => x.to_owned(),
+        };
+        general.push(format!("Restart:  {}",policy));
+        if inspection["HostConfig"]["AutoRemove"].as_bool().unwrap_or(false) {
+            general.push(String::from("Removed once stopped"));
+        }
+
+        let mounts = inspection["Mounts"].as_array().map(|x| x.iter().map(|y| {
+            let source = match string(&y["Source"]) {
+                x if x.is_empty() => string(&y["Name"]),
+                x => x,
+            };
+            let read_only = if y["RW"].as_bool().unwrap_or(true) { "" } else { " (read only)" };
+            format!("{} -> {}{}",source,string(&y["Destination"]),read_only)
+        }).collect()).unwrap_or_default();
+
+        let mut labels = config["Labels"].as_object().map(|x| x.iter().map(|(name,value)| format!("{}={}",name,value.as_str().unwrap_or(""))).collect::<Vec<String>>()).unwrap_or_default();
+        labels.sort();
+
+        Ok(ContainerDetails {
+            sections: vec![
+                (String::from("General"),general),
+                (String::from("Mounts"),mounts),
+                (String::from("Ports"),ports(&inspection)),
+                (String::from("Environment"),strings(&config["Env"]).iter().map(|x| mask_env(x)).collect()),
+                (String::from("Labels"),labels),
+            ],
+        })
+    }
+
+    /// The lines of the detail pane.
+    pub fn text(&self, style: Style) -> Vec<Spans<'static>> {
+        let mut lines = Vec::new();
+        for (title,entries) in self.sections.iter() {
+            if !lines.is_empty() {
+                lines.push(Spans::from(""));
+            }
+            lines.push(Spans::from(Span::styled(title.clone(),style.add_modifier(Modifier::BOLD))));
+            if entries.is_empty() {
+                lines.push(Spans::from(Span::styled("  none",style)));
+            }
+            for entry in entries.iter() {
+                lines.push(Spans::from(Span::styled(format!("  {}",entry),style)));
+            }
+        }
+        lines
+    }
+}
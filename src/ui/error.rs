@@ -1,4 +1,3 @@
-use std::io;
 use tui::{
     backend::Backend,
     layout::{Constraint, Direction, Layout,Alignment},
@@ -7,7 +6,7 @@ use tui::{
     widgets::{Block, Borders, Paragraph,Clear,Wrap},
     Terminal,
 };
-use termion::input::TermRead;
+use crate::ui::events::{Event,Events};
 use crate::ui::AppState;
 
 
@@ -44,18 +43,16 @@ impl AppErrorContext {
         }).unwrap();
     }
 
-    pub fn event_loop<B: Backend>(&mut self, term: &mut Terminal<B>) -> AppState {
-        let stdin = io::stdin();
+    pub async fn event_loop<B: Backend>(&mut self, term: &mut Terminal<B>, events: &mut Events) -> AppState {
         self.render(term);
-        for evt in stdin.keys() {
-            match evt {
-                Ok(_) => {
+        loop {
+            match events.next().await {
+                Event::Key(_) | Event::InputClosed => {
                     return AppState::Exiting;
                 },
                 _ => {}
             }
             self.render(term);
         }
-        AppState::Exiting
     }
 }
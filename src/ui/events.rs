@@ -0,0 +1,149 @@
+//! The events all screens wait for: key presses, clicks, a periodic tick and
+//! changes of containers and images reported by docker, merged into one channel
+//! so that a screen can react to whichever comes first.
+use std::collections::{HashMap,VecDeque};
+use std::time::Duration;
+use futures::StreamExt;
+use shiplift::{Docker,EventsOptions,rep};
+use shiplift::builder::{EventFilter,EventFilterType};
+use termion::event::{Key,MouseEvent};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use crate::input::{Input,InputParser,StdinReader};
+use crate::stats::Sample;
+use crate::ui::details::ContainerDetails;
+
+/// Interval of `Event::Tick`, fast enough to animate the task spinner.
+pub const TICK_RATE: Duration = Duration::from_millis(250);
+/// Delay before reconnecting to the docker event stream once it failed.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// Container events which change the container list.
+const CONTAINER_ACTIONS: [&str; 8] = ["create","start","die","destroy","rename","pause","unpause","update"];
+/// Image events which change the image list.
+const IMAGE_ACTIONS: [&str; 6] = ["pull","tag","untag","delete","import","load"];
+
+pub enum Event {
+    Key(Key),
+    /// A click or scroll, only reported while a screen enabled the mouse.
+    Mouse(MouseEvent),
+    /// Text pasted into the terminal.
+    Paste(String),
+    Tick,
+    /// A container was created, started, stopped, removed or otherwise changed,
+    /// or an image was pulled, tagged or removed.
+    Docker(rep::Event),
+    /// Resource usage sampled in the background, by container id.
+    Stats(HashMap<String,Sample>),
+    /// The inspection of a container loaded in the background, by its id.
+    Details(String,Result<ContainerDetails,String>),
+    /// A background task was started, made progress or finished.
+    Task,
+    /// Whether an image is available locally (`true`) or can be pulled, checked
+    /// in the background.
+    ImageChecked(String,Result<bool,String>),
+    /// Stdin was closed, nothing can be read anymore.
+    InputClosed,
+}
+
+pub struct Events {
+    sender: mpsc::UnboundedSender<Event>,
+    receiver: mpsc::UnboundedReceiver<Event>,
+    /// Events handed back with `requeue`, returned before new ones.
+    pending: VecDeque<Event>,
+    input: Option<JoinHandle<()>>,
+}
+
+fn spawn_input(sender: mpsc::UnboundedSender<Event>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut reader = StdinReader::new();
+        let mut parser = InputParser::default();
+        while let Some(bytes) = reader.recv().await {
+            for input in parser.parse(&bytes) {
+                let event = match input {
+                    Input::Key(key) => Event::Key(key),
+                    Input::Mouse(event) => Event::Mouse(event),
+                    Input::Paste(text) => Event::Paste(text),
+                };
+                if sender.send(event).is_err() {
+                    return;
+                }
+            }
+        }
+        let _ = sender.send(Event::InputClosed);
+    })
+}
+
+fn spawn_ticks(sender: mpsc::UnboundedSender<Event>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TICK_RATE);
+        loop {
+            interval.tick().await;
+            if sender.send(Event::Tick).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+fn spawn_docker_events(docker: Docker, sender: mpsc::UnboundedSender<Event>) {
+    tokio::spawn(async move {
+        let opts = EventsOptions::builder().filter(vec![EventFilter::Type(EventFilterType::Container),EventFilter::Type(EventFilterType::Image)]).build();
+        loop {
+            let mut stream = docker.events(&opts);
+            while let Some(Ok(event)) = stream.next().await {
+                let actions = if event.typ == "image" { &IMAGE_ACTIONS[..] } else { &CONTAINER_ACTIONS[..] };
+                if actions.contains(&event.action.as_str()) && sender.send(Event::Docker(event)).is_err() {
+                    return;
+                }
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    });
+}
+
+impl Events {
+    pub fn new(docker: &Docker) -> Events {
+        let (sender,receiver) = mpsc::unbounded_channel();
+        spawn_ticks(sender.clone());
+        spawn_docker_events(docker.clone(),sender.clone());
+        Events {
+            input: Some(spawn_input(sender.clone())),
+            sender,
+            receiver,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// A sender for results of work done in the background.
+    pub fn sender(&self) -> mpsc::UnboundedSender<Event> {
+        self.sender.clone()
+    }
+
+    /// Waits for the next event.
+    pub async fn next(&mut self) -> Event {
+        if let Some(event) = self.pending.pop_front() {
+            return event;
+        }
+        // The channel cannot close while `self` holds a sender.
+        self.receiver.recv().await.unwrap_or(Event::InputClosed)
+    }
+
+    /// Hands events back which were received but not handled, e.g. by a popup
+    /// on top of the screen they belong to.
+    pub fn requeue<I: IntoIterator<Item=Event>>(&mut self, events: I) {
+        self.pending.extend(events);
+    }
+
+    /// Stops reading stdin, e.g. while the terminal is attached to a container.
+    pub fn suspend_input(&mut self) {
+        if let Some(input) = self.input.take() {
+            input.abort();
+        }
+    }
+
+    pub fn resume_input(&mut self) {
+        if self.input.is_none() {
+            self.input = Some(spawn_input(self.sender.clone()));
+        }
+    }
+}
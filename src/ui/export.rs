@@ -0,0 +1,34 @@
+use std::path::Path;
+use shiplift::Docker;
+use tui::style::{Color, Style};
+use crate::ui::events::Events;
+use crate::ui::popup::AppPopupContext;
+use crate::workspace;
+
+/// Copies the working directory `target` of the container `id` back to `host`.
+///
+/// Shows the resulting changes and asks for confirmation before anything on the
+/// host is touched. `render` draws the calling screen with the given popup on top.
+/// Returns a message describing the outcome.
+pub async fn export_workspace<F: FnMut(&mut AppPopupContext)>(docker: &Docker, id: &str, target: &str, host: &Path, events: &mut Events, mut render: F) -> Result<String,String> {
+    let mut progress = AppPopupContext::new(format!("Downloading {} from the container...",target));
+    render(&mut progress);
+    let archive = workspace::download(docker,id,target).await?;
+    let changes = workspace::diff(&archive,host)?;
+    if changes.is_empty() {
+        return Ok(format!("\"{}\" is already up to date.",host.display()));
+    }
+
+    let message = format!("Export to \"{}\": {}\nApply these changes (y) - yes, (n) - no?",host.display(),changes.summary());
+    if AppPopupContext::decision(message).event_render_loop(events,|popup| render(popup)).await != "yes" {
+        return Ok(String::from("Export aborted."));
+    }
+    if workspace::has_uncommitted_changes(host) {
+        let message = format!("\"{}\" contains uncommitted changes which might be overwritten. Continue anyway (y) - yes, (n) - no?",host.display());
+        if AppPopupContext::decision(message).style(Style::default().fg(Color::LightRed)).event_render_loop(events,|popup| render(popup)).await != "yes" {
+            return Ok(String::from("Export aborted."));
+        }
+    }
+    workspace::apply(&archive,host,&changes)?;
+    Ok(format!("Exported to \"{}\": {} added, {} modified, {} deleted.",host.display(),changes.added.len(),changes.modified.len(),changes.deleted.len()))
+}
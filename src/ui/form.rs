@@ -0,0 +1,51 @@
+//! Typed fields of the forms next to the text fields: yes/no toggles and the
+//! borders of fields which show their validation error in the title.
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color,Modifier,Style},
+    text::{Span,Spans},
+    widgets::{Block,Borders,Paragraph},
+    Frame,
+};
+use termion::event::Key;
+
+#[derive(Clone,Copy)]
+pub struct Toggle {
+    value: bool,
+}
+
+impl Toggle {
+    pub fn new(value: bool) -> Toggle {
+        Toggle { value }
+    }
+
+    pub fn value(&self) -> bool {
+        self.value
+    }
+
+    /// Space flips the toggle, y and n set it. Returns false for other keys.
+    pub fn handle_key(&mut self, key: Key) -> bool {
+        match key {
+            Key::Char(' ') => self.value = !self.value,
+            Key::Char('y') => self.value = true,
+            Key::Char('n') => self.value = false,
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn render_on<B: Backend>(&self, f: &mut Frame<B>, area: Rect, block: Block, style: Style) {
+        let text = if self.value { ">> [x] yes" } else { ">> [ ] no" };
+        f.render_widget(Paragraph::new(Span::raw(text)).block(block).style(style),area);
+    }
+}
+
+/// The border of a form field, a validation error is appended to the title.
+pub fn field_block(title: &str, error: Option<&str>) -> Block<'static> {
+    let mut spans = vec![Span::raw(title.to_owned())];
+    if let Some(error) = error {
+        spans.push(Span::styled(format!(" {} ",error),Style::default().fg(Color::LightRed).add_modifier(Modifier::BOLD)));
+    }
+    Block::default().borders(Borders::ALL).title(Spans::from(spans))
+}
@@ -1,4 +1,3 @@
-use std::io;
 use tui::{
     backend::Backend,
     layout::{Constraint, Direction, Layout,Alignment},
@@ -8,7 +7,7 @@ use tui::{
     Terminal,
 };
 use termion::event::Key;
-use termion::input::TermRead;
+use crate::ui::events::{Event,Events};
 use crate::ui::AppState;
 use crate::VERSION;
 
@@ -33,7 +32,7 @@ impl AppHelpContext {
             let header_paragraph = Paragraph::new(Spans::from(vec![Span::styled(VERSION,Style::default().fg(Color::LightRed)),Span::raw(" | Help window")]))
                 .style(Style::default().bg(Color::Black).fg(Color::LightYellow))
                 .alignment(Alignment::Center);
-            let paragraph = Paragraph::new(Text::from(format!("Ctrl+h - Show help\nCtrl-a - Command mode\nCtrl-s - Search mode\nCtrl-c - quit programm\nPage up - Scroll results up\nPage down - Scroll results down\n\nCommand mode commands:\n-- help - Show commands"))).style(style_hi)
+            let paragraph = Paragraph::new(Text::from(format!("Ctrl+h - Show help\nCtrl-a - Command mode\nCtrl-s - Search mode\nCtrl-t - Background tasks\nCtrl-c - quit programm\nPage up - Scroll results up\nPage down - Scroll results down\n\nText fields:\nLeft/Right, Home/End - Move the cursor\nBackspace/Delete - Delete before/at the cursor\nCtrl-w - Delete the word before the cursor\nCtrl-u - Delete everything before the cursor\nPasted text is inserted at the cursor\n\nNew container form:\nEnter/Tab/Down - Next field, invalid fields show their error in the title\nShift+Tab/Up - Previous field, a click focuses a field\nCtrl-n/Ctrl-p - Choose a suggested local image in the image field, Tab accepts it\nSpace - Toggle yes/no fields, switch the profile and the working directory mode, y/n set a toggle\nThe last field leads to a review: Enter/c - Confirm, b - Back, Esc - Cancel\np - Switch the preview between docker run and compose, w - Save it to a file, y - Copy it to the clipboard\nEsc - Discard the new container\n\nSearch:\nFree text is fuzzy matched, filters are status:<state> image:<image> name:<name> path:<workspace> label:<name>[=<value>]\nAll terms have to match, a leading - negates a term\n\nSelection mode:\nb - Export the working directory of the container to the host\nx - Open an additional shell in the container\nX - Run a command in the container as the given user and working directory\nl - Show the logs of the container\nu - Show the resource usage of the container\nr - Show the docker run command or compose service recreating the container\nc - Choose the columns of the table (name, status, image, created, uptime, workspace, ports, cpu, memory)\no - Sort by the next column\nO - Reverse the sort order\na - Show all containers instead of only the ones managed by dde\nA - Manage the selected container with dde\n\nImages (i in search mode):\n/ - Filter by name, w/s - select, the right pane shows the layers of the image\np - Pull, t - Tag, d - Remove, P - Prune dangling images, r - Reload\n\nLog viewer:\nf - Follow new output\nt - Show timestamps\n/ - Search, n/N - next/previous match\nS - Save the log to a file\n\nCommand mode:\nTab - Complete commands, container and image names\nUp/Down - Browse the command history\nPage up/down - Scroll the output\n-- help [command] - Show the commands or the usage of one\n-- start/stop/restart/rm <container>... - Manage containers\n-- rename <container> <new name>\n-- attach <container>, exec [--user <user>] [--workdir <dir>] <container> [command...]\n-- logs <container>, new\n-- pull <image>[:<tag>], commit <container> <image>[:<tag>]\n-- export <container> [host directory] - Copy the working directory back to the host\n-- images - Manage the local images\n-- tasks - Show the background tasks\n\nPulls, container creation, stopping and removal run in the background, the status bar shows their progress."))).style(style_hi)
                                     .block(Block::default().borders(Borders::ALL).title("Help"))
                                     .alignment(Alignment::Left);
             f.render_widget(header_paragraph,chunks[0]);
@@ -41,18 +40,16 @@ impl AppHelpContext {
         }).unwrap();
     }
 
-    pub fn event_loop<B: Backend>(&mut self, term: &mut Terminal<B>) -> AppState {
-        let stdin = io::stdin();
+    pub async fn event_loop<B: Backend>(&mut self, term: &mut Terminal<B>, events: &mut Events) -> AppState {
         self.render(term);
-        for evt in stdin.keys() {
-            match evt {
-                Ok(Key::Ctrl('c')) => {return AppState::Exiting;},
-                Ok(Key::Ctrl('a')) => {return AppState::Command;},
-                Ok(Key::Ctrl('s')) => {return AppState::Search;},
+        loop {
+            match events.next().await {
+                Event::Key(Key::Ctrl('c')) | Event::InputClosed => {return AppState::Exiting;},
+                Event::Key(Key::Ctrl('a')) => {return AppState::Command;},
+                Event::Key(Key::Ctrl('s')) => {return AppState::Search;},
                 _ => {}
             }
             self.render(term);
         }
-        return AppState::Exiting;
     }
 }
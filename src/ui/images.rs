@@ -0,0 +1,367 @@
+//! The local images with the containers using them and the layers they are
+//! built from, images can be pulled, tagged, removed and pruned from here.
+use shiplift::{Docker,rep};
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout,Alignment},
+    style::{Color, Modifier, Style},
+    text::{Span,Text,Spans},
+    widgets::{Block, Borders, Paragraph,Row,Cell,Table,TableState,Wrap},
+    Terminal,
+};
+use termion::event::Key;
+use std::collections::HashMap;
+use crate::ui::events::{Event,Events};
+use crate::ui::tasks::Tasks;
+use crate::ui::AppState;
+use crate::ui::popup::AppPopupContext;
+use crate::ui::search::highlighted;
+use crate::ui::text_input::TextInput;
+use crate::images::{self,ImageEntry};
+use crate::workspace::format_size;
+use crate::fuzzy;
+use crate::VERSION;
+
+struct ImageList {
+    images: Vec<ImageEntry>,
+    filtered_list: Vec<usize>,
+    selected_state: TableState,
+    smart_case: bool,
+    /// Positions of the characters of the names matched by the filter.
+    highlights: HashMap<usize,Vec<usize>>,
+    height: usize,
+}
+
+impl ImageList {
+    async fn update(&mut self, docker: &Docker) -> Result<(),String> {
+        self.images = images::list(docker).await?;
+        self.images.sort_by(|a,b| b.created.cmp(&a.created).then_with(|| a.name.cmp(&b.name)));
+        Ok(())
+    }
+
+    /// Filters the images by a fuzzy match of `filter` on their name, best
+    /// matches first or else the newest image first.
+    fn update_filtered_list(&mut self, filter: &str) {
+        let case_sensitive = fuzzy::is_case_sensitive(filter,self.smart_case);
+        self.highlights.clear();
+        let mut scored = Vec::new();
+        for (idx,image) in self.images.iter().enumerate() {
+            if let Some(matched) = fuzzy::fuzzy_match(filter,&image.name,case_sensitive) {
+                scored.push((idx,matched.score));
+                self.highlights.insert(idx,matched.positions);
+            }
+        }
+        if !filter.is_empty() {
+            scored.sort_by_key(|x| std::cmp::Reverse(x.1));
+        }
+        self.filtered_list = scored.into_iter().map(|(idx,_)| idx).collect();
+        self.selected_state.select(if self.filtered_list.is_empty() { None } else { Some(0) });
+    }
+
+    fn selected_image(&self) -> Option<&ImageEntry> {
+        let selected = self.selected_state.selected()?;
+        self.images.get(*self.filtered_list.get(selected)?)
+    }
+
+    fn select_image(&mut self, name: &str) {
+        if let Some(idx) = self.filtered_list.iter().position(|x| self.images[*x].name == name) {
+            self.selected_state.select(Some(idx));
+        }
+    }
+
+    /// Moves the selection by `lines` entries, wrapping around for single steps.
+    fn select_by(&mut self, lines: isize) {
+        if self.filtered_list.is_empty() {
+            return;
+        }
+        let last = self.filtered_list.len() as isize-1;
+        let selected = self.selected_state.selected().unwrap_or(0) as isize+lines;
+        let selected = match lines {
+            -1 if selected < 0 => last,
+            1 if selected > last => 0,
+            _ => std::cmp::min(std::cmp::max(selected,0),last),
+        };
+        self.selected_state.select(Some(selected as usize));
+    }
+
+    fn rows(&self) -> Vec<Row<'static>> {
+        let width = self.filtered_list.len().to_string().len();
+        let highlight = Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD);
+        self.filtered_list.iter().enumerate().map(|(idx,x)| {
+            let image = &self.images[*x];
+            let name = match self.highlights.get(x) {
+                Some(positions) => Spans::from(highlighted(&image.name,positions,highlight)),
+                None => Spans::from(image.name.clone()),
+            };
+            let name = if image.is_tagged() { Cell::from(name) } else { Cell::from(name).style(Style::default().fg(Color::Gray)) };
+            Row::new(vec![
+                Cell::from(format!("{:0width$}.",idx,width = width)),
+                name,
+                Cell::from(image.short_id().to_owned()),
+                Cell::from(format_size(image.size)),
+                Cell::from(image.created.format("%Y-%m-%d %H:%M").to_string()),
+                Cell::from(image.containers.to_string()),
+            ])
+        }).collect()
+    }
+}
+
+pub struct AppImagesContext {
+    image_list: ImageList,
+    filter: TextInput,
+    entering_filter: bool,
+    /// The layers of the selected image, by image id.
+    history: Option<(String,Result<Vec<rep::History>,String>)>,
+    error: Option<String>,
+    tasks: Tasks,
+}
+
+impl AppImagesContext {
+    pub fn new(smart_case: bool, tasks: Tasks) -> AppImagesContext {
+        AppImagesContext {
+            image_list: ImageList {
+                images: Vec::new(),
+                filtered_list: Vec::new(),
+                selected_state: TableState::default(),
+                smart_case,
+                highlights: HashMap::new(),
+                height: 0,
+            },
+            filter: TextInput::default(),
+            entering_filter: false,
+            history: None,
+            error: None,
+            tasks,
+        }
+    }
+
+    /// Lists the images again, keeping the selection and the filter.
+    async fn refresh(&mut self, docker: &Docker) {
+        let selected = self.image_list.selected_image().map(|x| x.name.clone());
+        self.error = self.image_list.update(docker).await.err();
+        self.image_list.update_filtered_list(self.filter.value());
+        if let Some(name) = selected {
+            self.image_list.select_image(&name);
+        }
+    }
+
+    /// Loads the layers of the selected image unless they are already shown.
+    async fn update_history(&mut self, docker: &Docker) {
+        let selected = self.image_list.selected_image().map(|x| x.id.clone());
+        if selected.as_ref() == self.history.as_ref().map(|x| &x.0) {
+            return;
+        }
+        self.history = match selected {
+            Some(id) => {
+                let history = images::history(docker,&id).await;
+                Some((id,history))
+            },
+            None => None,
+        };
+    }
+
+    fn history_text(&self, style: Style) -> Text<'static> {
+        let layers = match &self.history {
+            Some((_,Ok(layers))) => layers,
+            Some((_,Err(error))) => return Text::styled(error.clone(),Style::default().fg(Color::LightRed)),
+            None => return Text::styled("No image selected",style),
+        };
+        let mut lines = Vec::new();
+        for layer in layers.iter() {
+            lines.push(Spans::from(Span::styled(layer.created.format("%Y-%m-%d").to_string(),Style::default().fg(Color::Gray))));
+            // Instructions of a Dockerfile are recorded behind a `/bin/sh -c #(nop)` prefix.
+            let created_by = layer.created_by.trim_start_matches("/bin/sh -c ").trim_start_matches("#(nop) ").trim();
+            lines.push(Spans::from(Span::styled(format!("  {}",created_by),style)));
+        }
+        Text::from(lines)
+    }
+
+    pub fn render<B: Backend>(&mut self,terminal: &mut Terminal<B>, popup: Option<&mut AppPopupContext>) {
+        terminal.draw(|f| {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1),Constraint::Length(3),Constraint::Min(2),Constraint::Length(3),Constraint::Length(1)].as_ref())
+                .split(f.size());
+
+            let mut style_non = Style::default().fg(Color::Blue);
+            let mut style_hi = Style::default().fg(Color::LightGreen);
+            let mut style_help = Style::default().fg(Color::Cyan);
+            let mut header_style = Style::default().fg(Color::LightRed);
+            let mut header_other_style = Style::default().bg(Color::Black).fg(Color::LightYellow);
+            let mut list_highlight_style = Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD);
+            let mut style_details = Style::default().fg(Color::White);
+
+            if popup.is_some() {
+                let style_dim = Style::default().fg(Color::Gray).add_modifier(Modifier::DIM);
+                style_non = style_dim;
+                style_hi = style_dim;
+                style_help = style_dim;
+                header_style = style_dim;
+                header_other_style = style_dim;
+                list_highlight_style = style_dim;
+                style_details = style_dim;
+            }
+
+            let header_paragraph = Paragraph::new(Spans::from(vec![Span::styled(VERSION,header_style),Span::raw(" | Images")]))
+                .style(header_other_style)
+                .alignment(Alignment::Center);
+            f.render_widget(header_paragraph,chunks[0]);
+
+            let filter_block = Block::default().borders(Borders::ALL).title("Filter");
+            match &self.error {
+                Some(error) => f.render_widget(Paragraph::new(Span::styled(error.clone(),Style::default().fg(Color::LightRed))).block(filter_block),chunks[1]),
+                None => self.filter.render_on(f,chunks[1],"",filter_block,if self.entering_filter { style_hi } else { style_non },self.entering_filter && popup.is_none()),
+            }
+
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(60),Constraint::Percentage(40)].as_ref())
+                .split(chunks[2]);
+            // The table loses one line to its header.
+            self.image_list.height = columns[0].height.saturating_sub(3) as usize;
+            let table = Table::new(self.image_list.rows())
+                .header(Row::new(vec!["","Image","Id","Size","Created","Containers"]).style(style_non.add_modifier(Modifier::BOLD)))
+                .widths(&[Constraint::Length(4),Constraint::Percentage(40),Constraint::Length(12),Constraint::Length(9),Constraint::Length(16),Constraint::Length(10)])
+                .block(Block::default().borders(Borders::ALL).title(format!("Images ({})",self.image_list.filtered_list.len())).style(if self.entering_filter { style_non } else { style_hi }))
+                .style(style_non)
+                .highlight_style(list_highlight_style).highlight_symbol(">> ");
+            f.render_stateful_widget(table,columns[0],&mut self.image_list.selected_state);
+
+            let history = Paragraph::new(self.history_text(style_details))
+                .block(Block::default().borders(Borders::ALL).title("History").style(style_non))
+                .wrap(Wrap { trim: false });
+            f.render_widget(history,columns[1]);
+
+            let help = if self.entering_filter {
+                "Enter part of the image name - accept with <enter>; clear with <esc>; quit with <ctrl-c>"
+            }
+            else {
+                "/ - filter; w/s - select; p - pull; t - tag; d - remove; P - prune dangling images; r - reload; back - q or <esc>; <ctrl-t> - background tasks"
+            };
+            let help = Paragraph::new(Text::from(help)).style(style_help)
+                                    .block(Block::default().borders(Borders::ALL).title("Help"))
+                                    .alignment(Alignment::Left);
+            f.render_widget(help,chunks[3]);
+            f.render_widget(self.tasks.status_bar(),chunks[4]);
+            if let Some(popup) = popup {
+                popup.render_on(f);
+            }
+        }).unwrap();
+    }
+
+    fn pull(&self, docker: &Docker, image: String) {
+        let docker = docker.clone();
+        self.tasks.spawn(format!("Pulling {}",image),move |progress| async move {
+            images::pull(&docker,&image,|x| progress.set(x.lines().join("\n"),x.fraction())).await
+        });
+    }
+
+    async fn handle_key<B: Backend>(&mut self, term: &mut Terminal<B>, docker: &Docker, events: &mut Events, key: char) -> Option<AppState> {
+        match key {
+            'q' => return Some(AppState::Search),
+            '/' => self.entering_filter = true,
+            'w' => self.image_list.select_by(-1),
+            's' => self.image_list.select_by(1),
+            'r' => self.refresh(docker).await,
+            'p' => {
+                let default = self.image_list.selected_image().filter(|x| x.is_tagged()).map(|x| x.name.clone()).unwrap_or_default();
+                let image = AppPopupContext::input(String::from("Image to pull (e.g. ubuntu:22.04):"),default).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+                if !image.trim().is_empty() {
+                    self.pull(docker,image.trim().to_owned());
+                }
+            },
+            't' => {
+                if let Some(id) = self.image_list.selected_image().map(|x| x.id.clone()) {
+                    let target = AppPopupContext::input(String::from("New tag (repository:tag):"),String::new()).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+                    if !target.trim().is_empty() {
+                        let docker = docker.clone();
+                        let target = target.trim().to_owned();
+                        self.tasks.spawn(format!("Tagging {}",target),move |_| async move {
+                            images::tag(&docker,&id,&target).await
+                        });
+                    }
+                }
+            },
+            'd' => {
+                if let Some(image) = self.image_list.selected_image() {
+                    // Untagged images can only be removed by their id.
+                    let name = if image.is_tagged() { image.name.clone() } else { image.short_id().to_owned() };
+                    let mut message = format!("Do you really want to remove {} (y) - yes, (n) - no?",name);
+                    if image.containers > 0 {
+                        message = format!("{} is used by {} containers. {}",name,image.containers,message);
+                    }
+                    let result = AppPopupContext::decision(message).style(Style::default().fg(Color::LightRed)).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+                    if result == "yes" {
+                        let docker = docker.clone();
+                        self.tasks.spawn(format!("Removing {}",name),move |_| async move {
+                            images::remove(&docker,&name).await
+                        });
+                    }
+                }
+            },
+            'P' => {
+                let result = AppPopupContext::decision(String::from("Remove all dangling images (y) - yes, (n) - no?")).style(Style::default().fg(Color::LightRed)).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+                if result == "yes" {
+                    self.tasks.spawn(String::from("Pruning images"),|_| images::prune());
+                }
+            },
+            _ => {},
+        }
+        None
+    }
+
+    pub async fn event_loop<B: Backend>(&mut self, term: &mut Terminal<B>, docker: &Docker, events: &mut Events) -> AppState {
+        self.refresh(docker).await;
+        self.update_history(docker).await;
+        self.render(term,None);
+        loop {
+            match events.next().await {
+                Event::Key(Key::Char(c)) if self.entering_filter => {
+                    if c == '\n' {
+                        self.entering_filter = false;
+                    }
+                    else {
+                        self.filter.handle_key(Key::Char(c));
+                        self.image_list.update_filtered_list(self.filter.value());
+                    }
+                },
+                Event::Paste(text) if self.entering_filter => {
+                    self.filter.insert_str(&text);
+                    self.image_list.update_filtered_list(self.filter.value());
+                },
+                Event::Key(key) if self.entering_filter && key != Key::Esc && self.filter.handle_key(key) => {
+                    self.image_list.update_filtered_list(self.filter.value());
+                },
+                Event::Key(Key::Esc) if self.entering_filter => {
+                    self.entering_filter = false;
+                    self.filter.clear();
+                    self.image_list.update_filtered_list(self.filter.value());
+                },
+                Event::Key(Key::Char(c)) => {
+                    if let Some(state) = self.handle_key(term,docker,events,c).await {
+                        return state;
+                    }
+                },
+                Event::Key(Key::Up) => self.image_list.select_by(-1),
+                Event::Key(Key::Down) => self.image_list.select_by(1),
+                Event::Key(Key::PageUp) => {
+                    let height = self.image_list.height as isize;
+                    self.image_list.select_by(-height);
+                },
+                Event::Key(Key::PageDown) => {
+                    let height = self.image_list.height as isize;
+                    self.image_list.select_by(height);
+                },
+                Event::Key(Key::Esc) => return AppState::Search,
+                Event::Key(Key::Ctrl('c')) | Event::InputClosed => return AppState::Exiting,
+                Event::Key(Key::Ctrl('h')) => return AppState::Help,
+                Event::Key(Key::Ctrl('t')) => return AppState::Tasks,
+                // Containers change the usage count, images the list itself.
+                Event::Docker(_) => self.refresh(docker).await,
+                _ => {},
+            }
+            self.update_history(docker).await;
+            self.render(term,None);
+        }
+    }
+}
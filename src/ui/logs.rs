@@ -0,0 +1,404 @@
+use shiplift::Docker;
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout,Alignment},
+    style::{Color, Modifier, Style},
+    text::{Span,Text,Spans},
+    widgets::{Block, Borders, Paragraph},
+    Terminal,
+};
+use termion::event::Key;
+use crate::ui::AppState;
+use crate::ui::popup::AppPopupContext;
+use crate::ui::run_command;
+use crate::ui::events::{Event,Events};
+use crate::ui::text_input::TextInput;
+use crate::session::{self,Output};
+use crate::VERSION;
+
+/// Maximum number of lines kept in memory, older lines are dropped.
+const MAX_LINES: usize = 10000;
+
+struct LogLine {
+    output: Output,
+    timestamp: String,
+    text: String,
+}
+
+impl LogLine {
+    fn parse(output: Output, line: &[u8]) -> LogLine {
+        let line = String::from_utf8_lossy(line);
+        let (timestamp,text) = match line.find(' ') {
+            Some(x) if line[..x].ends_with('Z') => (line[..x].to_owned(),&line[x+1..]),
+            _ => (String::new(),&line[..]),
+        };
+        LogLine {
+            output,
+            timestamp,
+            text: strip_control(text),
+        }
+    }
+
+    /// The timestamp without the fractional seconds, e.g. `2021-08-01 12:00:00`.
+    fn short_timestamp(&self) -> String {
+        self.timestamp.split('.').next().unwrap_or("").replace('T'," ").trim_end_matches('Z').to_owned()
+    }
+}
+
+/// Removes escape sequences and control characters a TTY writes along with the
+/// text, e.g. colours or carriage returns.
+fn strip_control(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                Some('[') => {
+                    for x in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&x) {
+                            break;
+                        }
+                    }
+                },
+                Some(']') => {
+                    while let Some(x) = chars.next() {
+                        if x == '\x07' || (x == '\x1b' && chars.peek() == Some(&'\\')) {
+                            chars.next();
+                            break;
+                        }
+                    }
+                },
+                _ => {},
+            },
+            '\t' => result.push_str("    "),
+            c if c.is_control() => {},
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+pub struct AppLogsContext {
+    id: String,
+    name: String,
+    lines: Vec<LogLine>,
+    partial_stdout: Vec<u8>,
+    partial_stderr: Vec<u8>,
+    top: usize,
+    height: usize,
+    follow: bool,
+    timestamps: bool,
+    ended: bool,
+    searching: bool,
+    search: TextInput,
+    search_origin: usize,
+    current_match: Option<usize>,
+}
+
+impl AppLogsContext {
+    pub fn new(id: String) -> AppLogsContext {
+        AppLogsContext {
+            name: id.clone(),
+            id,
+            lines: Vec::new(),
+            partial_stdout: Vec::new(),
+            partial_stderr: Vec::new(),
+            top: 0,
+            height: 0,
+            follow: true,
+            timestamps: false,
+            ended: false,
+            searching: false,
+            search: TextInput::default(),
+            search_origin: 0,
+            current_match: None,
+        }
+    }
+
+    fn push_output(&mut self, output: Output, data: Vec<u8>) {
+        let partial = match output {
+            Output::Stdout => &mut self.partial_stdout,
+            Output::Stderr => &mut self.partial_stderr,
+        };
+        partial.extend(data);
+        let mut lines = Vec::new();
+        while let Some(end) = partial.iter().position(|x| *x == b'\n') {
+            let line = partial.drain(..end+1).collect::<Vec<u8>>();
+            lines.push(LogLine::parse(output,&line[..end]));
+        }
+        self.lines.extend(lines);
+        if self.lines.len() > MAX_LINES {
+            let dropped = self.lines.len()-MAX_LINES;
+            self.lines.drain(..dropped);
+            self.top = self.top.saturating_sub(dropped);
+            self.search_origin = self.search_origin.saturating_sub(dropped);
+            self.current_match = self.current_match.and_then(|x| x.checked_sub(dropped));
+        }
+    }
+
+    fn max_top(&self) -> usize {
+        self.lines.len().saturating_sub(self.height)
+    }
+
+    fn scroll_up(&mut self, lines: usize) {
+        self.follow = false;
+        self.top = self.top.saturating_sub(lines);
+    }
+
+    fn scroll_down(&mut self, lines: usize) {
+        self.top = std::cmp::min(self.top+lines,self.max_top());
+    }
+
+    fn jump_to(&mut self, idx: usize) {
+        self.follow = false;
+        self.current_match = Some(idx);
+        self.top = std::cmp::min(idx.saturating_sub(self.height/2),self.max_top());
+    }
+
+    /// Finds the next line containing the search term starting at `from`,
+    /// wrapping around at the end of the log.
+    fn find(&self, from: usize, forward: bool) -> Option<usize> {
+        if self.search.is_empty() || self.lines.is_empty() {
+            return None;
+        }
+        let len = self.lines.len();
+        (0..len).map(|x| if forward { (from+x)%len } else { (from+len-x%len)%len })
+            .find(|x| self.lines[*x].text.contains(self.search.value()))
+    }
+
+    fn search_next(&mut self, forward: bool) {
+        let from = match (self.current_match,forward) {
+            (Some(x),true) => x+1,
+            (Some(x),false) => x+self.lines.len()-1,
+            (None,_) => self.top,
+        };
+        if let Some(x) = self.find(from%std::cmp::max(self.lines.len(),1),forward) {
+            self.jump_to(x);
+        }
+    }
+
+    fn update_search(&mut self) {
+        self.current_match = None;
+        match self.find(self.search_origin,true) {
+            Some(x) => self.jump_to(x),
+            None => self.top = std::cmp::min(self.search_origin,self.max_top()),
+        }
+    }
+
+    /// The line as shown on screen.
+    fn format_line(&self, line: &LogLine) -> String {
+        if self.timestamps {
+            format!("{} {}",line.short_timestamp(),line.text)
+        }
+        else {
+            line.text.clone()
+        }
+    }
+
+    fn save(&self, path: &str) -> Result<String,String> {
+        let content = self.lines.iter().map(|x| self.format_line(x)+"\n").collect::<String>();
+        std::fs::write(path,content).map_err(|e| format!("Could not write \"{}\": {}",path,e))?;
+        Ok(format!("Saved {} lines to \"{}\".",self.lines.len(),path))
+    }
+
+    fn highlight<'a>(&self, text: &'a str, style: Style) -> Vec<Span<'a>> {
+        if self.search.is_empty() {
+            return vec![Span::styled(text,style)];
+        }
+        let mut spans = Vec::new();
+        let mut last = 0;
+        for (idx,matched) in text.match_indices(self.search.value()) {
+            spans.push(Span::styled(&text[last..idx],style));
+            spans.push(Span::styled(matched,style.bg(Color::Yellow).fg(Color::Black)));
+            last = idx+matched.len();
+        }
+        spans.push(Span::styled(&text[last..],style));
+        spans
+    }
+
+    pub fn render<B: Backend>(&mut self,terminal: &mut Terminal<B>, popup: Option<&mut AppPopupContext>) {
+        terminal.draw(|f| {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1),Constraint::Min(3),Constraint::Length(3)].as_ref())
+                .split(f.size());
+
+            let mut style_text = Style::default();
+            let mut style_stderr = Style::default().fg(Color::LightRed);
+            let mut style_timestamp = Style::default().fg(Color::DarkGray);
+            let mut style_non = Style::default().fg(Color::Blue);
+            let mut style_help = Style::default().fg(Color::Cyan);
+            let mut header_style = Style::default().fg(Color::LightRed);
+            let mut header_other_style = Style::default().bg(Color::Black).fg(Color::LightYellow);
+
+            if popup.is_some() {
+                let style_dim = Style::default().fg(Color::Gray).add_modifier(Modifier::DIM);
+                style_text = style_dim;
+                style_stderr = style_dim;
+                style_timestamp = style_dim;
+                style_non = style_dim;
+                style_help = style_dim;
+                header_style = style_dim;
+                header_other_style = style_dim;
+            }
+
+            self.height = chunks[1].height.saturating_sub(2) as usize;
+            if self.follow {
+                self.top = self.max_top();
+            }
+            self.top = std::cmp::min(self.top,self.max_top());
+
+            let header_paragraph = Paragraph::new(Spans::from(vec![Span::styled(VERSION,header_style),Span::raw(" | Logs")]))
+                .style(header_other_style)
+                .alignment(Alignment::Center);
+            f.render_widget(header_paragraph,chunks[0]);
+
+            let lines = self.lines.iter().skip(self.top).take(self.height).map(|line| {
+                let style = if line.output == Output::Stderr { style_stderr } else { style_text };
+                let mut spans = Vec::new();
+                if self.timestamps {
+                    spans.push(Span::styled(line.short_timestamp()+" ",style_timestamp));
+                }
+                spans.extend(self.highlight(&line.text,style));
+                Spans::from(spans)
+            }).collect::<Vec<Spans>>();
+            let mut title = format!("{} - lines {}-{} of {}",self.name,std::cmp::min(self.top+1,self.lines.len()),std::cmp::min(self.top+self.height,self.lines.len()),self.lines.len());
+            if self.follow {
+                title.push_str(" [follow]");
+            }
+            if self.ended {
+                title.push_str(" [stream ended]");
+            }
+            let logs = Paragraph::new(lines)
+                .block(Block::default().borders(Borders::ALL).title(title).style(style_non));
+            f.render_widget(logs,chunks[1]);
+
+            if self.searching {
+                let block = Block::default().borders(Borders::ALL).title("Search - accept <enter>; cancel <esc>");
+                self.search.render_on(f,chunks[2],"Search: ",block,style_help,popup.is_none());
+            }
+            else {
+                let help = Paragraph::new(Text::from("f - follow; t - timestamps; / - search; n/N - next/previous match; w/s/PgUp/PgDn - scroll; g/G - top/bottom; S - save to file; q - back")).style(style_help)
+                                    .block(Block::default().borders(Borders::ALL).title("Help"))
+                                    .alignment(Alignment::Left);
+                f.render_widget(help,chunks[2]);
+            }
+            if let Some(popup) = popup {
+                popup.render_on(f);
+            }
+        }).unwrap();
+    }
+
+    /// Handles a key press, returns the next state once the screen is left.
+    fn handle_key(&mut self, key: Key) -> Option<AppState> {
+        if self.searching {
+            match key {
+                Key::Char('\n') => self.searching = false,
+                Key::Esc => {
+                    self.searching = false;
+                    self.search.clear();
+                    self.current_match = None;
+                    self.top = self.search_origin;
+                },
+                Key::Ctrl('c') => return Some(AppState::Exiting),
+                key => {
+                    if self.search.handle_key(key) {
+                        self.update_search();
+                    }
+                },
+            }
+            return None;
+        }
+        match key {
+            Key::Char('q') | Key::Esc => return Some(AppState::Search),
+            Key::Ctrl('c') => return Some(AppState::Exiting),
+            Key::Char('f') => self.follow = !self.follow,
+            Key::Char('t') => self.timestamps = !self.timestamps,
+            Key::Char('/') => {
+                self.searching = true;
+                self.search.clear();
+                self.search_origin = self.top;
+            },
+            Key::Char('n') => self.search_next(true),
+            Key::Char('N') => self.search_next(false),
+            Key::Up | Key::Char('w') => self.scroll_up(1),
+            Key::Down | Key::Char('s') => self.scroll_down(1),
+            Key::PageUp => self.scroll_up(std::cmp::max(self.height,1)),
+            Key::PageDown => self.scroll_down(std::cmp::max(self.height,1)),
+            Key::Char('g') => self.scroll_up(self.lines.len()),
+            Key::Char('G') => self.follow = true,
+            _ => {},
+        }
+        None
+    }
+
+    async fn save_dialog<B: Backend>(&mut self, term: &mut Terminal<B>, events: &mut Events) {
+        let path = AppPopupContext::input(String::from("Save log to file:"),format!("{}.log",self.name)).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+        if path.is_empty() || !run_command::confirm_overwrite(&path,events,|popup| self.render(term, Some(popup))).await {
+            return;
+        }
+        let mut popup = match self.save(&path) {
+            Ok(message) => AppPopupContext::new(message),
+            Err(error) => AppPopupContext::new(error).style(Style::default().fg(Color::LightRed)),
+        };
+        popup.event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+    }
+
+    pub async fn event_loop<B: Backend>(&mut self, term: &mut Terminal<B>, docker: &Docker, events: &mut Events) -> AppState {
+        let inspection = match docker.containers().get(&self.id).inspect().await {
+            Ok(x) => x,
+            Err(e) => {
+                AppPopupContext::new(format!("Could not inspect container: {}",e)).style(Style::default().fg(Color::LightRed)).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+                return AppState::Search;
+            }
+        };
+        self.name = inspection.name.trim_start_matches('/').to_owned();
+        let mut stream = match session::logs(&self.id,inspection.config.tty,MAX_LINES).await {
+            Ok(x) => x,
+            Err(error) => {
+                AppPopupContext::new(format!("Could not read the logs: {}",error)).style(Style::default().fg(Color::LightRed)).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+                return AppState::Search;
+            }
+        };
+
+        self.render(term,None);
+        loop {
+            tokio::select! {
+                output = stream.next(), if !self.ended => {
+                    match output {
+                        Ok(Some(output)) => {
+                            for (kind,data) in output {
+                                self.push_output(kind,data);
+                            }
+                        },
+                        Ok(None) | Err(_) => self.ended = true,
+                    }
+                },
+                event = events.next() => {
+                    match event {
+                        Event::Key(Key::Char('S')) if !self.searching => self.save_dialog(term,events).await,
+                        Event::Key(key) => {
+                            if let Some(state) = self.handle_key(key) {
+                                return state;
+                            }
+                        },
+                        Event::Paste(text) if self.searching => {
+                            self.search.insert_str(&text);
+                            self.update_search();
+                        },
+                        Event::InputClosed => return AppState::Exiting,
+                        // Follow the output of the container once it got started again.
+                        Event::Docker(event) if self.ended && event.action == "start" && event.actor.id == self.id => {
+                            if let Ok(x) = session::logs(&self.id,inspection.config.tty,0).await {
+                                stream = x;
+                                self.ended = false;
+                            }
+                        },
+                        _ => {},
+                    }
+                },
+            }
+            self.render(term,None);
+        }
+    }
+}
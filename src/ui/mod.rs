@@ -1,4 +1,5 @@
 use shiplift::{Docker};
+use crate::config::Config;
 use tui::{
     backend::Backend,
     Terminal,
@@ -10,6 +11,17 @@ mod command;
 mod help;
 mod popup;
 mod new_container;
+mod export;
+mod logs;
+mod stats;
+mod details;
+mod table;
+mod events;
+mod tasks;
+mod images;
+mod text_input;
+mod form;
+mod run_command;
 
 
 
@@ -20,36 +32,54 @@ pub enum AppState {
     Exiting,
     Help,
     NewContainer,
+    Logs(String),
+    Stats(String),
+    RunCommand(String),
+    Tasks,
+    Images,
 }
 
 pub struct App {
     docker: Docker,
+    config: Config,
     state: AppState,
     error_context: error::AppErrorContext,
     search_context: search::AppSearchContext,
     command_context: command::AppCommandContext,
     help_context: help::AppHelpContext,
+    events: events::Events,
+    tasks: tasks::Tasks,
 }
 
 impl App {
-    pub async fn new(docker: Docker) -> App {
-        let mut search_context = search::AppSearchContext::new();
-        let state = match docker.info().await {
-            Ok(_) => {
+    pub async fn new(docker: Docker, config: Result<Config,String>) -> App {
+        let (config,config_error) = match config {
+            Ok(x) => (x,None),
+            Err(error) => (Config::default(),Some(error)),
+        };
+        let events = events::Events::new(&docker);
+        let tasks = tasks::Tasks::new(&events);
+        let mut search_context = search::AppSearchContext::new(config.clone(),tasks.clone());
+        let state = match (config_error,docker.info().await) {
+            (Some(error),_) => AppState::Error(format!("{} Press any key to quit.",error)),
+            (None,Ok(_)) => {
                 search_context.update(&docker).await;
                 AppState::Search
             },
-            Err(_) => AppState::Error("Could not connect to docker! Is docker installed? Press any key to quit.".to_owned()),
+            (None,Err(_)) => AppState::Error("Could not connect to docker! Is docker installed? Press any key to quit.".to_owned()),
         };
 
 
         App {
+            events,
             docker,
             state,
             error_context: error::AppErrorContext::new(),
             search_context: search_context,
-            command_context: command::AppCommandContext::new(),
+            command_context: command::AppCommandContext::new(config.clone(),tasks.clone()),
             help_context: help::AppHelpContext::new(),
+            config,
+            tasks,
         }
     }
 
@@ -58,19 +88,34 @@ impl App {
             self.state = match &self.state {
                 AppState::Error(error) => {
                     self.error_context.set_error(error.clone());
-                    self.error_context.event_loop(terminal)
+                    self.error_context.event_loop(terminal,&mut self.events).await
                 },
                 AppState::Search => {
-                    self.search_context.event_loop(terminal,&self.docker).await
+                    self.search_context.event_loop(terminal,&self.docker,&mut self.events).await
                 },
                 AppState::Command => {
-                    self.command_context.event_loop(terminal)
+                    self.command_context.event_loop(terminal,&self.docker,&mut self.events).await
                 },
                 AppState::Help => {
-                    self.help_context.event_loop(terminal)
+                    self.help_context.event_loop(terminal,&mut self.events).await
                 },
                 AppState::NewContainer => {
-                    new_container::AppNewContainerContext::new().event_loop(terminal,&self.docker).await
+                    new_container::AppNewContainerContext::new(&self.config,self.tasks.clone()).event_loop(terminal,&self.docker,&mut self.events).await
+                },
+                AppState::Logs(id) => {
+                    logs::AppLogsContext::new(id.clone()).event_loop(terminal,&self.docker,&mut self.events).await
+                },
+                AppState::Stats(id) => {
+                    stats::AppStatsContext::new(id.clone()).event_loop(terminal,&self.docker,&mut self.events).await
+                },
+                AppState::RunCommand(id) => {
+                    run_command::AppRunCommandContext::new(id.clone()).event_loop(terminal,&mut self.events).await
+                },
+                AppState::Tasks => {
+                    tasks::AppTasksContext::new(self.tasks.clone()).event_loop(terminal,&mut self.events).await
+                },
+                AppState::Images => {
+                    images::AppImagesContext::new(self.config.smart_case,self.tasks.clone()).event_loop(terminal,&self.docker,&mut self.events).await
                 },
                 AppState::Exiting => {
                     return;
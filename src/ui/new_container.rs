@@ -1,68 +1,415 @@
-use shiplift::{Docker,ContainerOptions};
-use std::io;
+use shiplift::Docker;
+use std::path::PathBuf;
+use chrono::Utc;
 use tui::{
     backend::Backend,
-    layout::{Constraint, Direction, Layout,Alignment},
+    layout::{Constraint, Direction, Layout,Alignment,Rect},
     style::{Color,Style,Modifier},
     text::{Span,Text,Spans},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, Clear, Paragraph, Row, Cell, Table, Wrap},
     Terminal,
 };
-use termion::event::Key;
-use termion::input::TermRead;
+use termion::event::{Key,MouseButton,MouseEvent};
+use crate::ui::events::{Event,Events};
+use crate::ui::tasks::{Progress,Tasks};
 use crate::ui::AppState;
 use crate::VERSION;
-use home;
+use crate::input;
 use crate::ui::popup;
+use crate::ui::search::highlighted;
+use crate::ui::details::format_duration;
+use crate::ui::text_input::TextInput;
+use crate::ui::form::{self,Toggle};
+use crate::ui::run_command::{self,Format};
+use crate::run_command::RunCommand;
+use crate::workspace;
+use crate::fuzzy;
+use crate::images::{self,ImageEntry};
+use crate::container::{self,ContainerSetup,WorkingDirectorySetup};
+use crate::config::{Config,Profile};
 
+/// Local images shown below the image field while typing.
+const MAX_SUGGESTIONS: usize = 6;
+
+#[derive(Clone,Copy,PartialEq)]
 enum CurrentPhase {
+    SelectProfile,
     SelectName,
-    SelectImage,
+    AutoRemove,
     EntryCommand,
     AddSSHKeys,
     GitConfig,
-    AutoRemove,
+    SelectImage,
+    SelectWorkingDir,
     WorkingDirectory,
+    Review,
+}
+
+/// The phases in the order of the form, the review follows the last field.
+const PHASES: [CurrentPhase; 10] = [
+    CurrentPhase::SelectProfile,
+    CurrentPhase::SelectName,
+    CurrentPhase::AutoRemove,
+    CurrentPhase::EntryCommand,
+    CurrentPhase::AddSSHKeys,
+    CurrentPhase::GitConfig,
+    CurrentPhase::SelectImage,
+    CurrentPhase::SelectWorkingDir,
+    CurrentPhase::WorkingDirectory,
+    CurrentPhase::Review,
+];
+
+/// The fields which can hold invalid values.
+const VALIDATED: [CurrentPhase; 4] = [CurrentPhase::SelectName,CurrentPhase::EntryCommand,CurrentPhase::SelectImage,CurrentPhase::WorkingDirectory];
+
+#[derive(Clone,Copy,PartialEq)]
+enum ReviewAction {
+    Confirm,
+    Back,
+    Cancel,
+}
+
+const REVIEW_ACTIONS: [ReviewAction; 3] = [ReviewAction::Confirm,ReviewAction::Back,ReviewAction::Cancel];
+
+impl ReviewAction {
+    fn label(&self) -> &'static str {
+        match self {
+            ReviewAction::Confirm => "Confirm",
+            ReviewAction::Back => "Back",
+            ReviewAction::Cancel => "Cancel",
+        }
+    }
 }
 
-enum WorkingDirectorySetup {
-    MountDirectory(String),
-    CopyDirectory(String),
+/// How the working directory is handed to the container, the path itself is
+/// edited separately.
+#[derive(Clone,Copy,PartialEq)]
+enum WorkingDirMode {
+    Mount,
+    Copy,
     DontUse,
 }
 
 pub struct AppNewContainerContext {
-    container_name: String,
-    auto_remove: String,
-    entry_command: String,
-    import_keys: String,
-    git_config: String,
-    image_name: String,
-    working_dir: WorkingDirectorySetup,
+    container_name: TextInput,
+    auto_remove: Toggle,
+    entry_command: TextInput,
+    import_keys: Toggle,
+    git_config: Toggle,
+    image_name: TextInput,
+    working_dir: WorkingDirMode,
+    working_dir_path: TextInput,
+    mount_target: String,
+    config: Config,
+    profile: Option<String>,
     phase: CurrentPhase,
+    tasks: Tasks,
+    local_images: Vec<ImageEntry>,
+    /// Local images matching the image field with the matched characters, best first.
+    suggestions: Vec<(usize,Vec<usize>)>,
+    selected_suggestion: usize,
+    /// Why the image could not be found, cleared when the image field is edited.
+    image_error: Option<String>,
+    /// The image checked in the background and the phase to continue with once
+    /// it turns out to be available.
+    checking: Option<(String,CurrentPhase)>,
+    /// The last image which passed the check.
+    checked_image: Option<String>,
+    /// Fields whose errors are shown, the ones the user tried to leave.
+    touched: Vec<CurrentPhase>,
+    /// Whether the reviewed image exists locally or has to be pulled.
+    image_available: bool,
+    /// Index of the selected button of the review.
+    review_action: usize,
+    /// How the preview of the review shows the container.
+    preview_format: Format,
+    /// Where the fields and review buttons were drawn, to map clicks to them.
+    field_areas: Vec<(CurrentPhase,Rect)>,
+    button_areas: Vec<(ReviewAction,Rect)>,
+}
+
+/// Uploads `source` into the freshly created container `id` and reports the
+/// progress of packing it.
+async fn copy_working_directory(docker: &Docker, progress: &Progress, id: &str, source: PathBuf, target: String) -> Result<String,String> {
+    progress.set(format!("Packing \"{}\"",source.display()),None);
+    let packing = progress.clone();
+    let archive = tokio::task::spawn_blocking(move || workspace::pack_directory(&source,&target,|stats| {
+        if stats.files % 100 == 0 {
+            packing.set(format!("Packing \"{}\": {}",source.display(),stats.describe()),None);
+        }
+    })).await.map_err(|e| format!("Could not pack the working directory: {}",e))??;
+    progress.set(format!("Uploading {} into the container",archive.stats.describe()),None);
+    let stats = workspace::upload(docker,id,archive).await?;
+    Ok(format!("copied {}",stats.describe()))
 }
 
 impl AppNewContainerContext { 
-    pub fn new() -> AppNewContainerContext { 
+    pub fn new(config: &Config, tasks: Tasks) -> AppNewContainerContext { 
         AppNewContainerContext { 
-            container_name: String::new(),
-            auto_remove: String::from("no"),
-            image_name: String::from("shadowitaly/neovim_arch:latest"),
-            import_keys: String::from("yes"),
-            git_config: String::from("yes"),
-            entry_command: String::from("/bin/zsh"),
-            working_dir: WorkingDirectorySetup::MountDirectory(std::env::current_dir().unwrap().to_str().unwrap().to_string()),
-            phase: CurrentPhase::SelectName,
+            container_name: TextInput::default(),
+            auto_remove: Toggle::new(config.auto_remove),
+            image_name: TextInput::new(config.image.clone()),
+            import_keys: Toggle::new(config.import_ssh_keys),
+            git_config: Toggle::new(config.import_git_config),
+            entry_command: TextInput::new(config.entry_command.clone()),
+            working_dir: WorkingDirMode::Mount,
+            working_dir_path: TextInput::new(std::env::current_dir().unwrap().to_str().unwrap()),
+            mount_target: config.mount_target.clone(),
+            config: config.clone(),
+            profile: None,
+            phase: if config.profiles.is_empty() { CurrentPhase::SelectName } else { CurrentPhase::SelectProfile },
+            tasks,
+            local_images: Vec::new(),
+            suggestions: Vec::new(),
+            selected_suggestion: 0,
+            image_error: None,
+            checking: None,
+            checked_image: None,
+            touched: Vec::new(),
+            image_available: false,
+            review_action: 0,
+            preview_format: Format::Run,
+            field_areas: Vec::new(),
+            button_areas: Vec::new(),
+        }
+    }
+
+    /// The text field of the current phase.
+    fn input(&mut self) -> Option<&mut TextInput> {
+        match self.phase {
+            CurrentPhase::SelectProfile => None,
+            CurrentPhase::SelectName => Some(&mut self.container_name),
+            CurrentPhase::EntryCommand => Some(&mut self.entry_command),
+            CurrentPhase::SelectImage => Some(&mut self.image_name),
+            CurrentPhase::WorkingDirectory if self.working_dir == WorkingDirMode::DontUse => None,
+            CurrentPhase::WorkingDirectory => Some(&mut self.working_dir_path),
+            _ => None,
+        }
+    }
+
+    /// The toggle of the current phase.
+    fn toggle(&mut self) -> Option<&mut Toggle> {
+        match self.phase {
+            CurrentPhase::AutoRemove => Some(&mut self.auto_remove),
+            CurrentPhase::AddSSHKeys => Some(&mut self.import_keys),
+            CurrentPhase::GitConfig => Some(&mut self.git_config),
+            _ => None,
+        }
+    }
+
+    /// Applies `key` to the field of the current phase, returns false if it does
+    /// not edit the field.
+    fn edit(&mut self, key: Key) -> bool {
+        if let Some(input) = self.input() {
+            return input.handle_key(key);
+        }
+        self.toggle().map(|x| x.handle_key(key)).unwrap_or(false)
+    }
+
+    /// Called after the text of the current field changed.
+    fn edited(&mut self) {
+        if self.phase == CurrentPhase::SelectImage {
+            self.image_error = None;
+            self.checking = None;
+            self.update_suggestions();
+        }
+    }
+
+    /// Why the value of the field of `phase` cannot be used.
+    fn field_error(&self, phase: CurrentPhase) -> Option<String> {
+        match phase {
+            CurrentPhase::SelectName => container::validate_name(self.container_name.value()).err(),
+            CurrentPhase::EntryCommand if self.entry_command.value().trim().is_empty() => Some(String::from("The entry command cannot be empty")),
+            CurrentPhase::SelectImage => images::validate_reference(self.image_name.value()).err().or_else(|| self.image_error.clone()),
+            CurrentPhase::WorkingDirectory if self.working_dir != WorkingDirMode::DontUse && !std::path::Path::new(self.working_dir_path.value()).is_dir() => {
+                Some(String::from("The path is not a directory"))
+            },
+            _ => None,
+        }
+    }
+
+    /// The error shown below the field of `phase`, only once the user tried to
+    /// leave it.
+    fn shown_error(&self, phase: CurrentPhase) -> Option<String> {
+        if self.touched.contains(&phase) { self.field_error(phase) } else { None }
+    }
+
+    /// Marks the current field as touched and checks it.
+    fn check_current(&mut self) -> bool {
+        if !self.touched.contains(&self.phase) {
+            self.touched.push(self.phase);
+        }
+        self.field_error(self.phase).is_none()
+    }
+
+    fn working_dir_setup(&self) -> WorkingDirectorySetup {
+        let path = self.working_dir_path.value().to_owned();
+        match self.working_dir {
+            WorkingDirMode::Mount => WorkingDirectorySetup::MountDirectory(path),
+            WorkingDirMode::Copy => WorkingDirectorySetup::CopyDirectory(path),
+            WorkingDirMode::DontUse => WorkingDirectorySetup::DontUse,
+        }
+    }
+
+    /// Fuzzy matches the local images against the image field.
+    fn update_suggestions(&mut self) {
+        let case_sensitive = fuzzy::is_case_sensitive(self.image_name.value(),self.config.smart_case);
+        let mut scored = self.local_images.iter().enumerate().filter_map(|(idx,x)| {
+            let matched = fuzzy::fuzzy_match(self.image_name.value(),&x.name,case_sensitive)?;
+            Some((idx,matched.score,matched.positions))
+        }).collect::<Vec<(usize,i64,Vec<usize>)>>();
+        scored.sort_by(|a,b| b.1.cmp(&a.1).then_with(|| self.local_images[b.0].created.cmp(&self.local_images[a.0].created)));
+        self.suggestions = scored.into_iter().take(MAX_SUGGESTIONS).map(|(idx,_,positions)| (idx,positions)).collect();
+        self.selected_suggestion = 0;
+    }
+
+    /// The selected suggestion unless the image field already names it.
+    fn suggestion(&self) -> Option<&str> {
+        let (idx,_) = self.suggestions.get(self.selected_suggestion)?;
+        Some(self.local_images[*idx].name.as_str()).filter(|x| *x != self.image_name.value())
+    }
+
+    fn is_image_checked(&self) -> bool {
+        self.checked_image.as_deref() == Some(self.image_name.value())
+    }
+
+    /// Checks in the background that the image field names an image which is
+    /// available locally or can be pulled, the result arrives as
+    /// `Event::ImageChecked`. The form continues with `then` if it is.
+    fn check_image(&mut self, docker: &Docker, events: &Events, then: CurrentPhase) {
+        let image = self.image_name.value().to_owned();
+        self.checking = Some((image.clone(),then));
+        let local = self.local_images.iter().any(|x| x.name == image);
+        let docker = docker.clone();
+        let sender = events.sender();
+        tokio::spawn(async move {
+            let result = if local || images::exists(&docker,&image).await { Ok(true) } else { images::check_pullable(&image).await.map(|_| false) };
+            let _ = sender.send(Event::ImageChecked(image,result));
+        });
+    }
+
+    /// Applies the result of `check_image`, the form only continues if the user
+    /// is still waiting in the image field.
+    fn image_checked(&mut self, docker: &Docker, events: &Events, image: String, result: Result<bool,String>) {
+        let then = match &self.checking {
+            Some((x,then)) if *x == image => *then,
+            _ => return,
+        };
+        self.checking = None;
+        // A profile may have replaced the image meanwhile.
+        if image != self.image_name.value() {
+            return;
+        }
+        match result {
+            Ok(available) => {
+                self.checked_image = Some(image);
+                self.image_available = available;
+                if self.phase == CurrentPhase::SelectImage {
+                    self.advance(docker,events,then);
+                }
+            },
+            Err(error) => self.image_error = Some(error),
+        }
+    }
+
+    fn render_suggestions<B: Backend>(&self, f: &mut tui::Frame<B>, field: Rect) {
+        if self.suggestions.is_empty() {
+            return;
+        }
+        let size = f.size();
+        let top = field.y+field.height-1;
+        let area = Rect::new(field.x+3,top,field.width.saturating_sub(6),std::cmp::min(self.suggestions.len() as u16+2,size.height.saturating_sub(top)));
+        let highlight = Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD);
+        let now = Utc::now();
+        let rows = self.suggestions.iter().enumerate().map(|(idx,(image,positions))| {
+            let image = &self.local_images[*image];
+            let row = Row::new(vec![
+                Cell::from(Spans::from(highlighted(&image.name,positions,highlight))),
+                Cell::from(workspace::format_size(image.size)),
+                Cell::from(format!("{} ago",format_duration((now-image.created).num_seconds()))),
+            ]);
+            if idx == self.selected_suggestion { row.style(Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD)) } else { row }
+        }).collect::<Vec<Row>>();
+        let table = Table::new(rows)
+            .widths(&[Constraint::Percentage(60),Constraint::Length(9),Constraint::Min(10)])
+            .block(Block::default().borders(Borders::ALL).title(" Local images (<ctrl-n>/<ctrl-p> to choose, <tab> to accept) "))
+            .style(Style::default().fg(Color::White).bg(Color::Black));
+        f.render_widget(Clear,area);
+        f.render_widget(table,area);
+    }
+
+    /// The lines of the review, a label followed by one or more values.
+    fn review_lines(&self) -> Vec<Spans<'static>> {
+        let setup = self.setup();
+        let image = if self.image_available { format!("{} (available locally)",setup.image) } else { format!("{} (pulled on confirm)",setup.image) };
+        let working_dir = match &setup.working_dir {
+            WorkingDirectorySetup::MountDirectory(x) => format!("{} mounted at {}",x,setup.mount_target),
+            WorkingDirectorySetup::CopyDirectory(x) => format!("{} copied to {}",x,setup.mount_target),
+            WorkingDirectorySetup::DontUse => String::from("not used"),
+        };
+        let yes_no = |x: bool| String::from(if x { "yes" } else { "no" });
+        let entries = vec![
+            ("Container",vec![setup.container_name()]),
+            ("Image",vec![image]),
+            ("Command",vec![setup.entry_command.clone()]),
+            ("Profile",vec![setup.profile.clone().unwrap_or_else(|| String::from("--/--"))]),
+            ("Auto remove",vec![yes_no(setup.auto_remove)]),
+            ("Working dir",vec![working_dir]),
+            ("Volumes",setup.volumes().unwrap_or_else(|e| vec![e])),
+            ("Environment",setup.env()),
+            ("Ports",setup.ports.clone()),
+        ];
+        let label_style = Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD);
+        let mut lines = Vec::new();
+        for (label,values) in entries {
+            let values = if values.is_empty() { vec![String::from("--/--")] } else { values };
+            for (idx,value) in values.into_iter().enumerate() {
+                let label = if idx == 0 { label } else { "" };
+                lines.push(Spans::from(vec![Span::styled(format!("{:<13}",label),label_style),Span::raw(value)]));
+            }
+        }
+        lines
+    }
+
+    /// The container as `docker run` command line or compose service.
+    fn preview(&self) -> String {
+        RunCommand::from_setup(&self.setup()).map(|x| self.preview_format.text(&x)).unwrap_or_else(|e| e)
+    }
+
+    fn render_review<B: Backend>(&mut self, f: &mut tui::Frame<B>, area: Rect, style_hi: Style, style_non: Style) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(50),Constraint::Min(3),Constraint::Length(3)].as_ref())
+            .split(area);
+        let review = Paragraph::new(self.review_lines())
+            .block(Block::default().borders(Borders::ALL).title(" Review the new container "))
+            .style(style_non)
+            .wrap(Wrap { trim: false });
+        f.render_widget(review,chunks[0]);
+        let preview = Paragraph::new(Text::from(self.preview()))
+            .block(Block::default().borders(Borders::ALL).title(format!(" Preview: {} (<p> to switch) ",self.preview_format.title())))
+            .style(style_non);
+        f.render_widget(preview,chunks[1]);
+
+        let buttons = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(14),Constraint::Length(14),Constraint::Length(14),Constraint::Min(0)].as_ref())
+            .split(chunks[2]);
+        self.button_areas.clear();
+        for (idx,action) in REVIEW_ACTIONS.iter().enumerate() {
+            let style = if idx == self.review_action { style_hi.add_modifier(Modifier::BOLD) } else { style_non };
+            let button = Paragraph::new(Span::raw(action.label()))
+                .block(Block::default().borders(Borders::ALL))
+                .style(style)
+                .alignment(Alignment::Center);
+            f.render_widget(button,buttons[idx]);
+            self.button_areas.push((*action,buttons[idx]));
         }
     }
 
     pub fn render<B: Backend>(&mut self,terminal: &mut Terminal<B>,popup: Option<&mut popup::AppPopupContext>) {
+        let errors = VALIDATED.iter().filter_map(|x| self.shown_error(*x).map(|error| (*x,error))).collect::<Vec<(CurrentPhase,String)>>();
         terminal.draw(|f| {
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([Constraint::Length(1),Constraint::Length(3),Constraint::Length(3),Constraint::Length(3),Constraint::Length(3),Constraint::Length(3),Constraint::Length(3),Constraint::Length(3),Constraint::Length(3)].as_ref())
-                .split(f.size());
-
             let mut style_non = Style::default().fg(Color::Blue);
             let mut style_hi = Style::default().fg(Color::LightGreen);
             let mut header_style = Style::default().fg(Color::LightRed);
@@ -80,274 +427,382 @@ impl AppNewContainerContext {
             let header_paragraph = Paragraph::new(Spans::from(vec![Span::styled(VERSION,header_style),Span::raw(" | New container creation")]))
                 .style(header_other_style)
                 .alignment(Alignment::Center);
+            let help_text = if self.phase == CurrentPhase::Review {
+                "Enter - Select, Left/Right - Choose, c - Confirm, b - Back, Esc - Cancel, w - Save preview, y - Copy preview, Ctrl+h - Show full help"
+            }
+            else {
+                "Tab/Down - Next field, Shift+Tab/Up - Previous field, Ctrl+n/Ctrl+p - Choose a local image, Esc - Cancel, Ctrl+h - Show full help"
+            };
+            let help = Paragraph::new(Text::from(help_text)).style(style_help)
+                                    .block(Block::default().borders(Borders::ALL).title("Help"))
+                                    .alignment(Alignment::Left);
 
+            if self.phase == CurrentPhase::Review {
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(1),Constraint::Min(6),Constraint::Length(3)].as_ref())
+                    .split(f.size());
+                f.render_widget(header_paragraph,chunks[0]);
+                self.render_review(f,chunks[1],style_hi,style_non);
+                f.render_widget(help,chunks[2]);
+                if let Some(popup) = popup {
+                    popup.render_on(f);
+                }
+                return;
+            }
 
-            let mut paragraph = Paragraph::new(Span::raw(format!(">> {}",&self.container_name)))
-                                    .alignment(Alignment::Left)
-                                    .block(Block::default().borders(Borders::ALL).title("Enter new container name"));
-            paragraph = paragraph.style(match self.phase {
-                CurrentPhase::SelectName => {style_hi.clone()},
-                _ => style_non.clone()
-            });
-
-
-            let mut image_name = Paragraph::new(Span::raw(format!(">> {}",&self.image_name)))
-                                    .alignment(Alignment::Left)
-                                    .block(Block::default().borders(Borders::ALL).title("Enter the image name"));
- 
-            image_name = image_name.style(match self.phase {
-                CurrentPhase::SelectImage => style_hi.clone(),
-                _ => style_non.clone()
-            });
-            f.render_widget(header_paragraph,chunks[0]);
-            f.render_widget(paragraph,chunks[1]);
-            f.render_widget(image_name,chunks[6]);
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1),Constraint::Length(3),Constraint::Length(3),Constraint::Length(3),Constraint::Length(3),Constraint::Length(3),Constraint::Length(3),Constraint::Length(3),Constraint::Length(3),Constraint::Length(3),Constraint::Length(3)].as_ref())
+                .split(f.size());
+            self.field_areas = PHASES[..PHASES.len()-1].iter().cloned().zip(chunks[1..10].iter().cloned()).collect();
 
-            let mut remove = Paragraph::new(Span::raw(format!(">> {}",&self.auto_remove)))
-                                    .alignment(Alignment::Left)
-                                    .block(Block::default().borders(Borders::ALL).title(" Autoremove (yes/no) "));
-            remove = remove.style(match self.phase {
-                CurrentPhase::AutoRemove => {style_hi.clone()},
-                _ => style_non.clone()
-            });
-            f.render_widget(remove,chunks[2]);
-    
-            let mut entry_command = Paragraph::new(Span::raw(format!(">> {}",&self.entry_command)))
+            let profile = match &self.profile {
+                Some(x) => format!(">> {} - {}",x,self.config.profiles[x].describe()),
+                None => String::from(">> --/--"),
+            };
+            let mut profile = Paragraph::new(Span::raw(profile))
                                     .alignment(Alignment::Left)
-                                    .block(Block::default().borders(Borders::ALL).title(" Entry command (Experienced users only!) "));
-            entry_command = entry_command.style(match self.phase {
-                CurrentPhase::EntryCommand => {style_hi.clone()},
-                _ => style_non.clone()
+                                    .block(Block::default().borders(Borders::ALL).title(" Profile (<space> to switch) "));
+            profile = profile.style(match self.phase {
+                CurrentPhase::SelectProfile => {style_hi},
+                _ => style_non
             });
-            f.render_widget(entry_command,chunks[3]);
+            f.render_widget(profile,chunks[1]);
 
-            let mut import_ssh_key = Paragraph::new(Span::raw(format!(">> {}",&self.import_keys)))
-                                    .alignment(Alignment::Left)
-                                    .block(Block::default().borders(Borders::ALL).title(" Import SSH Keys (yes/no) "));
-            import_ssh_key = import_ssh_key.style(match self.phase {
-                CurrentPhase::AddSSHKeys => {style_hi.clone()},
-                _ => style_non.clone()
-            });
-            f.render_widget(import_ssh_key,chunks[4]);
+            f.render_widget(header_paragraph,chunks[0]);
+            let phase = self.phase;
+            let focused = popup.is_none();
+            let field = |title: &str, phase: CurrentPhase| form::field_block(title,errors.iter().find(|x| x.0 == phase).map(|x| x.1.as_str()));
+            let style = |active: bool| if active { style_hi } else { style_non };
 
-            let mut use_git_config = Paragraph::new(Span::raw(format!(">> {}",&self.git_config)))
-                                    .alignment(Alignment::Left)
-                                    .block(Block::default().borders(Borders::ALL).title(" Import Host git config (yes/no) "));
-            use_git_config = use_git_config .style(match self.phase {
-                CurrentPhase::GitConfig => {style_hi.clone()},
-                _ => style_non.clone()
-            });
-            f.render_widget(use_git_config,chunks[5]);
+            let active = phase == CurrentPhase::SelectName;
+            self.container_name.render_on(f,chunks[2],">> ",field("Enter new container name",CurrentPhase::SelectName),style(active),focused && active);
+            let active = phase == CurrentPhase::AutoRemove;
+            self.auto_remove.render_on(f,chunks[3],field(" Autoremove (<space> to toggle) ",CurrentPhase::AutoRemove),style(active));
+            let active = phase == CurrentPhase::EntryCommand;
+            self.entry_command.render_on(f,chunks[4],">> ",field(" Entry command (Experienced users only!) ",CurrentPhase::EntryCommand),style(active),focused && active);
+            let active = phase == CurrentPhase::AddSSHKeys;
+            self.import_keys.render_on(f,chunks[5],field(" Import SSH Keys (<space> to toggle) ",CurrentPhase::AddSSHKeys),style(active));
+            let active = phase == CurrentPhase::GitConfig;
+            self.git_config.render_on(f,chunks[6],field(" Import Host git config (<space> to toggle) ",CurrentPhase::GitConfig),style(active));
+            let active = phase == CurrentPhase::SelectImage;
+            let title = if self.checking.is_some() { "Enter the image name (checking the image...)" } else { "Enter the image name" };
+            self.image_name.render_on(f,chunks[7],">> ",field(title,CurrentPhase::SelectImage),style(active),focused && active);
 
-            let mut path = match &self.working_dir {
-                WorkingDirectorySetup::MountDirectory(x) => {
-                    Paragraph::new(Span::raw(format!(">> {}",&x)))
-                                    .alignment(Alignment::Left)
-                                    .block(Block::default().borders(Borders::ALL).title("Mount this path inside the container: "))
-                },
-                WorkingDirectorySetup::CopyDirectory(x) => {
-                    Paragraph::new(Span::raw(format!(">> {}",&x)))
-                                    .alignment(Alignment::Left)
-                                    .block(Block::default().borders(Borders::ALL).title("Copy this path inside the container: "))
-                },
-                _ => {
-                    Paragraph::new(Span::raw(">> --/--"))
-                                    .alignment(Alignment::Left)
-                                    .block(Block::default().borders(Borders::ALL).title("Neither mount nor copy working dir"))
-                }
+            let mode = match self.working_dir {
+                WorkingDirMode::Mount => ">> Mount",
+                WorkingDirMode::Copy => ">> Copy",
+                WorkingDirMode::DontUse => ">> Don't use",
             };
+            let mode = Paragraph::new(Span::raw(mode)).block(field(" Working directory (<space> to switch) ",CurrentPhase::SelectWorkingDir)).style(style(phase == CurrentPhase::SelectWorkingDir));
+            f.render_widget(mode,chunks[8]);
 
-            path = path.style(match self.phase {
-                CurrentPhase::WorkingDirectory => {style_hi.clone()},
-                _ => style_non.clone()
-            });
-            f.render_widget(path,chunks[7]);
-
+            let active = phase == CurrentPhase::WorkingDirectory;
+            let working_dir = |title| field(title,CurrentPhase::WorkingDirectory);
+            match self.working_dir {
+                WorkingDirMode::Mount => self.working_dir_path.render_on(f,chunks[9],">> ",working_dir("Mount this path inside the container: "),style(active),focused && active),
+                WorkingDirMode::Copy => self.working_dir_path.render_on(f,chunks[9],">> ",working_dir("Copy this path inside the container: "),style(active),focused && active),
+                WorkingDirMode::DontUse => {
+                    let path = Paragraph::new(Span::raw(">> --/--")).block(working_dir("Neither mount nor copy working dir")).style(style(active));
+                    f.render_widget(path,chunks[9]);
+                },
+            }
 
+            f.render_widget(help,chunks[10]);
 
-            let help = Paragraph::new(Text::from("Ctrl+h - Show full help")).style(style_help)
-                                    .block(Block::default().borders(Borders::ALL).title("Help"))
-                                    .alignment(Alignment::Left);
-            f.render_widget(help,chunks[8]);
+            if let (CurrentPhase::SelectImage,None) = (&self.phase,&popup) {
+                self.render_suggestions(f,chunks[7]);
+            }
 
             if popup.is_some() {
                 popup.unwrap().render_on(f);
             }
         }).unwrap();
-        match self.phase {
-            CurrentPhase::SelectName => {
-                terminal.show_cursor().unwrap();
-                terminal.set_cursor(self.container_name.len() as u16+4, 2).unwrap();
-            },
-            CurrentPhase::AutoRemove => {
-                terminal.show_cursor().unwrap();
-                terminal.set_cursor(self.auto_remove.len() as u16+4,5).unwrap();
-            },
-            CurrentPhase::EntryCommand => {
-                terminal.show_cursor().unwrap();
-                terminal.set_cursor(self.entry_command.len() as u16+4,8).unwrap();
-            },
-            CurrentPhase::AddSSHKeys => {
-                terminal.show_cursor().unwrap();
-                terminal.set_cursor(self.import_keys.len() as u16+4,11).unwrap();
-            },
-            CurrentPhase::GitConfig => {
-                terminal.show_cursor().unwrap();
-                terminal.set_cursor(self.git_config.len() as u16+4,14).unwrap();
-            },
-            CurrentPhase::SelectImage => {
-                terminal.show_cursor().unwrap();
-                terminal.set_cursor(self.image_name.len() as u16+4,17).unwrap();
+    }
+
+    /// Switches to the next configured profile, wrapping around to no profile.
+    fn next_profile(&mut self) {
+        let mut names = self.config.profiles.keys();
+        self.profile = match &self.profile {
+            Some(current) => names.skip_while(|x| *x != current).nth(1).cloned(),
+            None => names.next().cloned(),
+        };
+    }
+
+    /// Pre-fills the form with the values of the selected profile.
+    fn apply_profile(&mut self) {
+        let profile = self.selected_profile();
+        self.image_name.set_value(profile.image.unwrap_or_else(|| self.config.image.clone()));
+        self.entry_command.set_value(profile.entry_command.unwrap_or_else(|| self.config.entry_command.clone()));
+    }
+
+    fn selected_profile(&self) -> Profile {
+        match &self.profile {
+            Some(x) => self.config.profiles[x].clone(),
+            None => Profile::default(),
+        }
+    }
+
+    /// The container described by the form.
+    fn setup(&self) -> ContainerSetup {
+        let profile = self.selected_profile();
+        ContainerSetup {
+            name: self.container_name.value().to_owned(),
+            image: self.image_name.value().to_owned(),
+            entry_command: self.entry_command.value().to_owned(),
+            auto_remove: self.auto_remove.value(),
+            import_keys: self.import_keys.value(),
+            git_config: self.git_config.value(),
+            working_dir: self.working_dir_setup(),
+            mount_target: self.mount_target.clone(),
+            env: profile.env,
+            mounts: profile.mounts,
+            ports: profile.ports,
+            profile: self.profile.clone(),
+        }
+    }
+
+    /// Whether the field of `phase` can be focused, the profile only exists with
+    /// configured profiles and the path only while the working dir is used.
+    fn is_available(&self, phase: CurrentPhase) -> bool {
+        match phase {
+            CurrentPhase::SelectProfile => !self.config.profiles.is_empty(),
+            CurrentPhase::WorkingDirectory => self.working_dir != WorkingDirMode::DontUse,
+            _ => true,
+        }
+    }
+
+    fn focus(&mut self, phase: CurrentPhase) {
+        self.phase = phase;
+        if phase == CurrentPhase::SelectImage {
+            self.update_suggestions();
+        }
+    }
+
+    /// Moves to the previous field, the review goes back to the last field.
+    fn previous(&mut self) {
+        let current = PHASES.iter().position(|x| *x == self.phase).unwrap_or(0);
+        if let Some(phase) = PHASES[..current].iter().rev().find(|x| self.is_available(**x)) {
+            self.focus(*phase);
+        }
+    }
+
+    /// Moves to the next field once the current one is valid, the last field
+    /// leads to the review. The image is checked in the background first.
+    fn next(&mut self, docker: &Docker, events: &Events) {
+        if !self.check_current() {
+            return;
+        }
+        let current = PHASES.iter().position(|x| *x == self.phase).unwrap_or(0);
+        let next = match PHASES[current+1..].iter().find(|x| self.is_available(**x)) {
+            Some(x) => *x,
+            None => return,
+        };
+        if self.phase == CurrentPhase::SelectImage && !self.is_image_checked() {
+            self.check_image(docker,events,next);
+            return;
+        }
+        self.advance(docker,events,next);
+    }
+
+    fn advance(&mut self, docker: &Docker, events: &Events, phase: CurrentPhase) {
+        if phase == CurrentPhase::Review {
+            self.review(docker,events);
+        }
+        else {
+            self.focus(phase);
+        }
+    }
+
+    /// Shows the review once all fields are valid, otherwise focuses the first
+    /// invalid field.
+    fn review(&mut self, docker: &Docker, events: &Events) {
+        if let Some(invalid) = VALIDATED.iter().find(|x| self.field_error(**x).is_some()) {
+            self.focus(*invalid);
+            self.check_current();
+            return;
+        }
+        // The image field may have been left with the mouse without checking it.
+        if !self.is_image_checked() {
+            self.focus(CurrentPhase::SelectImage);
+            self.check_current();
+            self.check_image(docker,events,CurrentPhase::Review);
+            return;
+        }
+        self.review_action = 0;
+        self.phase = CurrentPhase::Review;
+    }
+
+    /// Creates the container in the background, pulling the image first if it
+    /// is not available locally.
+    fn create(&self, docker: &Docker) {
+        let setup = self.setup();
+        let pull = !self.image_available;
+        let docker = docker.clone();
+        let target = self.mount_target.clone();
+        self.tasks.spawn(format!("Creating {}",setup.name),move |progress| async move {
+            if pull {
+                images::pull(&docker,&setup.image,|x| progress.set(x.lines().join("\n"),x.fraction())).await?;
+            }
+            progress.set(String::from("Creating the container"),None);
+            let id = container::create(&docker,&setup).await?;
+            if let WorkingDirectorySetup::CopyDirectory(x) = &setup.working_dir {
+                match copy_working_directory(&docker,&progress,&id,PathBuf::from(x),target).await {
+                    Ok(message) => return Ok(format!("Created {}, {}",setup.name,message)),
+                    Err(error) => {
+                        let _ = container::remove(&docker,&id).await;
+                        return Err(error);
+                    }
+                }
+            }
+            Ok(format!("Created {}",setup.name))
+        });
+    }
+
+    /// Runs a button of the review, returns the next screen if the form is left.
+    fn activate(&mut self, action: ReviewAction, docker: &Docker) -> Option<AppState> {
+        match action {
+            ReviewAction::Confirm => {
+                self.create(docker);
+                Some(AppState::Search)
             },
-            CurrentPhase::WorkingDirectory => {
-                terminal.show_cursor().unwrap();
-                let len = match &self.working_dir {WorkingDirectorySetup::MountDirectory(x)=>x.len(),WorkingDirectorySetup::CopyDirectory(x)=>x.len(),_ => 5};
-                terminal.set_cursor(len as u16+4,20).unwrap();
+            ReviewAction::Back => {
+                self.previous();
+                None
             },
+            ReviewAction::Cancel => Some(AppState::Search),
         }
     }
 
-    pub async fn event_loop<B: Backend>(&mut self, term: &mut Terminal<B>,docker: &Docker) -> AppState {
-        let stdin = io::stdin();
+    /// Focuses the field or runs the review button at the clicked position.
+    fn click(&mut self, x: u16, y: u16, docker: &Docker) -> Option<AppState> {
+        // Mouse positions start at 1.
+        let contains = |area: &Rect| x > area.x && x <= area.x+area.width && y > area.y && y <= area.y+area.height;
+        if self.phase == CurrentPhase::Review {
+            let action = self.button_areas.iter().find(|x| contains(&x.1)).map(|x| x.0)?;
+            return self.activate(action,docker);
+        }
+        if let Some(phase) = self.field_areas.iter().find(|x| contains(&x.1) && self.is_available(x.0)).map(|x| x.0) {
+            self.focus(phase);
+        }
+        None
+    }
+
+    pub async fn event_loop<B: Backend>(&mut self, term: &mut Terminal<B>,docker: &Docker, events: &mut Events) -> AppState {
+        print!("{}",input::ENABLE_MOUSE);
+        let state = self.run(term,docker,events).await;
+        print!("{}",input::DISABLE_MOUSE);
+        state
+    }
 
+    async fn run<B: Backend>(&mut self, term: &mut Terminal<B>,docker: &Docker, events: &mut Events) -> AppState {
+        self.local_images = images::list(docker).await.map(|x| x.into_iter().filter(ImageEntry::is_tagged).collect()).unwrap_or_default();
         self.render(term,None);
-        for evt in stdin.keys() {
-            match evt {
-                Ok(Key::Char(r)) => {
-                    match self.phase {
-                        CurrentPhase::SelectName => {
-                            if r == '\n' || r == '\t' {
-                                if self.container_name == "" {
-                                    popup::AppPopupContext::new("The controller name cannot be empty!".to_owned()).style(Style::default().fg(Color::LightRed)).event_render_loop(|popup| self.render(term,Some(popup)));
-                                }
-                                else {
-                                    self.phase = CurrentPhase::AutoRemove;
-                                }
-                            }
-                            else {
-                                self.container_name.push(r);
-                            }
-                        },
-                        CurrentPhase::AutoRemove => {
-                            if r == '\n' || r == '\t'{
-                                self.phase = CurrentPhase::EntryCommand;
-                            }
-                            else {
-                                self.auto_remove.push(r);
-                            }
-                        },
-                        CurrentPhase::EntryCommand => {
-                            if r == '\n' || r == '\t'{
-                                self.phase = CurrentPhase::AddSSHKeys;
-                            }
-                            else {
-                                self.entry_command.push(r);
-                            }
-                        },
-                        CurrentPhase::AddSSHKeys => {
-                            if r == '\n' || r == '\t'{
-                                self.phase = CurrentPhase::GitConfig;
-                            }
-                            else {
-                                self.import_keys.push(r);
-                            }
-                        },
-                        CurrentPhase::GitConfig => {
-                            if r == '\n' || r == '\t'{
-                                self.phase = CurrentPhase::SelectImage;
-                            }
-                            else {
-                                self.git_config.push(r);
-                            }
-                        },
-                        CurrentPhase::SelectImage => {
-                            if r == '\n' || r == '\t'{
-                                self.phase = CurrentPhase::WorkingDirectory;
-                            }
-                            else {
-                                self.image_name.push(r);
-                            }
-                        },
-                        CurrentPhase::WorkingDirectory => {
-                            if r == '\n' {
-                                //TODO: Write the copy code so that one can copy the current
-                                //working directory
-                                let timezone = format!("TZ={}",std::fs::read_to_string("/etc/timezone").unwrap().trim());
-                                let container_name = String::from("dde_")+&self.container_name;
-                                let mut volumes:Vec<String> = match &self.working_dir {
-                                    WorkingDirectorySetup::MountDirectory(x) => {
-                                        let outer_string=x.clone()+":/root/mounted:Z";
-                                        vec![outer_string]
-                                    },
-                                    WorkingDirectorySetup::CopyDirectory(_) => {Vec::new()},
-                                    _ => {Vec::new()}
-                                };
-                                if self.import_keys == "yes" {
-                                    let home_dir = home::home_dir().unwrap().join(".ssh").to_str().unwrap().to_string();
-                                    volumes.push(home_dir+":/root/.ssh:Z");
-                                }
-                                if self.git_config == "yes" {
-                                    let home_dir = home::home_dir().unwrap().join(".gitconfig").to_str().unwrap().to_string();
-                                    volumes.push(home_dir+":/root/.gitconfig:Z");
-                                }
-                                let opts = ContainerOptions::builder(&self.image_name).auto_remove(self.auto_remove != "no").name(&container_name).cmd(self.entry_command.split(" ").collect()).tty(true).env(vec![&timezone]).attach_stdin(true).attach_stderr(true).attach_stdout(true).volumes(volumes.iter().map(|x| &x[..]).collect()).build();
-
-                                let _ = docker.containers().create(&opts).await.unwrap();
-
-                                return AppState::Search;
-                            }
-                            if r == '\t' {
-                                match &self.working_dir {
-                                    WorkingDirectorySetup::MountDirectory(x) => {self.working_dir = WorkingDirectorySetup::CopyDirectory(x.clone());}
-                                    WorkingDirectorySetup::CopyDirectory(_) => {self.working_dir = WorkingDirectorySetup::DontUse;}
-                                    _ => {self.working_dir = WorkingDirectorySetup::MountDirectory(std::env::current_dir().unwrap().to_str().unwrap().to_string());}
-                                }
-                            }
-                        }
+        loop {
+            let key = match events.next().await {
+                Event::Key(key) => key,
+                Event::Mouse(MouseEvent::Press(MouseButton::Left,x,y)) => {
+                    if let Some(state) = self.click(x,y,docker) {
+                        return state;
                     }
+                    self.render(term,None);
+                    continue;
                 },
-                Ok(Key::Backspace) => {
-                    match self.phase {
-                        CurrentPhase::SelectName=> {
-                            self.container_name.pop();
-                        },
-                        CurrentPhase::AutoRemove => {
-                            self.auto_remove.pop();
+                Event::Paste(text) => {
+                    if let Some(input) = self.input() {
+                        input.insert_str(&text);
+                        self.edited();
+                    }
+                    self.render(term,None);
+                    continue;
+                },
+                Event::ImageChecked(image,result) => {
+                    self.image_checked(docker,events,image,result);
+                    self.render(term,None);
+                    continue;
+                },
+                Event::InputClosed => return AppState::Exiting,
+                _ => continue,
+            };
+            if self.edit(key) {
+                self.edited();
+                self.render(term,None);
+                continue;
+            }
+            match key {
+                Key::Ctrl('c') => {
+                    return AppState::Exiting;
+                }
+                Key::Ctrl('h') => {
+                    return AppState::Help;
+                },
+                _ if self.phase == CurrentPhase::Review => {
+                    let action = match key {
+                        Key::Char('\n') => Some(REVIEW_ACTIONS[self.review_action]),
+                        Key::Char('c') => Some(ReviewAction::Confirm),
+                        Key::Char('b') | Key::Up | Key::BackTab => Some(ReviewAction::Back),
+                        Key::Char('q') | Key::Esc => Some(ReviewAction::Cancel),
+                        Key::Left => {
+                            self.review_action = (self.review_action+REVIEW_ACTIONS.len()-1)%REVIEW_ACTIONS.len();
+                            None
                         },
-                        CurrentPhase::EntryCommand => {
-                            self.entry_command.pop();
+                        Key::Right | Key::Char('\t') => {
+                            self.review_action = (self.review_action+1)%REVIEW_ACTIONS.len();
+                            None
                         },
-                        CurrentPhase::AddSSHKeys => {
-                            self.import_keys.pop();
+                        Key::Char('p') => {
+                            self.preview_format = self.preview_format.next();
+                            None
                         },
-                        CurrentPhase::GitConfig => {
-                            self.git_config.pop();
+                        Key::Char('w') => {
+                            let preview = self.preview();
+                            let default = self.preview_format.default_path(&self.setup().container_name());
+                            run_command::save(&preview,default,events,|popup| self.render(term,Some(popup))).await;
+                            None
                         },
-                        CurrentPhase::SelectImage => {
-                            self.image_name.pop();
+                        Key::Char('y') => {
+                            let preview = self.preview();
+                            run_command::copy(&preview,events,|popup| self.render(term,Some(popup))).await;
+                            None
                         },
-                        CurrentPhase::WorkingDirectory => {
-                            match &mut self.working_dir {
-                                WorkingDirectorySetup::MountDirectory(x) => {x.pop();},
-                                WorkingDirectorySetup::CopyDirectory(x) => {x.pop();},
-                                _ => {}    
-                            };
-                        }
+                        _ => None,
+                    };
+                    if let Some(state) = action.and_then(|x| self.activate(x,docker)) {
+                        return state;
                     }
                 },
-                Ok(Key::Ctrl('c')) => {
-                    return AppState::Exiting;
-                }
-                Ok(Key::Ctrl('h')) => {
-                    return AppState::Help;
+                Key::Ctrl('p') | Key::Ctrl('n') if self.phase == CurrentPhase::SelectImage && !self.suggestions.is_empty() => {
+                    let count = self.suggestions.len();
+                    self.selected_suggestion = if key == Key::Ctrl('p') { (self.selected_suggestion+count-1)%count } else { (self.selected_suggestion+1)%count };
+                },
+                Key::Char('\t') if self.phase == CurrentPhase::SelectImage && self.suggestion().is_some() => {
+                    let suggestion = self.suggestion().map(String::from).unwrap_or_default();
+                    self.image_name.set_value(suggestion);
+                    self.edited();
+                },
+                Key::Char('\n') | Key::Char('\t') | Key::Down => self.next(docker,events),
+                Key::BackTab | Key::Up => self.previous(),
+                Key::Char(' ') if self.phase == CurrentPhase::SelectProfile => {
+                    self.next_profile();
+                    self.apply_profile();
                 },
-                Ok(Key::Esc) => {
-                    return AppState::Search;
+                Key::Char(' ') if self.phase == CurrentPhase::SelectWorkingDir => {
+                    self.working_dir = match self.working_dir {
+                        WorkingDirMode::Mount => WorkingDirMode::Copy,
+                        WorkingDirMode::Copy => WorkingDirMode::DontUse,
+                        WorkingDirMode::DontUse => WorkingDirMode::Mount,
+                    };
+                },
+                Key::Esc => {
+                    let message = String::from("Discard the new container (y) - yes, (n) - no?");
+                    if popup::AppPopupContext::decision(message).event_render_loop(events,|popup| self.render(term,Some(popup))).await == "yes" {
+                        return AppState::Search;
+                    }
                 },
                 _ => {}
             }
             self.render(term,None);
         }
-        return AppState::Exiting;
     }
 }
@@ -1,20 +1,21 @@
-use std::io;
 use tui::{
     backend::Backend,
     layout::{Constraint, Direction, Layout,Rect,Alignment},
     style::Style,
-    text::Span,
+    text::Text,
     widgets::{Block, Borders, Paragraph,Clear,Wrap,BorderType},
     Terminal,
     terminal::Frame,
 };
-use termion::input::TermRead;
 use termion::event::Key;
+use crate::ui::events::{Event,Events};
+use crate::ui::text_input::TextInput;
  
 
 enum PopupTask {
     YesNoDecision,
     Information,
+    TextInput(TextInput),
 }
 
 pub struct AppPopupContext {
@@ -40,20 +41,28 @@ impl AppPopupContext {
         }
     }
 
+    pub fn input(message: String, default: String) -> AppPopupContext {
+        AppPopupContext {
+            message,
+            style: Style::default(),
+            task: PopupTask::TextInput(TextInput::new(default)),
+        }
+    }
+
     pub fn style(mut self, style: Style) -> AppPopupContext {
         self.style = style;
         self
     }
 
     /// helper function to create a centered rect using up
-    /// certain percentage of the available rect `r`
-    fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+    /// certain percentage of the available width and `height` lines of `r`
+    fn centered_rect(percent_x: u16, height: u16, r: Rect) -> Rect {
         let popup_layout = Layout::default()
             .direction(Direction::Vertical)
             .constraints(
                 [
-                Constraint::Percentage((100 - percent_y) / 2),
-                Constraint::Length(3),
+                Constraint::Length(r.height.saturating_sub(height) / 2),
+                Constraint::Length(height),
                 Constraint::Min(0),
                 ]
                 .as_ref(),
@@ -73,6 +82,12 @@ impl AppPopupContext {
             .split(popup_layout[1])[1]
     }
 
+    /// Number of lines `text` occupies when wrapped to `width` columns.
+    fn wrapped_lines(text: &str, width: u16) -> u16 {
+        let width = std::cmp::max(width,1) as usize;
+        text.split('\n').map(|line| std::cmp::max(1,line.chars().count().div_ceil(width)) as u16).sum()
+    }
+
     pub fn render<B: Backend>(&mut self,terminal: &mut Terminal<B>) {
         terminal.draw(|mut f| {
             self.render_on(&mut f);
@@ -80,46 +95,111 @@ impl AppPopupContext {
     }
 
     pub fn render_on<B: Backend>(&mut self, f: &mut Frame<B>) {
-        let popup_layout = AppPopupContext::centered_rect(80, 20, f.size());
-        let block = Paragraph::new(Span::raw(&self.message)).block(Block::default().title(" Popup message ").borders(Borders::ALL).style(self.style).border_type(BorderType::Double)).style(self.style).wrap(Wrap { trim: true }).alignment(Alignment::Center);
-        let bigger_rect = Rect::new(popup_layout.x-1,popup_layout.y-1,popup_layout.width+2,popup_layout.height+2);
+        let size = f.size();
+        // A text input takes the last line of the popup.
+        let input_lines = if let PopupTask::TextInput(_) = self.task { 1 } else { 0 };
+        let height = std::cmp::min(AppPopupContext::wrapped_lines(&self.message,(u32::from(size.width)*80/100).saturating_sub(2) as u16).saturating_add(input_lines+2),size.height.saturating_sub(2));
+        let popup_layout = AppPopupContext::centered_rect(80, height, size);
+        let outer = Block::default().title(" Popup message ").borders(Borders::ALL).style(self.style).border_type(BorderType::Double);
+        let inner = outer.inner(popup_layout);
+        let message_area = Rect::new(inner.x,inner.y,inner.width,inner.height.saturating_sub(input_lines));
+        let block = Paragraph::new(Text::from(self.message.clone())).style(self.style).wrap(Wrap { trim: true }).alignment(Alignment::Center);
+        let bigger_rect = Rect::new(popup_layout.x.saturating_sub(1),popup_layout.y.saturating_sub(1),popup_layout.width+2,popup_layout.height+2).intersection(size);
         f.render_widget(Clear, bigger_rect);
-        f.render_widget(block, popup_layout);
+        f.render_widget(outer, popup_layout);
+        f.render_widget(block, message_area);
+        if let PopupTask::TextInput(input) = &mut self.task {
+            let input_area = Rect::new(inner.x,inner.y+message_area.height,inner.width,std::cmp::min(1,inner.height));
+            input.render_on(f,input_area,">> ",Block::default(),self.style,true);
+        }
+    }
+
+    /// Shows the popup until it is answered, `render_func` draws the calling
+    /// screen with the popup on top. Events other than input are handed back to
+    /// the calling screen afterwards.
+    pub async fn event_render_loop<F: FnMut(&mut AppPopupContext)>(&mut self, events: &mut Events, render_func: F) -> String {
+        let mut held = Vec::new();
+        let answer = self.answer(events,render_func,&mut held).await;
+        events.requeue(held);
+        answer
     }
 
-    pub fn event_render_loop<F: FnMut(&mut AppPopupContext)>(&mut self, mut render_func: F) -> String {
-        let stdin = io::stdin();
+    async fn answer<F: FnMut(&mut AppPopupContext)>(&mut self, events: &mut Events, mut render_func: F, held: &mut Vec<Event>) -> String {
         render_func(self);
-        for evt in stdin.keys() {
+        loop {
+            let key = match events.next().await {
+                Event::Key(key) => key,
+                Event::Paste(text) => {
+                    if let PopupTask::TextInput(input) = &mut self.task {
+                        input.insert_str(&text);
+                    }
+                    render_func(self);
+                    continue;
+                },
+                Event::InputClosed => {
+                    held.push(Event::InputClosed);
+                    return String::new();
+                },
+                // Clicks were meant for the popup, not for the screen below.
+                Event::Tick | Event::Mouse(_) => {
+                    render_func(self);
+                    continue;
+                },
+                event => {
+                    held.push(event);
+                    render_func(self);
+                    continue;
+                },
+            };
             match self.task {
                 PopupTask::Information => {
-                    match evt {
-                        Ok(_) => {
-                            return String::new();
-                        },
-                        _ => {}
-                    }
+                    return String::new();
                 },
                 PopupTask::YesNoDecision => {
-                    match evt {
-                        Ok(Key::Char(r)) => {
-                            if r == 'y' || r == 'Y' {
-                                return String::from("yes");
-                            }
-                            else if r == 'n' || r == 'N' {
-                                return String::from("no");
-                            }
+                    if let Key::Char(r) = key {
+                        if r == 'y' || r == 'Y' {
+                            return String::from("yes");
+                        }
+                        else if r == 'n' || r == 'N' {
+                            return String::from("no");
+                        }
+                    }
+                },
+                PopupTask::TextInput(ref mut input) => {
+                    match key {
+                        Key::Char('\n') => {
+                            return input.value().to_owned();
+                        },
+                        Key::Esc => {
+                            return String::new();
+                        },
+                        key => {
+                            input.handle_key(key);
                         },
-                        _ => {}
                     }
                 },
             }
             render_func(self);
         }
-        String::new()
     }
 
-    pub fn event_loop<B: Backend>(&mut self, term: &mut Terminal<B>) -> String {
-        self.event_render_loop(|app| app.render(term))
+    pub async fn event_loop<B: Backend>(&mut self, term: &mut Terminal<B>, events: &mut Events) -> String {
+        self.event_render_loop(events,|app| app.render(term)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tui::backend::TestBackend;
+
+    #[test]
+    fn renders_on_tiny_terminals() {
+        for (width,height) in [(0,0),(1,1),(2,3),(3,2),(5,5),(80,24)].iter() {
+            let mut terminal = Terminal::new(TestBackend::new(*width,*height)).unwrap();
+            for mut popup in [AppPopupContext::new(String::from("A message")),AppPopupContext::decision(String::from("Sure?")),AppPopupContext::input(String::from("Name:"),String::from("x"))] {
+                terminal.draw(|f| popup.render_on(f)).unwrap();
+            }
+        }
     }
 }
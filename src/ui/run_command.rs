@@ -0,0 +1,193 @@
+use std::io::Write;
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout,Alignment},
+    style::{Color, Modifier, Style},
+    text::{Span,Text,Spans},
+    widgets::{Block, Borders, Paragraph},
+    Terminal,
+};
+use termion::event::Key;
+use crate::ui::AppState;
+use crate::ui::popup::AppPopupContext;
+use crate::ui::events::{Event,Events};
+use crate::run_command::RunCommand;
+use crate::VERSION;
+
+/// How a container is shown, as `docker run` command line or compose service.
+#[derive(Clone,Copy,PartialEq)]
+pub enum Format {
+    Run,
+    Compose,
+}
+
+impl Format {
+    pub fn next(self) -> Format {
+        match self {
+            Format::Run => Format::Compose,
+            Format::Compose => Format::Run,
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Format::Run => "docker run",
+            Format::Compose => "docker-compose.yml",
+        }
+    }
+
+    pub fn text(self, command: &RunCommand) -> String {
+        match self {
+            Format::Run => command.docker_run(),
+            Format::Compose => command.compose(),
+        }
+    }
+
+    /// The file the text is saved to unless the user picks another one.
+    pub fn default_path(self, name: &str) -> String {
+        let dir = std::env::current_dir().unwrap_or_default();
+        let file = match self {
+            Format::Run => format!("{}.sh",name),
+            Format::Compose => String::from("docker-compose.yml"),
+        };
+        dir.join(file).to_string_lossy().to_string()
+    }
+}
+
+/// Hands `text` to the clipboard of the terminal with an OSC 52 sequence.
+pub fn copy_to_clipboard(text: &str) -> Result<(),String> {
+    let mut stdout = std::io::stdout();
+    write!(stdout,"\x1b]52;c;{}\x07",base64::encode(text)).and_then(|_| stdout.flush()).map_err(|e| format!("Could not write to the terminal: {}",e))
+}
+
+/// Asks for a file and saves `text` in it, `render` draws the calling screen
+/// with the given popup on top.
+pub async fn save<F: FnMut(&mut AppPopupContext)>(text: &str, default: String, events: &mut Events, mut render: F) {
+    let path = AppPopupContext::input(String::from("Save to file:"),default).event_render_loop(events,|popup| render(popup)).await;
+    if path.is_empty() || !confirm_overwrite(&path,events,|popup| render(popup)).await {
+        return;
+    }
+    let mut popup = match std::fs::write(&path,format!("{}\n",text)) {
+        Ok(_) => AppPopupContext::new(format!("Saved to \"{}\".",path)),
+        Err(e) => AppPopupContext::new(format!("Could not write \"{}\": {}",path,e)).style(Style::default().fg(Color::LightRed)),
+    };
+    popup.event_render_loop(events,|popup| render(popup)).await;
+}
+
+/// Asks before replacing the existing file `path`, true if it may be written.
+pub async fn confirm_overwrite<F: FnMut(&mut AppPopupContext)>(path: &str, events: &mut Events, mut render: F) -> bool {
+    if !std::path::Path::new(path).exists() {
+        return true;
+    }
+    let message = format!("\"{}\" already exists. Overwrite it (y) - yes, (n) - no?",path);
+    AppPopupContext::decision(message).style(Style::default().fg(Color::LightRed)).event_render_loop(events,|popup| render(popup)).await == "yes"
+}
+
+/// Copies `text` to the clipboard and tells whether that worked.
+pub async fn copy<F: FnMut(&mut AppPopupContext)>(text: &str, events: &mut Events, mut render: F) {
+    let mut popup = match copy_to_clipboard(text) {
+        Ok(_) => AppPopupContext::new(String::from("Copied to the clipboard, the terminal has to support OSC 52.")),
+        Err(error) => AppPopupContext::new(error).style(Style::default().fg(Color::LightRed)),
+    };
+    popup.event_render_loop(events,|popup| render(popup)).await;
+}
+
+pub struct AppRunCommandContext {
+    id: String,
+    command: Option<RunCommand>,
+    format: Format,
+    scroll: u16,
+}
+
+impl AppRunCommandContext {
+    pub fn new(id: String) -> AppRunCommandContext {
+        AppRunCommandContext {
+            id,
+            command: None,
+            format: Format::Run,
+            scroll: 0,
+        }
+    }
+
+    fn text(&self) -> String {
+        self.command.as_ref().map(|x| self.format.text(x)).unwrap_or_default()
+    }
+
+    pub fn render<B: Backend>(&mut self,terminal: &mut Terminal<B>, popup: Option<&mut AppPopupContext>) {
+        terminal.draw(|f| {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1),Constraint::Min(3),Constraint::Length(3)].as_ref())
+                .split(f.size());
+
+            let mut style_text = Style::default().fg(Color::White);
+            let mut style_non = Style::default().fg(Color::Blue);
+            let mut style_help = Style::default().fg(Color::Cyan);
+            let mut header_style = Style::default().fg(Color::LightRed);
+            let mut header_other_style = Style::default().bg(Color::Black).fg(Color::LightYellow);
+
+            if popup.is_some() {
+                let style_dim = Style::default().fg(Color::Gray).add_modifier(Modifier::DIM);
+                style_text = style_dim;
+                style_non = style_dim;
+                style_help = style_dim;
+                header_style = style_dim;
+                header_other_style = style_dim;
+            }
+
+            let header_paragraph = Paragraph::new(Spans::from(vec![Span::styled(VERSION,header_style),Span::raw(" | Recreate the container")]))
+                .style(header_other_style)
+                .alignment(Alignment::Center);
+            f.render_widget(header_paragraph,chunks[0]);
+
+            let title = format!("{} - {}",self.command.as_ref().map(|x| x.name.as_str()).unwrap_or(&self.id),self.format.title());
+            let text = Paragraph::new(Text::from(self.text())).style(style_text)
+                .block(Block::default().borders(Borders::ALL).title(title).style(style_non))
+                .scroll((self.scroll,0));
+            f.render_widget(text,chunks[1]);
+
+            let help = Paragraph::new(Text::from("p - Switch between docker run and compose; w - Save to a file; y - Copy to the clipboard; back - q or <esc>")).style(style_help)
+                                    .block(Block::default().borders(Borders::ALL).title("Help"))
+                                    .alignment(Alignment::Left);
+            f.render_widget(help,chunks[2]);
+            if let Some(popup) = popup {
+                popup.render_on(f);
+            }
+        }).unwrap();
+    }
+
+    pub async fn event_loop<B: Backend>(&mut self, term: &mut Terminal<B>, events: &mut Events) -> AppState {
+        match RunCommand::load(&self.id).await {
+            Ok(x) => self.command = Some(x),
+            Err(error) => {
+                AppPopupContext::new(error).style(Style::default().fg(Color::LightRed)).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+                return AppState::Search;
+            }
+        }
+        self.render(term,None);
+        loop {
+            match events.next().await {
+                Event::Key(Key::Char('q')) | Event::Key(Key::Esc) => return AppState::Search,
+                Event::Key(Key::Ctrl('c')) | Event::InputClosed => return AppState::Exiting,
+                Event::Key(Key::Ctrl('h')) => return AppState::Help,
+                Event::Key(Key::Char('p')) => {
+                    self.format = self.format.next();
+                    self.scroll = 0;
+                },
+                Event::Key(Key::Char('w')) => {
+                    let text = self.text();
+                    let default = self.format.default_path(self.command.as_ref().map(|x| x.name.as_str()).unwrap_or(&self.id));
+                    save(&text,default,events,|popup| self.render(term, Some(popup))).await;
+                },
+                Event::Key(Key::Char('y')) => {
+                    let text = self.text();
+                    copy(&text,events,|popup| self.render(term, Some(popup))).await;
+                },
+                Event::Key(Key::Up) => self.scroll = self.scroll.saturating_sub(1),
+                Event::Key(Key::Down) => self.scroll = std::cmp::min(self.scroll+1,self.text().lines().count() as u16),
+                _ => {},
+            }
+            self.render(term,None);
+        }
+    }
+}
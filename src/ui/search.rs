@@ -1,43 +1,95 @@
-use shiplift::{Docker,ContainerListOptions,rep,RmContainerOptions};
-use std::io;
+use shiplift::{Docker,rep};
 use tui::{
     backend::Backend,
-    layout::{Constraint, Direction, Layout,Alignment},
+    layout::{Constraint, Direction, Layout,Alignment,Rect},
     style::{Color, Modifier, Style},
     text::{Span,Text,Spans},
-    widgets::{Block, Borders, Paragraph,List, ListItem,ListState},
+    widgets::{Block, Borders, Paragraph,Row,Cell,Table,TableState,Wrap},
     Terminal,
 };
 use termion::event::Key;
-use termion::input::TermRead;
+use crate::ui::events::{Event,Events};
+use crate::ui::tasks::Tasks;
 use crate::ui::AppState;
 use crate::ui::popup::AppPopupContext;
-use std::process::Command;
+use crate::ui::export;
+use crate::ui::details::ContainerDetails;
+use crate::ui::table::{Column,TableSettings};
+use crate::ui::text_input::TextInput;
+use std::path::Path;
 use termion::screen::{ToAlternateScreen,ToMainScreen};
 use crate::VERSION;
+use crate::input;
+use crate::container::{self,ExecSetup};
+use crate::config::Config;
+use crate::query::{self,Query};
+use crate::stats::{self,Sample};
+use std::collections::{HashMap,HashSet};
+use futures::future::join_all;
+use std::time::{Duration,Instant};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool,Ordering};
+
+/// Minimum time between two samples of the resource usage columns.
+const STATS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Splits `text` into spans with the characters at `positions` highlighted.
+pub(crate) fn highlighted(text: &str, positions: &[usize], style: Style) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut current = String::new();
+    let mut current_matched = false;
+    for (idx,c) in text.chars().enumerate() {
+        let matched = positions.contains(&idx);
+        if matched != current_matched && !current.is_empty() {
+            let part = std::mem::take(&mut current);
+            spans.push(if current_matched { Span::styled(part,style) } else { Span::raw(part) });
+        }
+        current_matched = matched;
+        current.push(c);
+    }
+    spans.push(if current_matched { Span::styled(current,style) } else { Span::raw(current) });
+    spans
+}
 
 struct ContainerList {
     containers: Vec<rep::Container>,
     filtered_list: Vec<usize>,
-    selected_state: ListState,
+    selected_state: TableState,
+    settings: TableSettings,
+    stats: HashMap<String,Sample>,
+    show_all: bool,
+    foreign: HashSet<String>,
+    smart_case: bool,
+    query_error: Option<String>,
+    /// Positions of the characters of the names matched by the search.
+    highlights: HashMap<usize,Vec<usize>>,
+    /// Index of the first visible entry, only visible entries are rendered.
+    offset: usize,
+    height: usize,
 }
 
 impl ContainerList {
-    pub async fn update(&mut self, docker: &Docker) {
-        let val = docker.containers();
-        let opts = ContainerListOptions::builder().all().build();
-        self.containers = val.list(&opts).await.unwrap();
-        self.containers.retain(|x| {
-            for name in x.names.iter() {
-                if name.starts_with("/") {
-                    return true;
-                }
-            }
-            false 
-        });
+    pub async fn update(&mut self, docker: &Docker) -> Result<(),String> {
+        self.containers = container::list(docker,self.show_all).await?;
+        let adopted = container::adopted();
+        self.foreign = self.containers.iter().filter(|x| !container::is_managed(x,&adopted)).map(|x| x.id.clone()).collect();
+        let running = self.running();
+        self.stats.retain(|id,_| running.contains(id));
 
         self.filtered_list = (0..self.containers.len()).collect();
         self.selected_state.select(Some(0));
+        Ok(())
+    }
+
+    fn running(&self) -> Vec<String> {
+        self.containers.iter().filter(|x| x.state == "running").map(|x| x.id.clone()).collect()
+    }
+
+    /// Selects the container `id` if it is still listed.
+    pub fn select_container(&mut self, id: &str) {
+        if let Some(idx) = self.filtered_list.iter().position(|x| self.containers[*x].id == id) {
+            self.selected_state.select(Some(idx));
+        }
     }
 
     pub fn select_next(&mut self) {
@@ -60,13 +112,38 @@ impl ContainerList {
         }
     }
 
+    /// Filters the containers by the query `search`, best matches of its free text
+    /// first or else in the order of the sort column. An invalid query keeps the previous result and is reported in
+    /// `query_error`.
     pub fn update_filtered_list(&mut self, search: &str) {
-        self.filtered_list = self.containers.iter().enumerate().filter_map(|(idx,y)| if y.names[0].find(search).is_some() {
-            Some(idx)
-        } 
-        else { 
-            None 
-        }).collect();
+        let query = match Query::parse(search) {
+            Ok(x) => x,
+            Err(error) => {
+                self.query_error = Some(error);
+                return;
+            }
+        };
+        self.query_error = None;
+        self.highlights.clear();
+        let mut scored = Vec::new();
+        for (idx,container) in self.containers.iter().enumerate() {
+            if let Some(matched) = query.matches(container,self.smart_case) {
+                scored.push((idx,matched.score));
+                self.highlights.insert(idx,matched.positions);
+            }
+        }
+        if query.has_text() {
+            scored.sort_by_key(|x| std::cmp::Reverse(x.1));
+        }
+        else {
+            let (containers,stats,sort) = (&self.containers,&self.stats,self.settings.sort);
+            scored.sort_by(|a,b| {
+                let (a,b) = (&containers[a.0],&containers[b.0]);
+                let order = sort.compare(a,b,stats.get(&a.id),stats.get(&b.id));
+                if self.settings.descending { order.reverse() } else { order }
+            });
+        }
+        self.filtered_list = scored.into_iter().map(|(idx,_)| idx).collect();
         self.selected_state.select(Some(0));
     }
 
@@ -79,19 +156,19 @@ impl ContainerList {
         self.containers.len()
     }
 
+    pub fn selected_container(&self) -> Option<&rep::Container> {
+        let selected = self.selected_state.selected()?;
+        let idx = self.filtered_list.get(selected)?;
+        self.containers.get(*idx)
+    }
+
+    /// The quick select number of the entry at `idx`, zero padded to the width of
+    /// the largest number so that typing all digits always selects one entry. The
+    /// width follows all containers, not the filtered ones, so that it stays the
+    /// same while the search changes.
     pub fn get_expanded_string(&self, idx: usize) -> String {
-        if self.filtered_list.len() < 9 {
-            return idx.to_string();
-        }
-        else if self.filtered_list.len() < 98 {
-            if idx < 10 {
-                return String::from("0")+&idx.to_string();
-            }
-            else {
-                return idx.to_string();
-            }
-        }
-        panic!("At the moment only up to 100 container supported!");
+        let width = self.containers.len().to_string().len();
+        format!("{:0width$}",idx,width = width)
     }
 
     pub fn get_selected_by_string(&self, selection: &str) -> Vec<usize> {
@@ -104,34 +181,92 @@ impl ContainerList {
         results 
     }
 
-    pub fn as_gui_element<'a>(&'a mut self, _search: &str, selection: &'a str) -> (Vec<ListItem>,&'a mut ListState) {
-        let mut item_list = self.filtered_list.iter().enumerate().map(|(idx,x)| { 
-            let result = self.get_expanded_string(idx);
-            let status = if self.containers[*x].state == "running" {
-                Span::styled("[RUNNING]",Style::default().fg(Color::Green))
-            }
-            else {
-                Span::styled("[STOPPED]",Style::default().fg(Color::Red))
-            };
-            let index = if result.starts_with(selection) {
-                vec![Span::styled(selection, Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD)),Span::raw(format!("{}. ",&result[selection.len()..])),Span::raw(self.containers[*x].names[0].clone()),Span::raw(" - "),status]
+    /// Moves the selection by `lines` entries without wrapping around.
+    pub fn select_by(&mut self, lines: isize) {
+        let selected = self.selected_state.selected().unwrap_or(0) as isize;
+        let selected = std::cmp::min(std::cmp::max(selected+lines,0) as usize,self.filtered_list.len());
+        self.selected_state.select(Some(selected));
+    }
+
+    fn entry(&self, idx: usize, selection: &str) -> Row<'static> {
+        let result = self.get_expanded_string(idx);
+        let index = if let Some(rest) = result.strip_prefix(selection) {
+            vec![Span::styled(selection.to_owned(), Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD)),Span::raw(format!("{}.",rest))]
+        }
+        else {
+            vec![Span::raw(format!("{}.",result))]
+        };
+        let mut cells = vec![Cell::from(Spans::from(index))];
+        let container = match self.filtered_list.get(idx) {
+            Some(x) => &self.containers[*x],
+            None => {
+                cells.push(Cell::from("Create new container development environment"));
+                return Row::new(cells);
             }
-            else {
-                vec![Span::raw(format!("{}. ",result)),Span::raw(self.containers[*x].names[0].clone()),Span::raw(" - "),status]
+        };
+        for column in self.settings.columns.iter() {
+            let value = column.value(container,self.stats.get(&container.id));
+            let cell = match column {
+                Column::Name => {
+                    let highlight = Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD);
+                    let mut name = highlighted(&value,self.highlights.get(&self.filtered_list[idx]).map(|x| &x[..]).unwrap_or(&[]),highlight);
+                    if self.foreign.contains(&container.id) {
+                        name.push(Span::styled(" [not managed]",Style::default().fg(Color::Gray)));
+                    }
+                    Cell::from(Spans::from(name))
+                },
+                Column::Status if container.state == "running" => Cell::from(Span::styled(value,Style::default().fg(Color::Green))),
+                Column::Status => Cell::from(Span::styled(value,Style::default().fg(Color::Red))),
+                _ => Cell::from(value),
             };
-            ListItem::new(Spans::from(index))
-            }).collect::<Vec<ListItem>>();
+            cells.push(cell);
+        }
+        Row::new(cells)
+    }
 
-            let idx = self.get_expanded_string(self.filtered_list.len());
-            let index = if idx.starts_with(selection) {
-                vec![Span::styled(selection, Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD)),Span::raw(format!("{}. ",&idx[selection.len()..])),Span::raw("Create new container development environment")]
+    /// The titles of the columns, marking the sort column with its direction.
+    pub fn header(&self) -> Row<'static> {
+        let mut titles = vec![String::new()];
+        titles.extend(self.settings.columns.iter().map(|x| {
+            match (*x == self.settings.sort,self.settings.descending) {
+                (true,false) => format!("{} ▲",x.title()),
+                (true,true) => format!("{} ▼",x.title()),
+                (false,_) => x.title().to_owned(),
             }
-            else {
-                vec![Span::raw(format!("{}. ",idx)),Span::raw("Create new container development environment")]
-            };
+        }));
+        Row::new(titles)
+    }
+
+    pub fn widths(&self) -> Vec<Constraint> {
+        let mut widths = vec![Constraint::Length(self.get_expanded_string(0).len() as u16+1)];
+        widths.extend(self.settings.columns.iter().map(Column::width));
+        widths
+    }
+
+    /// Builds the rows visible in a table of `height` lines. The table scrolls to
+    /// keep the selection, or the first entry matching the typed number, visible.
+    pub fn as_gui_element(&mut self, height: usize, selection: &str) -> (Vec<Row<'static>>,TableState) {
+        let total = self.filtered_list.len()+1;
+        let height = std::cmp::max(height,1);
+        self.height = height;
+        let focus = if selection.is_empty() {
+            self.selected_state.selected().unwrap_or(0)
+        }
+        else {
+            self.get_selected_by_string(selection).first().copied().unwrap_or(0)
+        };
+        if focus < self.offset {
+            self.offset = focus;
+        }
+        else if focus >= self.offset+height {
+            self.offset = focus+1-height;
+        }
+        self.offset = std::cmp::min(self.offset,total.saturating_sub(height));
 
-            item_list.push(ListItem::new(Spans::from(index)));
-            (item_list,&mut self.selected_state)
+        let rows = (self.offset..std::cmp::min(self.offset+height,total)).map(|idx| self.entry(idx,selection)).collect::<Vec<Row>>();
+        let mut state = TableState::default();
+        state.select(self.selected_state.selected().and_then(|x| x.checked_sub(self.offset)));
+        (rows,state)
     }
 }
 
@@ -143,41 +278,163 @@ enum CurrentState {
 
 pub struct AppSearchContext {
     container_list: ContainerList,
-    search_term: String,
+    search_term: TextInput,
     current_selection: String,
     current_state: CurrentState,
+    config: Config,
+    /// The details shown for the selected container, by its id. They are kept
+    /// while newer ones are loaded to avoid flickering.
+    details: Option<(String,Result<ContainerDetails,String>)>,
+    /// The container whose details were requested last.
+    details_requested: Option<String>,
+    /// Why the containers could not be listed, shown before the next render.
+    list_error: Option<String>,
+    /// Whether resource usage is being sampled in the background, cleared by the
+    /// sampling task itself as other screens drop its result.
+    sampling: Arc<AtomicBool>,
+    last_sample: Option<Instant>,
+    tasks: Tasks,
 }
 
-async fn attach_to_container(id: &str) {
-    let args = vec!["attach",id,"--detach-keys","ctrl-d"];
-    println!("{}",ToMainScreen);
-    Command::new("docker").args(args).spawn().unwrap().wait().unwrap();
-    println!("{}",ToAlternateScreen);
+/// Attaches to the container or, given an `exec` setup, runs a new process in it.
+/// The session reads stdin itself meanwhile and ends with the `detach` sequence.
+pub async fn attach_to_container(docker: &Docker, events: &mut Events, id: &str, exec: Option<&ExecSetup>, detach: &[u8]) -> Result<(),String> {
+    events.suspend_input();
+    println!("{}{}",input::DISABLE_BRACKETED_PASTE,ToMainScreen);
+    let result = match exec {
+        Some(setup) => container::start_and_exec(docker,id,setup,detach).await,
+        None => container::start_and_attach(docker,id,detach).await,
+    };
+    println!("{}{}",ToAlternateScreen,input::ENABLE_BRACKETED_PASTE);
+    events.resume_input();
+    result
 }
 
 impl AppSearchContext{ 
-    pub fn new() -> AppSearchContext { 
+    pub fn new(config: Config, tasks: Tasks) -> AppSearchContext { 
         AppSearchContext { 
             container_list: ContainerList {
                 containers: Vec::new(),
                 filtered_list: Vec::new(),
-                selected_state: ListState::default(),
+                selected_state: TableState::default(),
+                settings: TableSettings::load(),
+                stats: HashMap::new(),
+                show_all: false,
+                foreign: HashSet::new(),
+                smart_case: config.smart_case,
+                query_error: None,
+                highlights: HashMap::new(),
+                offset: 0,
+                height: 0,
             },
             current_selection: String::new(),
-            search_term: String::new(),
+            search_term: TextInput::default(),
             current_state: CurrentState::CommandMode,
+            config,
+            details: None,
+            details_requested: None,
+            list_error: None,
+            sampling: Arc::new(AtomicBool::new(false)),
+            last_sample: None,
+            tasks,
         }
     }
 
+    /// Lists the containers again, a failure is kept in `list_error`.
     pub async fn update(&mut self, docker: &Docker) {
-        self.container_list.update(docker).await;
+        self.list_error = self.container_list.update(docker).await.err();
+        self.details_requested = None;
+    }
+
+    /// Shows why the containers could not be listed.
+    async fn show_list_error<B: Backend>(&mut self, term: &mut Terminal<B>, events: &mut Events) {
+        if let Some(error) = self.list_error.take() {
+            AppPopupContext::new(error).style(Style::default().fg(Color::LightRed)).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+        }
+    }
+
+    /// Lists the containers again, keeping the selection and the search.
+    async fn refresh(&mut self, docker: &Docker) {
+        let selected = self.container_list.selected_container().map(|x| x.id.clone());
+        self.update(docker).await;
+        self.container_list.update_filtered_list(self.search_term.value());
+        if let Some(id) = selected {
+            self.container_list.select_container(&id);
+        }
+    }
+
+    /// Samples the resource usage of the running containers in the background
+    /// if the table shows it, the result arrives as `Event::Stats`.
+    fn sample_stats(&mut self, events: &Events) {
+        let due = self.last_sample.map(|x| x.elapsed() >= STATS_INTERVAL).unwrap_or(true);
+        if self.sampling.load(Ordering::Relaxed) || !due || !self.container_list.settings.columns.iter().any(Column::needs_stats) {
+            return;
+        }
+        self.sampling.store(true,Ordering::Relaxed);
+        let sampling = self.sampling.clone();
+        let running = self.container_list.running();
+        let sender = events.sender();
+        tokio::spawn(async move {
+            let samples = join_all(running.iter().map(|id| stats::sample(id))).await;
+            let stats = running.into_iter().zip(samples).filter_map(|(id,sample)| Some((id,sample.ok()?))).collect();
+            sampling.store(false,Ordering::Relaxed);
+            let _ = sender.send(Event::Stats(stats));
+        });
+    }
+
+    fn set_stats(&mut self, stats: HashMap<String,Sample>) {
+        self.last_sample = Some(Instant::now());
+        self.container_list.stats = stats;
+        if self.container_list.settings.sort.needs_stats() {
+            let selected = self.container_list.selected_container().map(|x| x.id.clone());
+            self.container_list.update_filtered_list(self.search_term.value());
+            if let Some(id) = selected {
+                self.container_list.select_container(&id);
+            }
+        }
+    }
+
+    /// Inspects the selected container in the background unless it was already
+    /// requested, the result arrives as `Event::Details`.
+    fn update_details(&mut self, events: &Events) {
+        let selected = self.container_list.selected_container().map(|x| x.id.clone());
+        if selected == self.details_requested {
+            return;
+        }
+        self.details_requested = selected.clone();
+        if let Some(id) = selected {
+            let sender = events.sender();
+            tokio::spawn(async move {
+                let details = ContainerDetails::load(&id).await;
+                let _ = sender.send(Event::Details(id,details));
+            });
+        }
+    }
+
+    /// Starts the container at `idx` if necessary and attaches to it, or runs the
+    /// `exec` setup in it.
+    async fn open_container<B: Backend>(&mut self, term: &mut Terminal<B>, docker: &Docker, events: &mut Events, idx: usize, exec: Option<&ExecSetup>) {
+        let id = self.container_list.containers[idx].id.clone();
+        match attach_to_container(docker,events,&id,exec,&self.config.detach_sequence()).await {
+            Ok(_) => {
+                term.clear().unwrap();
+                self.update(docker).await;
+                self.search_term.clear();
+                self.current_selection.clear();
+                self.container_list.update_filtered_list(self.search_term.value());
+            },
+            Err(error) => {
+                term.clear().unwrap();
+                AppPopupContext::new(format!("An error occured press any key to continue: {}",error)).style(Style::default().fg(Color::LightRed)).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+            }
+        }
     }
 
     pub fn render<B: Backend>(&mut self,terminal: &mut Terminal<B>, popup: Option<&mut AppPopupContext>) {
         terminal.draw(|f| {
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
-                .constraints([Constraint::Length(1),Constraint::Length(3),Constraint::Min(2),Constraint::Length(3)].as_ref())
+                .constraints([Constraint::Length(1),Constraint::Length(if self.container_list.query_error.is_some() { 4 } else { 3 }),Constraint::Min(2),Constraint::Length(3),Constraint::Length(1)].as_ref())
                 .split(f.size());
 
             let mut style_non = Style::default().fg(Color::Blue);
@@ -186,6 +443,7 @@ impl AppSearchContext{
             let mut header_style = Style::default().fg(Color::LightRed);
             let mut header_other_style = Style::default().bg(Color::Black).fg(Color::LightYellow);
             let mut list_highlight_style = Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD);
+            let mut style_details = Style::default().fg(Color::White);
 
 
             if popup.is_some() {
@@ -196,86 +454,110 @@ impl AppSearchContext{
                 header_style = style_dim;
                 header_other_style = style_dim;
                 list_highlight_style = style_dim;
+                style_details = style_dim;
             }
 
             let header_paragraph = Paragraph::new(Spans::from(vec![Span::styled(VERSION,header_style),Span::raw(" | Search mode")]))
                 .style(header_other_style)
                 .alignment(Alignment::Center);
-            let mut paragraph = Paragraph::new(Span::raw(&self.search_term))
-                                    .block(Block::default().borders(Borders::ALL).title("Search"))
-                                    .alignment(Alignment::Left);
-            paragraph = match self.current_state {
-                CurrentState::EnteringSearch => paragraph.style(style_hi),
-                _ => paragraph.style(style_non),
+            let search_style = match self.current_state {
+                CurrentState::EnteringSearch => style_hi,
+                _ => style_non,
             };
+            let search_block = Block::default().borders(Borders::ALL).title("Search");
+            let search_inner = search_block.inner(chunks[1]);
+            let entering = matches!(self.current_state,CurrentState::EnteringSearch) && popup.is_none();
 
             f.render_widget(header_paragraph,chunks[0]);
-            f.render_widget(paragraph,chunks[1]);
+            self.search_term.render_on(f,chunks[1],"",search_block,search_style,entering);
+            if let Some(error) = &self.container_list.query_error {
+                let error = Paragraph::new(Span::styled(error.clone(),Style::default().fg(Color::LightRed)));
+                f.render_widget(error,Rect::new(search_inner.x,search_inner.y+1,search_inner.width,search_inner.height.saturating_sub(1)));
+            }
 
-            let (result,state) = self.container_list.as_gui_element(&self.search_term,&self.current_selection);
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(60),Constraint::Percentage(40)].as_ref())
+                .split(chunks[2]);
+            // The table loses one line to its header.
+            let (result,mut state) = self.container_list.as_gui_element(columns[0].height.saturating_sub(3) as usize,&self.current_selection);
             let mut block = Block::default().borders(Borders::ALL).title(format!("Selection: {}",&self.current_selection));
             block = match self.current_state {
                 CurrentState::SelectingOption => block.style(style_hi),
                 _ => block.style(style_non)
             };
 
-            let container = List::new(result)
+            let widths = self.container_list.widths();
+            let container = Table::new(result)
+                .header(self.container_list.header().style(style_non.add_modifier(Modifier::BOLD)))
+                .widths(&widths)
                 .block(block)
                 .style(style_non)
                 .highlight_style(list_highlight_style).highlight_symbol(">> ");
             match self.current_state {
                 CurrentState::SelectingOption => {
-                    f.render_stateful_widget(container,chunks[2],state);
+                    f.render_stateful_widget(container,columns[0],&mut state);
                 },
                 _ => {
-                    f.render_widget(container, chunks[2]);
+                    f.render_widget(container, columns[0]);
                 }
             }
 
+            let selected = self.container_list.selected_container().map(|x| &x.id);
+            let details = match (&self.details,selected) {
+                (_,None) => Paragraph::new(Span::styled("No container selected",style_details)),
+                (Some((id,Ok(details))),Some(x)) if id == x => Paragraph::new(details.text(style_details)),
+                (Some((id,Err(error))),Some(x)) if id == x => Paragraph::new(Span::styled(error.clone(),style_details.fg(Color::LightRed))),
+                _ => Paragraph::new(Span::styled("Loading...",style_details)),
+            };
+            let details = details.block(Block::default().borders(Borders::ALL).title("Details").style(style_non)).wrap(Wrap { trim: false });
+            f.render_widget(details,columns[1]);
+
             let help = match self.current_state {
                 CurrentState::EnteringSearch => {
-                    Paragraph::new(Text::from("Enter name - accept with <enter>; quit with <ctrl-c>")).style(style_help)
+                    Paragraph::new(Text::from("Enter name or filters like status:running image:rust path:~/work label:team=infra, negate with -status:exited - accept with <enter>; quit with <ctrl-c>")).style(style_help)
                                     .block(Block::default().borders(Borders::ALL).title("Help"))
                                     .alignment(Alignment::Left)
                 },
                 CurrentState::SelectingOption => {
-                    Paragraph::new(Text::from("Select container: accept - <enter>; back to name field - <esc>; quit - <ctrl-c>; w - up; s - down")).style(style_help)
+                    Paragraph::new(Text::from("Select container: accept - <enter>; back to name field - <esc>; quit - <ctrl-c>; w - up; s - down; x - new shell; X - run command; l - logs; u - resource usage; r - docker run command; c - columns; o/O - sort column/direction; a - show all containers; A - adopt container; b - export workspace to host")).style(style_help)
                                     .block(Block::default().borders(Borders::ALL).title("Help"))
                                     .alignment(Alignment::Left)
                 },
                 CurrentState::CommandMode => {
-                    Paragraph::new(Text::from("/ : Enter search mode, 0-9 : Select container, <space> : Select container")).style(style_help)
+                    Paragraph::new(Text::from("/ : Enter search mode, 0-9 : Select container, <space> : Select container, i : Images, <ctrl-t> : Background tasks")).style(style_help)
                                     .block(Block::default().borders(Borders::ALL).title("Help"))
                                     .alignment(Alignment::Left)
                 }
             };
             f.render_widget(help,chunks[3]);
+            f.render_widget(self.tasks.status_bar(),chunks[4]);
             if popup.is_some() {
                 popup.unwrap().render_on(f);
             }
         }).unwrap();
-        terminal.show_cursor().unwrap();
-        terminal.set_cursor(self.search_term.len() as u16+1, 2).unwrap();
     }
 
-    pub async fn event_loop<B: Backend>(&mut self, term: &mut Terminal<B>, docker: &Docker) -> AppState {
+    pub async fn event_loop<B: Backend>(&mut self, term: &mut Terminal<B>, docker: &Docker, events: &mut Events) -> AppState {
         self.update(docker).await;
-        self.container_list.update_filtered_list(&self.search_term);
+        self.container_list.update_filtered_list(self.search_term.value());
         self.current_selection.clear();
+        self.sample_stats(events);
 
-        let stdin = io::stdin();
+        self.update_details(events);
+        self.show_list_error(term,events).await;
         self.render(term,None);
-        for evt in stdin.keys() {
-            match evt {
-                Ok(Key::Char(r)) => {
+        loop {
+            match events.next().await {
+                Event::Key(Key::Char(r)) => {
                     match self.current_state {
                         CurrentState::EnteringSearch => {
                             if r == '\n' {
                                 self.current_state = CurrentState::CommandMode;
                             }
                             else {
-                                self.search_term.push(r);
-                                self.container_list.update_filtered_list(&self.search_term);
+                                self.search_term.handle_key(Key::Char(r));
+                                self.container_list.update_filtered_list(self.search_term.value());
                             }
                         },
                         CurrentState::CommandMode => {
@@ -288,6 +570,9 @@ impl AppSearchContext{
                             else if r == 'n' {
                                 return AppState::NewContainer;
                             }
+                            else if r == 'i' {
+                                return AppState::Images;
+                            }
                             else if r >= '0' && r <= '9' {
                                 self.current_selection.push(r);
                                 let num_results = self.container_list.get_selected_by_string(&self.current_selection);
@@ -295,33 +580,12 @@ impl AppSearchContext{
                                     self.current_selection.clear();
                                 }
                                 else if num_results.len() == 1 {
-                                    let mut selected = num_results[0];
+                                    let selected = num_results[0];
                                     if selected == self.container_list.filtered_list.len() {
                                         return AppState::NewContainer;
                                     }
                                     else {
-                                        selected = self.container_list.filtered_list[selected];
-                                        let cont = self.container_list.containers.iter().filter_map(|x| if &x.names[0] == &self.container_list.containers[selected].names[0] { Some(&x.id) } else { None }).collect::<Vec<&String>>();
-                                        let container = docker.containers().get(cont[0]);
-                                        if let Ok(inspection) = container.inspect().await {
-                                            if inspection.state.running {
-                                                attach_to_container(cont[0]).await;
-                                                term.clear().unwrap();
-                                            }
-                                            else {
-                                                //Start it
-                                                container.start().await.unwrap();
-                                                attach_to_container(cont[0]).await;
-                                                term.clear().unwrap();
-                                            }
-                                            self.container_list.update(&docker).await;
-                                            self.search_term.clear();
-                                            self.current_selection.clear();
-                                            self.container_list.update_filtered_list(&self.search_term);
-                                        }
-                                        else {
-                                            AppPopupContext::new("An error occured press any key to continue".to_owned()).style(Style::default().fg(Color::LightRed)).event_render_loop(|popup| self.render(term, Some(popup)));
-                                        }
+                                        self.open_container(term,docker,events,self.container_list.filtered_list[selected],None).await;
                                     }
                                 }
                             }
@@ -337,93 +601,236 @@ impl AppSearchContext{
                                 self.container_list.select_next();
                             }
                             else if r == 'd' {
-                                let selected = self.container_list.get_selected();
-                                if selected != self.container_list.containers.len() {
-                                    let result = AppPopupContext::decision("Do you really want to delete this container (y) - yes, (n) - no?".to_owned()).style(Style::default().fg(Color::LightRed)).event_render_loop(|popup| self.render(term, Some(popup)));
+                                if let Some((id,name)) = self.container_list.selected_container().map(|x| (x.id.clone(),container::display_name(x).to_owned())) {
+                                    let result = AppPopupContext::decision(format!("Do you really want to delete {} (y) - yes, (n) - no?",name)).style(Style::default().fg(Color::LightRed)).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
                                     if result == "yes" {
-                                        let cont = &self.container_list.containers[selected].id;
-                                        let _ = docker.containers().get(cont).stop(None).await;
-                                        let opts = RmContainerOptions::builder().build();
-                                        let res = docker.containers().get(cont).remove(opts).await;
-                                        if res.is_err() {
-                                            AppPopupContext::new("Could not remove container.".to_owned()).style(Style::default().fg(Color::LightRed)).event_render_loop(|popup| self.render(term, Some(popup)));
-                                        }
-                                        self.update(docker).await;
-                                        self.container_list.update_filtered_list(&self.search_term);
+                                        let docker = docker.clone();
+                                        self.tasks.spawn(format!("Removing {}",name),move |_| async move {
+                                            container::remove(&docker,&id).await.map(|_| format!("Removed {}",name))
+                                        });
                                     }
                                 }
                             }
                             else if r == 'e' {
-                                let selected = self.container_list.get_selected();
-                                if selected != self.container_list.containers.len() {
-                                    let cont = &self.container_list.containers[selected].id;
-                                    let res = docker.containers().get(cont).stop(None).await;
-                                    if res.is_err() {
-                                        AppPopupContext::new("Could not stop container.".to_owned()).style(Style::default().fg(Color::LightRed)).event_render_loop(|popup| self.render(term, Some(popup)));
+                                if let Some((id,name)) = self.container_list.selected_container().map(|x| (x.id.clone(),container::display_name(x).to_owned())) {
+                                    let docker = docker.clone();
+                                    self.tasks.spawn(format!("Stopping {}",name),move |_| async move {
+                                        container::stop(&docker,&id).await.map(|_| format!("Stopped {}",name))
+                                    });
+                                }
+                            }
+                            else if r == 'b' {
+                                if let Some(id) = self.container_list.selected_container().map(|x| x.id.clone()) {
+                                    let default = std::env::current_dir().map(|x| x.to_string_lossy().to_string()).unwrap_or_default();
+                                    let target = self.config.mount_target.clone();
+                                    let host = AppPopupContext::input(format!("Export {} to host directory:",target),default).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+                                    if !host.is_empty() {
+                                        let mut popup = match export::export_workspace(docker,&id,&target,Path::new(&host),events,|popup| self.render(term, Some(popup))).await {
+                                            Ok(message) => AppPopupContext::new(message),
+                                            Err(error) => AppPopupContext::new(error).style(Style::default().fg(Color::LightRed)),
+                                        };
+                                        popup.event_render_loop(events,|popup| self.render(term, Some(popup))).await;
                                     }
-                                    self.update(docker).await;
-                                    self.container_list.update_filtered_list(&self.search_term);
                                 }
                             }
-                            else if r == '\n' || r == '\t' {
-                                let mut selected = self.container_list.get_selected();
-                                if selected == self.container_list.filtered_list.len() {
-                                    return AppState::NewContainer;
+                            else if r == 'l' {
+                                if let Some(selected) = self.container_list.selected_container() {
+                                    return AppState::Logs(selected.id.clone());
                                 }
-                                else {
-                                    selected = self.container_list.filtered_list[selected];
-                                    let cont = self.container_list.containers.iter().filter_map(|x| if &x.names[0] == &self.container_list.containers[selected].names[0] { Some(&x.id) } else { None }).collect::<Vec<&String>>();
-                                    let container = docker.containers().get(cont[0]);
-                                    if let Ok(inspection) = container.inspect().await {
-                                        if inspection.state.running {
-                                            attach_to_container(cont[0]).await;
-                                            term.clear().unwrap();
+                            }
+                            else if r == 'u' {
+                                if let Some(selected) = self.container_list.selected_container() {
+                                    return AppState::Stats(selected.id.clone());
+                                }
+                            }
+                            else if r == 'r' {
+                                if let Some(selected) = self.container_list.selected_container() {
+                                    return AppState::RunCommand(selected.id.clone());
+                                }
+                            }
+                            else if r == 'a' {
+                                self.container_list.show_all = !self.container_list.show_all;
+                                self.update(docker).await;
+                                self.container_list.update_filtered_list(self.search_term.value());
+                            }
+                            else if r == 'A' {
+                                if let Some(selected) = self.container_list.selected_container() {
+                                    let id = selected.id.clone();
+                                    if self.container_list.foreign.contains(&id) {
+                                        let message = format!("Manage \"{}\" with dde (y) - yes, (n) - no?",container::display_name(selected));
+                                        if AppPopupContext::decision(message).event_render_loop(events,|popup| self.render(term, Some(popup))).await == "yes" {
+                                            if let Err(error) = container::adopt(&id) {
+                                                AppPopupContext::new(error).style(Style::default().fg(Color::LightRed)).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+                                            }
+                                            self.update(docker).await;
+                                            self.container_list.update_filtered_list(self.search_term.value());
                                         }
-                                        else {
-                                            //Start it
-                                            container.start().await.unwrap();
-                                            attach_to_container(cont[0]).await;
-                                            term.clear().unwrap();
+                                    }
+                                }
+                            }
+                            else if r == 'c' || r == 'o' || r == 'O' {
+                                let mut settings = self.container_list.settings.clone();
+                                if r == 'c' {
+                                    let current = settings.columns.iter().map(|x| x.name()).collect::<Vec<&str>>().join(", ");
+                                    let columns = AppPopupContext::input(String::from("Columns (name, status, image, created, uptime, workspace, ports, cpu, memory):"),current).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+                                    if let Err(error) = settings.set_columns(&columns) {
+                                        AppPopupContext::new(error).style(Style::default().fg(Color::LightRed)).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+                                    }
+                                }
+                                else if r == 'o' {
+                                    settings.next_sort_column();
+                                }
+                                else {
+                                    settings.descending = !settings.descending;
+                                }
+                                if let Err(error) = settings.save() {
+                                    AppPopupContext::new(error).style(Style::default().fg(Color::LightRed)).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+                                }
+                                self.container_list.settings = settings;
+                                self.container_list.update_filtered_list(self.search_term.value());
+                            }
+                            else if r == 'x' || r == 'X' {
+                                let selected = self.container_list.get_selected();
+                                if selected < self.container_list.filtered_list.len() {
+                                    let mut setup = Ok(ExecSetup::default());
+                                    if r == 'X' {
+                                        let command = AppPopupContext::input(String::from("Command (empty for the entry command):"),String::new()).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+                                        setup = query::split(&command).map(|command| ExecSetup { command, ..ExecSetup::default() });
+                                        if let Ok(setup) = &mut setup {
+                                            setup.user = AppPopupContext::input(String::from("User (empty for the default user):"),String::new()).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+                                            setup.working_dir = AppPopupContext::input(String::from("Working directory (empty for the default):"),self.config.mount_target.clone()).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
                                         }
-                                        self.container_list.update(&docker).await;
-                                        self.search_term.clear();
-                                        self.current_selection.clear();
-                                        self.container_list.update_filtered_list(&self.search_term);
                                     }
-                                    else {
-                                        AppPopupContext::new("An error occured press any key to continue".to_owned()).style(Style::default().fg(Color::LightRed)).event_render_loop(|popup| self.render(term, Some(popup)));
+                                    match setup {
+                                        Ok(setup) => self.open_container(term,docker,events,self.container_list.filtered_list[selected],Some(&setup)).await,
+                                        Err(error) => {
+                                            AppPopupContext::new(error).style(Style::default().fg(Color::LightRed)).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+                                        },
                                     }
                                 }
                             }
+                            else if r == '\n' || r == '\t' {
+                                let selected = self.container_list.get_selected();
+                                if selected >= self.container_list.filtered_list.len() {
+                                    return AppState::NewContainer;
+                                }
+                                else {
+                                    self.open_container(term,docker,events,self.container_list.filtered_list[selected],None).await;
+                                }
+                            }
                         },
                     }
                 },
-                Ok(Key::Backspace) => {
-                    match self.current_state {
-                        CurrentState::EnteringSearch => {
-                            self.search_term.pop();
-                            self.container_list.update_filtered_list(&self.search_term);
-                        },
-                        _ => {}
-                    }
+                Event::Key(Key::PageUp) => {
+                    let height = self.container_list.height as isize;
+                    self.container_list.select_by(-height);
                 },
-                Ok(Key::Esc) => {
+                Event::Key(Key::PageDown) => {
+                    let height = self.container_list.height as isize;
+                    self.container_list.select_by(height);
+                },
+                Event::Key(key) if matches!(self.current_state,CurrentState::EnteringSearch) && self.search_term.handle_key(key) => {
+                    self.container_list.update_filtered_list(self.search_term.value());
+                },
+                Event::Paste(text) if matches!(self.current_state,CurrentState::EnteringSearch) => {
+                    self.search_term.insert_str(&text);
+                    self.container_list.update_filtered_list(self.search_term.value());
+                },
+                Event::Key(Key::Esc) => {
                     self.current_state = CurrentState::CommandMode;
                     self.current_selection.clear();
                 },
-                Ok(Key::Ctrl('c')) => {
+                Event::Key(Key::Ctrl('c')) => {
                     return AppState::Exiting;
                 },
-                Ok(Key::Ctrl('h')) => {
+                Event::Key(Key::Ctrl('h')) => {
                     return AppState::Help;
                 },
-                Ok(Key::Ctrl('a')) => {
+                Event::Key(Key::Ctrl('a')) => {
                     return AppState::Command;
                 },
+                Event::Key(Key::Ctrl('t')) => {
+                    return AppState::Tasks;
+                },
+                Event::Tick => {
+                    self.sample_stats(events);
+                },
+                Event::Docker(event) if event.typ == "container" => {
+                    self.refresh(docker).await;
+                },
+                Event::Stats(stats) => {
+                    self.set_stats(stats);
+                },
+                Event::Details(id,details) if self.details_requested.as_ref() == Some(&id) => {
+                    self.details = Some((id,details));
+                },
+                Event::InputClosed => {
+                    return AppState::Exiting;
+                },
                 _ => {}
             }
+            self.update_details(events);
+            self.show_list_error(term,events).await;
             self.render(term,None);
         }
-        return AppState::Exiting;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container_list(names: &[&str]) -> ContainerList {
+        let containers = names.iter().map(|name| serde_json::from_value(serde_json::json!({
+            "Created": 0,
+            "Command": "/bin/zsh",
+            "Id": name,
+            "Image": "alpine",
+            "ImageID": "sha256:0",
+            "Labels": {},
+            "Names": [format!("/{}",name)],
+            "Ports": [],
+            "State": "running",
+            "Status": "Up",
+        })).unwrap()).collect::<Vec<rep::Container>>();
+        ContainerList {
+            filtered_list: (0..containers.len()).collect(),
+            containers,
+            selected_state: TableState::default(),
+            settings: TableSettings::default(),
+            stats: HashMap::new(),
+            show_all: false,
+            foreign: HashSet::new(),
+            smart_case: false,
+            query_error: None,
+            highlights: HashMap::new(),
+            offset: 0,
+            height: 0,
+        }
+    }
+
+    /// The name of the container the typed `digits` select, `None` for the
+    /// entry creating a new container.
+    fn typed(list: &ContainerList, digits: &str) -> Vec<Option<String>> {
+        list.get_selected_by_string(digits).into_iter().map(|x| list.filtered_list.get(x).map(|y| list.containers[*y].id.clone())).collect()
+    }
+
+    #[test]
+    fn quick_select_numbers_keep_their_width_across_filters() {
+        let mut list = container_list(&["web0","web1","web2","web3","web4","web5","web6","web7","web8","web9","db0","db1"]);
+        list.update_filtered_list("web");
+        assert_eq!(list.get_expanded_string(0),"00");
+        assert_eq!(typed(&list,"01"),vec![Some(String::from("web1"))]);
+        assert_eq!(typed(&list,"10"),vec![None]);
+
+        // Typing the first digit before the filter narrows the list must not
+        // select an entry on its own.
+        assert_eq!(typed(&list,"0").len(),10);
+        list.update_filtered_list("db");
+        assert_eq!(list.get_expanded_string(0),"00");
+        assert_eq!(typed(&list,"0").len(),3);
+        assert_eq!(typed(&list,"01"),vec![Some(String::from("db1"))]);
+        assert_eq!(typed(&list,"02"),vec![None]);
+        assert!(typed(&list,"1").is_empty());
     }
 }
@@ -0,0 +1,190 @@
+use shiplift::Docker;
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout,Alignment,Rect},
+    style::{Color, Modifier, Style},
+    text::{Span,Text,Spans},
+    widgets::{Block, Borders, Paragraph, Sparkline},
+    Terminal,
+    terminal::Frame,
+};
+use termion::event::Key;
+use crate::ui::AppState;
+use crate::ui::popup::AppPopupContext;
+use crate::ui::events::{Event,Events};
+use crate::stats::{History,StatsStream};
+use crate::workspace::format_size;
+use crate::VERSION;
+
+pub struct AppStatsContext {
+    id: String,
+    name: String,
+    history: History,
+    ended: bool,
+}
+
+fn rate(bytes: u64) -> String {
+    format!("{}/s",format_size(bytes))
+}
+
+/// Draws the last values of `data` that fit into `area`, `max` is the value
+/// reaching the top of the area.
+fn render_sparkline<B: Backend>(f: &mut Frame<B>, area: Rect, title: String, data: &[u64], max: u64, style: Style) {
+    let width = area.width.saturating_sub(2) as usize;
+    let data = &data[data.len().saturating_sub(width)..];
+    let max = std::cmp::max(max,data.iter().copied().max().unwrap_or(0)).max(1);
+    let sparkline = Sparkline::default()
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .data(data)
+        .max(max)
+        .style(style);
+    f.render_widget(sparkline,area);
+}
+
+impl AppStatsContext {
+    pub fn new(id: String) -> AppStatsContext {
+        AppStatsContext {
+            name: id.clone(),
+            id,
+            history: History::default(),
+            ended: false,
+        }
+    }
+
+    pub fn render<B: Backend>(&mut self,terminal: &mut Terminal<B>, popup: Option<&mut AppPopupContext>) {
+        terminal.draw(|f| {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1),Constraint::Length(7),Constraint::Min(9),Constraint::Length(3)].as_ref())
+                .split(f.size());
+
+            let mut style_text = Style::default().fg(Color::LightYellow);
+            let mut style_cpu = Style::default().fg(Color::LightGreen);
+            let mut style_memory = Style::default().fg(Color::LightMagenta);
+            let mut style_network = Style::default().fg(Color::LightCyan);
+            let mut style_block = Style::default().fg(Color::LightBlue);
+            let mut style_non = Style::default().fg(Color::Blue);
+            let mut style_help = Style::default().fg(Color::Cyan);
+            let mut header_style = Style::default().fg(Color::LightRed);
+            let mut header_other_style = Style::default().bg(Color::Black).fg(Color::LightYellow);
+
+            if popup.is_some() {
+                let style_dim = Style::default().fg(Color::Gray).add_modifier(Modifier::DIM);
+                style_text = style_dim;
+                style_cpu = style_dim;
+                style_memory = style_dim;
+                style_network = style_dim;
+                style_block = style_dim;
+                style_non = style_dim;
+                style_help = style_dim;
+                header_style = style_dim;
+                header_other_style = style_dim;
+            }
+
+            let header_paragraph = Paragraph::new(Spans::from(vec![Span::styled(VERSION,header_style),Span::raw(" | Resource usage")]))
+                .style(header_other_style)
+                .alignment(Alignment::Center);
+            f.render_widget(header_paragraph,chunks[0]);
+
+            let current = self.history.current();
+            let summary = match &self.history.last {
+                Some(sample) => {
+                    let (rx,tx) = sample.network();
+                    let (read,write) = sample.block_io();
+                    format!("CPU:       {:.1}%\nMemory:    {} / {} ({:.1}%)\nNetwork:   {} received ({}), {} sent ({})\nBlock IO:  {} read ({}), {} written ({})\nProcesses: {}",
+                        current.cpu_percent,
+                        format_size(sample.memory_usage()),format_size(sample.memory_limit()),sample.memory_percent(),
+                        format_size(rx),rate(current.rx_rate),format_size(tx),rate(current.tx_rate),
+                        format_size(read),rate(current.read_rate),format_size(write),rate(current.write_rate),
+                        sample.pids())
+                },
+                None if self.ended => String::from("The container is not running."),
+                None => String::from("Waiting for stats..."),
+            };
+            let mut title = self.name.clone();
+            if self.ended {
+                title.push_str(" [stream ended]");
+            }
+            let summary = Paragraph::new(Text::from(summary)).style(style_text)
+                .block(Block::default().borders(Borders::ALL).title(title).style(style_non));
+            f.render_widget(summary,chunks[1]);
+
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Ratio(1,3),Constraint::Ratio(1,3),Constraint::Ratio(1,3)].as_ref())
+                .split(chunks[2]);
+            let cells = rows.iter().map(|row| Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50),Constraint::Percentage(50)].as_ref())
+                .split(*row)).collect::<Vec<Vec<Rect>>>();
+
+            let points = &self.history.points;
+            // Tenths of a percent to keep the resolution of small loads.
+            let cpu = points.iter().map(|x| (x.cpu_percent*10.0) as u64).collect::<Vec<u64>>();
+            render_sparkline(f,cells[0][0],format!("CPU {:.1}%",current.cpu_percent),&cpu,1000,style_cpu);
+            let memory = points.iter().map(|x| x.memory).collect::<Vec<u64>>();
+            let limit = self.history.last.as_ref().map(|x| x.memory_limit()).unwrap_or(0);
+            render_sparkline(f,cells[0][1],format!("Memory {}",format_size(current.memory)),&memory,limit,style_memory);
+            let rx = points.iter().map(|x| x.rx_rate).collect::<Vec<u64>>();
+            render_sparkline(f,cells[1][0],format!("Network received {}",rate(current.rx_rate)),&rx,0,style_network);
+            let tx = points.iter().map(|x| x.tx_rate).collect::<Vec<u64>>();
+            render_sparkline(f,cells[1][1],format!("Network sent {}",rate(current.tx_rate)),&tx,0,style_network);
+            let read = points.iter().map(|x| x.read_rate).collect::<Vec<u64>>();
+            render_sparkline(f,cells[2][0],format!("Block read {}",rate(current.read_rate)),&read,0,style_block);
+            let write = points.iter().map(|x| x.write_rate).collect::<Vec<u64>>();
+            render_sparkline(f,cells[2][1],format!("Block write {}",rate(current.write_rate)),&write,0,style_block);
+
+            let help = Paragraph::new(Text::from("History of the last 5 minutes; back - q or <esc>; quit - <ctrl-c>")).style(style_help)
+                                    .block(Block::default().borders(Borders::ALL).title("Help"))
+                                    .alignment(Alignment::Left);
+            f.render_widget(help,chunks[3]);
+            if let Some(popup) = popup {
+                popup.render_on(f);
+            }
+        }).unwrap();
+    }
+
+    pub async fn event_loop<B: Backend>(&mut self, term: &mut Terminal<B>, docker: &Docker, events: &mut Events) -> AppState {
+        match docker.containers().get(&self.id).inspect().await {
+            Ok(x) => self.name = x.name.trim_start_matches('/').to_owned(),
+            Err(e) => {
+                AppPopupContext::new(format!("Could not inspect container: {}",e)).style(Style::default().fg(Color::LightRed)).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+                return AppState::Search;
+            }
+        }
+        let mut stream = match StatsStream::open(&self.id).await {
+            Ok(x) => x,
+            Err(error) => {
+                AppPopupContext::new(format!("Could not read the stats: {}",error)).style(Style::default().fg(Color::LightRed)).event_render_loop(events,|popup| self.render(term, Some(popup))).await;
+                return AppState::Search;
+            }
+        };
+
+        self.render(term,None);
+        loop {
+            tokio::select! {
+                sample = stream.next(), if !self.ended => {
+                    match sample {
+                        Ok(Some(sample)) => self.history.push(sample),
+                        Ok(None) | Err(_) => self.ended = true,
+                    }
+                },
+                event = events.next() => {
+                    match event {
+                        Event::Key(Key::Char('q')) | Event::Key(Key::Esc) => return AppState::Search,
+                        Event::Key(Key::Ctrl('c')) | Event::InputClosed => return AppState::Exiting,
+                        // Follow the container once it got started again.
+                        Event::Docker(event) if self.ended && event.action == "start" && event.actor.id == self.id => {
+                            if let Ok(x) = StatsStream::open(&self.id).await {
+                                stream = x;
+                                self.ended = false;
+                            }
+                        },
+                        _ => {},
+                    }
+                },
+            }
+            self.render(term,None);
+        }
+    }
+}
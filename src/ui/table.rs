@@ -0,0 +1,194 @@
+use std::cmp::Ordering;
+use std::path::PathBuf;
+use serde::{Deserialize,Serialize};
+use shiplift::rep;
+use tui::layout::Constraint;
+use crate::config::Config;
+use crate::container::{self,LABEL_WORKSPACE};
+use crate::stats::Sample;
+use crate::workspace::format_size;
+
+/// A column of the container table.
+#[derive(Serialize,Deserialize,Clone,Copy,PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    Name,
+    Status,
+    Image,
+    Created,
+    Uptime,
+    Workspace,
+    Ports,
+    Cpu,
+    Memory,
+}
+
+pub const ALL_COLUMNS: [Column; 9] = [Column::Name,Column::Status,Column::Image,Column::Created,Column::Uptime,Column::Workspace,Column::Ports,Column::Cpu,Column::Memory];
+
+/// Seconds of a duration as docker describes it in the status of a container,
+/// e.g. "Up 3 hours" or "Up About a minute". Not running containers have none.
+fn uptime(status: &str) -> u64 {
+    let words = match status.strip_prefix("Up ") {
+        Some(x) => x.split_whitespace().collect::<Vec<&str>>(),
+        None => return 0,
+    };
+    let count = words.iter().find_map(|x| x.parse::<u64>().ok()).unwrap_or(1);
+    let unit = words.iter().map(|x| x.trim_end_matches('s')).find_map(|x| match x {
+        "second" => Some(1),
+        "minute" => Some(60),
+        "hour" => Some(3600),
+        "day" => Some(86400),
+        "week" => Some(604800),
+        "month" => Some(2592000),
+        "year" => Some(31536000),
+        _ => None,
+    }).unwrap_or(0);
+    count*unit
+}
+
+fn ports(container: &rep::Container) -> String {
+    container.ports.iter().map(|x| match x.public_port {
+        Some(public) => format!("{}->{}/{}",public,x.private_port,x.typ),
+        None => format!("{}/{}",x.private_port,x.typ),
+    }).collect::<Vec<String>>().join(", ")
+}
+
+impl Column {
+    pub fn parse(name: &str) -> Result<Column,String> {
+        ALL_COLUMNS.iter().copied().find(|x| x.name() == name.trim().to_lowercase())
+            .ok_or_else(|| format!("Unknown column \"{}\", expected one of {}",name.trim(),ALL_COLUMNS.iter().map(|x| x.name()).collect::<Vec<&str>>().join(", ")))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Name => "name",
+            Column::Status => "status",
+            Column::Image => "image",
+            Column::Created => "created",
+            Column::Uptime => "uptime",
+            Column::Workspace => "workspace",
+            Column::Ports => "ports",
+            Column::Cpu => "cpu",
+            Column::Memory => "memory",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Column::Name => "Name",
+            Column::Status => "Status",
+            Column::Image => "Image",
+            Column::Created => "Created",
+            Column::Uptime => "Uptime",
+            Column::Workspace => "Workspace",
+            Column::Ports => "Ports",
+            Column::Cpu => "CPU",
+            Column::Memory => "Memory",
+        }
+    }
+
+    pub fn width(&self) -> Constraint {
+        match self {
+            Column::Name | Column::Image | Column::Workspace => Constraint::Min(12),
+            Column::Ports => Constraint::Min(10),
+            Column::Status => Constraint::Length(9),
+            Column::Created => Constraint::Length(16),
+            Column::Uptime => Constraint::Length(18),
+            Column::Cpu => Constraint::Length(7),
+            Column::Memory => Constraint::Length(10),
+        }
+    }
+
+    /// Whether the column shows resource usage which has to be sampled.
+    pub fn needs_stats(&self) -> bool {
+        *self == Column::Cpu || *self == Column::Memory
+    }
+
+    /// The text of the cell for `container`, the name and status are styled by
+    /// the table itself.
+    pub fn value(&self, container: &rep::Container, stats: Option<&Sample>) -> String {
+        match self {
+            Column::Name => container::display_name(container).to_owned(),
+            Column::Status => if container.state == "running" { String::from("RUNNING") } else { String::from("STOPPED") },
+            Column::Image => container.image.clone(),
+            Column::Created => container.created.format("%Y-%m-%d %H:%M").to_string(),
+            Column::Uptime => if container.state == "running" { container.status.clone() } else { String::new() },
+            Column::Workspace => container.labels.get(LABEL_WORKSPACE).cloned().unwrap_or_default(),
+            Column::Ports => ports(container),
+            Column::Cpu => stats.map(|x| format!("{:.1}%",x.cpu_percent())).unwrap_or_default(),
+            Column::Memory => stats.map(|x| format_size(x.memory_usage())).unwrap_or_default(),
+        }
+    }
+
+    pub fn compare(&self, a: &rep::Container, b: &rep::Container, stats_a: Option<&Sample>, stats_b: Option<&Sample>) -> Ordering {
+        match self {
+            Column::Name => container::display_name(a).to_lowercase().cmp(&container::display_name(b).to_lowercase()),
+            Column::Status => a.state.cmp(&b.state),
+            Column::Image => a.image.cmp(&b.image),
+            Column::Created => a.created.cmp(&b.created),
+            Column::Uptime => uptime(&a.status).cmp(&uptime(&b.status)),
+            Column::Workspace => a.labels.get(LABEL_WORKSPACE).cmp(&b.labels.get(LABEL_WORKSPACE)),
+            Column::Ports => a.ports.iter().map(|x| x.public_port).max().cmp(&b.ports.iter().map(|x| x.public_port).max()),
+            Column::Cpu => stats_a.map(|x| x.cpu_percent()).partial_cmp(&stats_b.map(|x| x.cpu_percent())).unwrap_or(Ordering::Equal),
+            Column::Memory => stats_a.map(|x| x.memory_usage()).cmp(&stats_b.map(|x| x.memory_usage())),
+        }
+    }
+}
+
+/// The columns and sort order of the container table, kept between sessions.
+#[derive(Serialize,Deserialize,Clone)]
+pub struct TableSettings {
+    pub columns: Vec<Column>,
+    pub sort: Column,
+    pub descending: bool,
+}
+
+impl Default for TableSettings {
+    fn default() -> TableSettings {
+        TableSettings {
+            columns: vec![Column::Name,Column::Status,Column::Image,Column::Uptime],
+            sort: Column::Name,
+            descending: false,
+        }
+    }
+}
+
+impl TableSettings {
+    fn path() -> Option<PathBuf> {
+        Some(Config::data_dir()?.join("table.toml"))
+    }
+
+    /// Loads the settings of the last session, unreadable settings are replaced
+    /// by the defaults.
+    pub fn load() -> TableSettings {
+        TableSettings::path().and_then(|x| std::fs::read_to_string(x).ok()).and_then(|x| toml::from_str(&x).ok()).unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(),String> {
+        let path = TableSettings::path().ok_or("Could not find the home directory")?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Could not create \"{}\": {}",parent.display(),e))?;
+        }
+        let content = toml::to_string(self).map_err(|e| format!("Could not store the table settings: {}",e))?;
+        std::fs::write(&path,content).map_err(|e| format!("Could not write \"{}\": {}",path.display(),e))
+    }
+
+    /// Parses a comma separated list of column names.
+    pub fn set_columns(&mut self, columns: &str) -> Result<(),String> {
+        let columns = columns.split(',').filter(|x| !x.trim().is_empty()).map(Column::parse).collect::<Result<Vec<Column>,String>>()?;
+        if columns.is_empty() {
+            return Err(String::from("At least one column is needed"));
+        }
+        if !columns.contains(&self.sort) {
+            self.sort = columns[0];
+        }
+        self.columns = columns;
+        Ok(())
+    }
+
+    /// Sorts by the next visible column, wrapping around.
+    pub fn next_sort_column(&mut self) {
+        let idx = self.columns.iter().position(|x| *x == self.sort).map(|x| x+1).unwrap_or(0);
+        self.sort = self.columns[idx%self.columns.len()];
+    }
+}
@@ -0,0 +1,293 @@
+//! Long operations such as pulls, container creation, stopping and removal run
+//! as background tasks, the screens show their progress in a status bar and
+//! the task panel lists them with their results.
+use std::future::Future;
+use std::sync::{Arc,Mutex};
+use std::time::{Duration,Instant};
+use tokio::sync::mpsc;
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout,Alignment},
+    style::{Color, Modifier, Style},
+    text::{Span,Text,Spans},
+    widgets::{Block, Borders, Paragraph, Row, Table, TableState, Wrap},
+    Terminal,
+};
+use termion::event::Key;
+use crate::ui::AppState;
+use crate::ui::events::{Event,Events};
+use crate::VERSION;
+
+/// Finished tasks kept in the task list.
+const MAX_FINISHED: usize = 50;
+/// How long the status bar reports a successful task.
+const SUCCESS_NOTICE: Duration = Duration::from_secs(5);
+const SPINNER: [char; 10] = ['⠋','⠙','⠹','⠸','⠼','⠴','⠦','⠧','⠇','⠏'];
+
+#[derive(Clone)]
+pub enum TaskStatus {
+    Running,
+    Succeeded(String),
+    Failed(String),
+}
+
+#[derive(Clone)]
+pub struct Task {
+    pub id: usize,
+    pub title: String,
+    pub status: TaskStatus,
+    /// What the task is doing right now.
+    pub message: String,
+    /// Completed fraction between 0 and 1, if it is known.
+    pub progress: Option<f64>,
+    pub started: Instant,
+    pub finished: Option<Instant>,
+}
+
+impl Task {
+    fn duration(&self) -> Duration {
+        self.finished.unwrap_or_else(Instant::now)-self.started
+    }
+}
+
+/// The list of background tasks, shared by all screens.
+#[derive(Clone)]
+pub struct Tasks {
+    list: Arc<Mutex<Vec<Task>>>,
+    next_id: Arc<Mutex<usize>>,
+    sender: mpsc::UnboundedSender<Event>,
+}
+
+/// Handed to a running task to report its progress.
+#[derive(Clone)]
+pub struct Progress {
+    tasks: Tasks,
+    id: usize,
+}
+
+impl Progress {
+    pub fn set(&self, message: String, progress: Option<f64>) {
+        self.tasks.update(self.id,|task| {
+            task.message = message;
+            task.progress = progress.map(|x| x.clamp(0.0,1.0));
+        });
+    }
+}
+
+fn progress_bar(progress: f64, width: usize) -> String {
+    let filled = (progress*width as f64).round() as usize;
+    format!("[{}{}] {:.0}%","#".repeat(filled),"-".repeat(width-filled.min(width)),progress*100.0)
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 60 { format!("{}m {}s",seconds/60,seconds%60) } else { format!("{}.{}s",seconds,duration.subsec_millis()/100) }
+}
+
+impl Tasks {
+    pub fn new(events: &Events) -> Tasks {
+        Tasks {
+            list: Arc::new(Mutex::new(Vec::new())),
+            next_id: Arc::new(Mutex::new(0)),
+            sender: events.sender(),
+        }
+    }
+
+    fn update<F: FnOnce(&mut Task)>(&self, id: usize, change: F) {
+        if let Some(task) = self.list.lock().unwrap().iter_mut().find(|x| x.id == id) {
+            change(task);
+        }
+        let _ = self.sender.send(Event::Task);
+    }
+
+    /// Runs `task` in the background, it resolves to a message describing the
+    /// outcome. Returns the id of the task.
+    pub fn spawn<F,T>(&self, title: String, task: F) -> usize
+        where F: FnOnce(Progress) -> T, T: Future<Output = Result<String,String>> + Send + 'static {
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            *next_id
+        };
+        self.list.lock().unwrap().push(Task {
+            id,
+            title,
+            status: TaskStatus::Running,
+            message: String::new(),
+            progress: None,
+            started: Instant::now(),
+            finished: None,
+        });
+        let future = task(Progress { tasks: self.clone(), id });
+        let tasks = self.clone();
+        tokio::spawn(async move {
+            let result = future.await;
+            tasks.update(id,|task| {
+                task.status = match result {
+                    Ok(x) => TaskStatus::Succeeded(x),
+                    Err(x) => TaskStatus::Failed(x),
+                };
+                task.finished = Some(Instant::now());
+                task.progress = None;
+            });
+            tasks.prune();
+        });
+        let _ = self.sender.send(Event::Task);
+        id
+    }
+
+    /// Drops the oldest finished tasks beyond `MAX_FINISHED`.
+    fn prune(&self) {
+        let mut list = self.list.lock().unwrap();
+        let finished = list.iter().filter(|x| x.finished.is_some()).count();
+        let mut excess = finished.saturating_sub(MAX_FINISHED);
+        list.retain(|x| {
+            if excess > 0 && x.finished.is_some() {
+                excess -= 1;
+                return false;
+            }
+            true
+        });
+    }
+
+    pub fn get(&self, id: usize) -> Option<Task> {
+        self.list.lock().unwrap().iter().find(|x| x.id == id).cloned()
+    }
+
+    /// All tasks, the oldest first.
+    pub fn all(&self) -> Vec<Task> {
+        self.list.lock().unwrap().clone()
+    }
+
+    pub fn clear_finished(&self) {
+        self.list.lock().unwrap().retain(|x| x.finished.is_none());
+    }
+
+    /// One line describing the running tasks, or else the outcome of the last
+    /// finished one.
+    pub fn status_bar(&self) -> Paragraph<'static> {
+        let list = self.list.lock().unwrap();
+        let running = list.iter().filter(|x| x.finished.is_none()).collect::<Vec<&Task>>();
+        let hint = Span::styled(" (ctrl-t: tasks)",Style::default().fg(Color::Gray));
+        let line = if let Some(first) = running.first() {
+            let frame = SPINNER[(first.started.elapsed().as_millis()/100) as usize%SPINNER.len()];
+            let mut spans = vec![Span::styled(format!("{} ",frame),Style::default().fg(Color::LightYellow))];
+            for (idx,task) in running.iter().enumerate() {
+                let mut text = if idx > 0 { format!(" | {}",task.title) } else { task.title.clone() };
+                if let Some(progress) = task.progress {
+                    text.push(' ');
+                    text.push_str(&progress_bar(progress,10));
+                }
+                else if !task.message.is_empty() {
+                    text.push_str(&format!(": {}",task.message.lines().next().unwrap_or("")));
+                }
+                spans.push(Span::styled(text,Style::default().fg(Color::LightYellow)));
+            }
+            spans.push(hint);
+            Spans::from(spans)
+        }
+        else {
+            match list.iter().filter(|x| x.finished.is_some()).max_by_key(|x| x.finished) {
+                Some(Task { title, status: TaskStatus::Failed(error), .. }) => Spans::from(vec![Span::styled(format!("✗ {} failed: {}",title,error.lines().next().unwrap_or("")),Style::default().fg(Color::LightRed)),hint]),
+                Some(Task { status: TaskStatus::Succeeded(message), finished: Some(finished), .. }) if finished.elapsed() < SUCCESS_NOTICE => Spans::from(vec![Span::styled(format!("✓ {}",message),Style::default().fg(Color::LightGreen)),hint]),
+                _ => Spans::from(Span::styled("No background tasks running",Style::default().fg(Color::Gray))),
+            }
+        };
+        Paragraph::new(line).style(Style::default().bg(Color::Black))
+    }
+}
+
+pub struct AppTasksContext {
+    tasks: Tasks,
+    selected: TableState,
+}
+
+impl AppTasksContext {
+    pub fn new(tasks: Tasks) -> AppTasksContext {
+        AppTasksContext {
+            tasks,
+            selected: TableState::default(),
+        }
+    }
+
+    pub fn render<B: Backend>(&mut self,terminal: &mut Terminal<B>) {
+        // The newest task first.
+        let tasks = self.tasks.all().into_iter().rev().collect::<Vec<Task>>();
+        let selected = self.selected.selected().unwrap_or(0).min(tasks.len().saturating_sub(1));
+        self.selected.select(if tasks.is_empty() { None } else { Some(selected) });
+        terminal.draw(|f| {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1),Constraint::Percentage(45),Constraint::Min(6),Constraint::Length(3)].as_ref())
+                .split(f.size());
+
+            let header_paragraph = Paragraph::new(Spans::from(vec![Span::styled(VERSION,Style::default().fg(Color::LightRed)),Span::raw(" | Background tasks")]))
+                .style(Style::default().bg(Color::Black).fg(Color::LightYellow))
+                .alignment(Alignment::Center);
+            f.render_widget(header_paragraph,chunks[0]);
+
+            let rows = tasks.iter().map(|task| {
+                let (state,style) = match &task.status {
+                    TaskStatus::Running => (String::from("RUNNING"),Style::default().fg(Color::LightYellow)),
+                    TaskStatus::Succeeded(_) => (String::from("DONE"),Style::default().fg(Color::Green)),
+                    TaskStatus::Failed(_) => (String::from("FAILED"),Style::default().fg(Color::Red)),
+                };
+                let detail = match (&task.status,task.progress) {
+                    (TaskStatus::Running,Some(progress)) => format!("{} {}",progress_bar(progress,20),task.message.lines().next().unwrap_or("")),
+                    (TaskStatus::Running,None) => task.message.lines().next().unwrap_or("").to_owned(),
+                    (TaskStatus::Succeeded(x),_) | (TaskStatus::Failed(x),_) => x.lines().next().unwrap_or("").to_owned(),
+                };
+                Row::new(vec![Span::styled(state,style),Span::raw(task.title.clone()),Span::raw(format_duration(task.duration())),Span::raw(detail)])
+            }).collect::<Vec<Row>>();
+            let table = Table::new(rows)
+                .header(Row::new(vec!["State","Task","Time","Progress / result"]).style(Style::default().add_modifier(Modifier::BOLD)))
+                .widths(&[Constraint::Length(8),Constraint::Percentage(35),Constraint::Length(8),Constraint::Min(10)])
+                .block(Block::default().borders(Borders::ALL).title("Tasks").style(Style::default().fg(Color::Blue)))
+                .style(Style::default().fg(Color::White))
+                .highlight_style(Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD))
+                .highlight_symbol(">> ");
+            f.render_stateful_widget(table,chunks[1],&mut self.selected);
+
+            let details = match tasks.get(selected) {
+                Some(task) => match &task.status {
+                    TaskStatus::Running => format!("{}\n{}",task.title,task.message),
+                    TaskStatus::Succeeded(x) | TaskStatus::Failed(x) => format!("{}\n{}",task.title,x),
+                },
+                None => String::from("No tasks were started yet."),
+            };
+            let details = Paragraph::new(Text::from(details)).style(Style::default().fg(Color::White))
+                .block(Block::default().borders(Borders::ALL).title("Details").style(Style::default().fg(Color::Blue)))
+                .wrap(Wrap { trim: false });
+            f.render_widget(details,chunks[2]);
+
+            let help = Paragraph::new(Text::from("w/s - select; c - clear finished tasks; back - q or <esc>; quit - <ctrl-c>")).style(Style::default().fg(Color::Cyan))
+                                    .block(Block::default().borders(Borders::ALL).title("Help"))
+                                    .alignment(Alignment::Left);
+            f.render_widget(help,chunks[3]);
+        }).unwrap();
+    }
+
+    pub async fn event_loop<B: Backend>(&mut self, term: &mut Terminal<B>, events: &mut Events) -> AppState {
+        self.render(term);
+        loop {
+            match events.next().await {
+                Event::Key(Key::Char('q')) | Event::Key(Key::Esc) => return AppState::Search,
+                Event::Key(Key::Ctrl('c')) | Event::InputClosed => return AppState::Exiting,
+                Event::Key(Key::Char('w')) | Event::Key(Key::Up) => {
+                    let selected = self.selected.selected().unwrap_or(0);
+                    self.selected.select(Some(selected.saturating_sub(1)));
+                },
+                Event::Key(Key::Char('s')) | Event::Key(Key::Down) => {
+                    let selected = self.selected.selected().unwrap_or(0);
+                    self.selected.select(Some(selected+1));
+                },
+                Event::Key(Key::Char('c')) => {
+                    self.tasks.clear_finished();
+                    self.selected.select(Some(0));
+                },
+                _ => {},
+            }
+            self.render(term);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! A single line text field shared by all screens: cursor movement, word and
+//! line deletion, pasting and horizontal scrolling of values wider than the
+//! field. Positions are kept on grapheme boundaries and measured in display
+//! columns, so wide and combined characters are edited and drawn as one.
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::Style,
+    text::Span,
+    widgets::{Block, Paragraph},
+    Frame,
+};
+use termion::event::Key;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+#[derive(Clone,Default)]
+pub struct TextInput {
+    value: String,
+    /// Byte index of the cursor in `value`.
+    cursor: usize,
+    /// Display column of `value` shown at the left edge of the field.
+    scroll: usize,
+}
+
+impl TextInput {
+    /// A field holding `value` with the cursor at its end.
+    pub fn new<S: Into<String>>(value: S) -> TextInput {
+        let value = value.into();
+        TextInput {
+            cursor: value.len(),
+            value,
+            scroll: 0,
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// The text left of the cursor.
+    pub fn before_cursor(&self) -> &str {
+        &self.value[..self.cursor]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.value.is_empty()
+    }
+
+    /// Replaces the value and moves the cursor to its end.
+    pub fn set_value<S: Into<String>>(&mut self, value: S) {
+        *self = TextInput::new(value);
+    }
+
+    pub fn clear(&mut self) {
+        self.set_value(String::new());
+    }
+
+    fn previous_boundary(&self) -> usize {
+        self.value[..self.cursor].grapheme_indices(true).next_back().map(|(x,_)| x).unwrap_or(0)
+    }
+
+    fn next_boundary(&self) -> usize {
+        self.value[self.cursor..].graphemes(true).next().map(|x| self.cursor+x.len()).unwrap_or(self.cursor)
+    }
+
+    /// Inserts `text` at the cursor, line breaks and tabs become spaces and other
+    /// control characters are dropped.
+    pub fn insert_str(&mut self, text: &str) {
+        let text = text.trim_end_matches(['\n','\r']).chars()
+            .map(|x| if x == '\n' || x == '\r' || x == '\t' { ' ' } else { x })
+            .filter(|x| !x.is_control()).collect::<String>();
+        self.value.insert_str(self.cursor,&text);
+        self.cursor += text.len();
+    }
+
+    /// Deletes the word left of the cursor and the whitespace following it.
+    fn delete_word(&mut self) {
+        let before = self.value[..self.cursor].trim_end();
+        let start = before.rfind(char::is_whitespace).map(|x| x+1).unwrap_or(0);
+        self.value.replace_range(start..self.cursor,"");
+        self.cursor = start;
+    }
+
+    /// Applies an editing key, returns false if the key does not edit the field.
+    /// Enter and tab are left to the screen.
+    pub fn handle_key(&mut self, key: Key) -> bool {
+        match key {
+            Key::Char('\n') | Key::Char('\t') => return false,
+            Key::Char(c) => {
+                self.value.insert(self.cursor,c);
+                self.cursor += c.len_utf8();
+            },
+            Key::Backspace => {
+                let start = self.previous_boundary();
+                self.value.replace_range(start..self.cursor,"");
+                self.cursor = start;
+            },
+            Key::Delete => {
+                let end = self.next_boundary();
+                self.value.replace_range(self.cursor..end,"");
+            },
+            Key::Left => self.cursor = self.previous_boundary(),
+            Key::Right => self.cursor = self.next_boundary(),
+            Key::Home => self.cursor = 0,
+            Key::End => self.cursor = self.value.len(),
+            Key::Ctrl('w') => self.delete_word(),
+            Key::Ctrl('u') => {
+                self.value.replace_range(..self.cursor,"");
+                self.cursor = 0;
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    /// The part of the value visible in `width` columns, scrolled so that the
+    /// cursor stays in view, and the column of the cursor in it.
+    pub fn view(&mut self, width: usize) -> (String,usize) {
+        let width = width.max(1);
+        let cursor = self.value[..self.cursor].width();
+        if cursor < self.scroll {
+            self.scroll = cursor;
+        }
+        else if cursor >= self.scroll+width {
+            self.scroll = cursor+1-width;
+        }
+        let mut visible = String::new();
+        let mut column = 0;
+        for grapheme in self.value.graphemes(true) {
+            let end = column+grapheme.width();
+            if column >= self.scroll && end <= self.scroll+width {
+                visible.push_str(grapheme);
+            }
+            else if column < self.scroll && end > self.scroll {
+                // A wide character cut by the left edge.
+                visible.push_str(&" ".repeat(end-self.scroll));
+            }
+            column = end;
+        }
+        (visible,cursor-self.scroll)
+    }
+
+    /// Draws the field into `area` inside `block`, the value follows `prompt`. The
+    /// terminal cursor is placed in the field if it has the focus.
+    pub fn render_on<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect, prompt: &str, block: Block, style: Style, focused: bool) {
+        let inner = block.inner(area);
+        let prompt_width = prompt.width();
+        let (visible,cursor) = self.view((inner.width as usize).saturating_sub(prompt_width));
+        f.render_widget(Paragraph::new(Span::raw(format!("{}{}",prompt,visible))).block(block).style(style),area);
+        if focused && inner.height > 0 {
+            f.set_cursor(inner.x+std::cmp::min(prompt_width+cursor,inner.width.saturating_sub(1) as usize) as u16,inner.y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(keys: &[Key]) -> TextInput {
+        let mut input = TextInput::default();
+        for key in keys {
+            input.handle_key(*key);
+        }
+        input
+    }
+
+    #[test]
+    fn editing_at_the_cursor() {
+        let mut input = typed(&[Key::Char('a'),Key::Char('c'),Key::Left,Key::Char('b')]);
+        assert_eq!(input.value(),"abc");
+        assert_eq!(input.before_cursor(),"ab");
+        input.handle_key(Key::Delete);
+        assert_eq!(input.value(),"ab");
+        input.handle_key(Key::Home);
+        input.handle_key(Key::Delete);
+        assert_eq!(input.value(),"b");
+        assert!(!input.handle_key(Key::Char('\n')));
+        assert!(!input.handle_key(Key::Char('\t')));
+    }
+
+    #[test]
+    fn graphemes_are_edited_as_one() {
+        // An e followed by a combining acute accent.
+        let mut input = TextInput::new("ae\u{301}");
+        input.handle_key(Key::Left);
+        assert_eq!(input.before_cursor(),"a");
+        input.handle_key(Key::Right);
+        input.handle_key(Key::Backspace);
+        assert_eq!(input.value(),"a");
+        let mut input = TextInput::new("漢字");
+        input.handle_key(Key::Left);
+        input.handle_key(Key::Char('x'));
+        assert_eq!(input.value(),"漢x字");
+    }
+
+    #[test]
+    fn word_and_line_deletion() {
+        let mut input = TextInput::new("foo bar ");
+        input.handle_key(Key::Ctrl('w'));
+        assert_eq!(input.value(),"foo ");
+        let mut input = TextInput::new("foo bar");
+        input.handle_key(Key::Left);
+        input.handle_key(Key::Ctrl('u'));
+        assert_eq!(input.value(),"r");
+        assert_eq!(input.before_cursor(),"");
+    }
+
+    #[test]
+    fn pasted_text() {
+        let mut input = TextInput::new("ab");
+        input.handle_key(Key::Left);
+        input.insert_str("1\n2\t3\x07\n");
+        assert_eq!(input.value(),"a1 2 3b");
+        assert_eq!(input.before_cursor(),"a1 2 3");
+    }
+
+    #[test]
+    fn scrolls_to_the_cursor() {
+        let mut input = TextInput::new("abcdef");
+        assert_eq!(input.view(3),(String::from("ef"),2));
+        input.handle_key(Key::Home);
+        assert_eq!(input.view(3),(String::from("abc"),0));
+    }
+
+    #[test]
+    fn wide_characters_in_the_cursor_column() {
+        let mut input = TextInput::new("漢字x");
+        assert_eq!(input.view(10),(String::from("漢字x"),5));
+        // The second character is cut by the left edge and shown as a space.
+        assert_eq!(input.view(3),(String::from(" x"),2));
+        let mut input = TextInput::new("漢字x");
+        input.handle_key(Key::Left);
+        input.handle_key(Key::Left);
+        assert_eq!(input.view(10),(String::from("漢字x"),2));
+    }
+}
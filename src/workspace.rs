@@ -0,0 +1,396 @@
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::{Component,Path,PathBuf};
+use std::process::Command;
+use futures_util::TryStreamExt;
+use ignore::WalkBuilder;
+use ignore::gitignore::{Gitignore,GitignoreBuilder};
+use shiplift::Docker;
+
+/// Counters describing how much of a working directory was transferred.
+#[derive(Default,Clone,Copy)]
+pub struct CopyStats {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl CopyStats {
+    pub fn describe(&self) -> String {
+        format!("{} files ({})",self.files,format_size(self.bytes))
+    }
+}
+
+/// Formats a byte count in a human readable way, e.g. `12.3 MiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B","KiB","MiB","GiB","TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len()-1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}",bytes,UNITS[0])
+    }
+    else {
+        format!("{:.1} {}",size,UNITS[unit])
+    }
+}
+
+/// Splits a target directory inside the container like `/root/mounted` into the
+/// directory the archive is extracted to (`/root`) and the name every archive
+/// entry is prefixed with (`mounted`). Docker refuses to extract into a path that
+/// does not exist yet, so the archive has to create the target itself.
+fn split_target(target: &str) -> Result<(PathBuf,PathBuf),String> {
+    let target = Path::new(target);
+    match (target.parent(),target.file_name()) {
+        (Some(parent),Some(name)) => Ok((parent.to_path_buf(),PathBuf::from(name))),
+        _ => Err(format!("Invalid target directory \"{}\"",target.display())),
+    }
+}
+
+/// Walks `source` while respecting `.gitignore` and `.dockerignore` files.
+pub fn walk(source: &Path) -> ignore::Walk {
+    WalkBuilder::new(source)
+        .hidden(false)
+        .require_git(false)
+        .add_custom_ignore_filename(".dockerignore")
+        .build()
+}
+
+/// A packed working directory ready to be extracted into a container.
+pub struct Archive {
+    data: Vec<u8>,
+    destination: PathBuf,
+    pub stats: CopyStats,
+}
+
+/// Packs `source` into a tar archive which recreates it as `target` inside the
+/// container. `progress` is called after every added file.
+pub fn pack_directory<F: FnMut(&CopyStats)>(source: &Path, target: &str, mut progress: F) -> Result<Archive,String> {
+    if !source.is_dir() {
+        return Err(format!("\"{}\" is not a directory!",source.display()));
+    }
+    let (destination,prefix) = split_target(target)?;
+    let mut stats = CopyStats::default();
+    let mut builder = tar::Builder::new(Vec::new());
+    builder.follow_symlinks(false);
+    for entry in walk(source) {
+        let entry = entry.map_err(|e| format!("Could not read working directory: {}",e))?;
+        let relative = match entry.path().strip_prefix(source) {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+        let name = prefix.join(relative);
+        let file_type = match entry.file_type() {
+            Some(x) => x,
+            None => continue,
+        };
+        if file_type.is_dir() {
+            builder.append_dir(&name,entry.path()).map_err(|e| format!("Could not pack \"{}\": {}",entry.path().display(),e))?;
+        }
+        else {
+            builder.append_path_with_name(entry.path(),&name).map_err(|e| format!("Could not pack \"{}\": {}",entry.path().display(),e))?;
+            if file_type.is_file() {
+                stats.files += 1;
+                stats.bytes += entry.metadata().map(|x| x.len()).unwrap_or(0);
+                progress(&stats);
+            }
+        }
+    }
+    let data = builder.into_inner().map_err(|e| format!("Could not finish archive: {}",e))?;
+    Ok(Archive {
+        data,
+        destination,
+        stats,
+    })
+}
+
+/// Extracts a packed working directory inside the container `id`.
+pub async fn upload(docker: &Docker, id: &str, archive: Archive) -> Result<CopyStats,String> {
+    docker.containers().get(id).copy_to(&archive.destination,archive.data.into()).await.map_err(|e| format!("Could not upload working directory: {}",e))?;
+    Ok(archive.stats)
+}
+
+/// Differences between a working directory inside a container and a host directory.
+#[derive(Default)]
+pub struct Changes {
+    pub added: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+    pub deleted: Vec<PathBuf>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.modified.is_empty() && self.deleted.is_empty()
+    }
+
+    /// Short overview listing the first few changed paths of every kind.
+    pub fn summary(&self) -> String {
+        const SHOWN: usize = 5;
+        let mut result = format!("{} added, {} modified, {} deleted",self.added.len(),self.modified.len(),self.deleted.len());
+        for (sign,paths) in [("+",&self.added),("~",&self.modified),("-",&self.deleted)].iter() {
+            for path in paths.iter().take(SHOWN) {
+                result += &format!("\n{} {}",sign,path.display());
+            }
+            if paths.len() > SHOWN {
+                result += &format!("\n{} ... {} more",sign,paths.len()-SHOWN);
+            }
+        }
+        result
+    }
+}
+
+/// Downloads `target` from the container `id` as a tar archive.
+pub async fn download(docker: &Docker, id: &str, target: &str) -> Result<Vec<u8>,String> {
+    docker.containers().get(id).copy_from(Path::new(target)).try_concat().await.map_err(|e| format!("Could not download \"{}\": {}",target,e))
+}
+
+/// Matcher for the ignore files at the top of `host`, used to skip files in a
+/// downloaded archive that would not have been copied into the container.
+fn host_ignore(host: &Path) -> Gitignore {
+    let mut builder = GitignoreBuilder::new(host);
+    for name in [".gitignore",".dockerignore"].iter() {
+        let file = host.join(name);
+        if file.is_file() {
+            builder.add(file);
+        }
+    }
+    builder.build().unwrap_or_else(|_| Gitignore::empty())
+}
+
+/// The path of an archive entry relative to the archived directory, every entry
+/// starts with the name of the downloaded directory itself. Paths which could
+/// leave the directory through `..` or absolute components are rejected.
+fn relative_path(path: &Path) -> Result<PathBuf,String> {
+    let mut relative = PathBuf::new();
+    for (idx,component) in path.components().filter(|x| *x != Component::CurDir).enumerate() {
+        match component {
+            Component::Normal(_) if idx == 0 => {},
+            Component::Normal(x) => relative.push(x),
+            _ => return Err(format!("The archive contains the unsafe path \"{}\"",path.display())),
+        }
+    }
+    Ok(relative)
+}
+
+/// Calls `func` with the path relative to the archived directory for every
+/// entry of `archive` that should be synchronised to `host`.
+fn for_each_entry<F: FnMut(PathBuf,&mut tar::Entry<&[u8]>) -> Result<(),String>>(archive: &[u8], host: &Path, mut func: F) -> Result<(),String> {
+    let ignored = host_ignore(host);
+    let mut archive = tar::Archive::new(archive);
+    for entry in archive.entries().map_err(|e| format!("Invalid archive: {}",e))? {
+        let mut entry = entry.map_err(|e| format!("Invalid archive: {}",e))?;
+        let kind = entry.header().entry_type();
+        if !kind.is_file() && !kind.is_symlink() {
+            continue;
+        }
+        let relative = relative_path(&entry.path().map_err(|e| format!("Invalid archive: {}",e))?)?;
+        if relative.as_os_str().is_empty() || ignored.matched_path_or_any_parents(&relative,false).is_ignore() {
+            continue;
+        }
+        func(relative,&mut entry)?;
+    }
+    Ok(())
+}
+
+/// Whether the archive entry differs from the existing host file `path`.
+/// Symlinks are compared by their target instead of the file they point to.
+fn differs(path: &Path, entry: &mut tar::Entry<&[u8]>) -> Result<bool,String> {
+    let host_link = std::fs::read_link(path).ok();
+    if entry.header().entry_type().is_symlink() {
+        let target = entry.link_name().map_err(|e| format!("Invalid archive: {}",e))?.map(|x| x.into_owned());
+        return Ok(host_link.is_none() || host_link != target);
+    }
+    if host_link.is_some() {
+        return Ok(true);
+    }
+    let content = match std::fs::read(path) {
+        Ok(x) => x,
+        Err(_) => return Ok(true),
+    };
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data).map_err(|e| format!("Invalid archive: {}",e))?;
+    Ok(data != content)
+}
+
+/// Compares a downloaded working directory against the host directory `host`.
+pub fn diff(archive: &[u8], host: &Path) -> Result<Changes,String> {
+    let mut changes = Changes::default();
+    let mut seen = HashSet::new();
+    for_each_entry(archive,host,|relative,entry| {
+        let path = host.join(&relative);
+        if std::fs::symlink_metadata(&path).is_err() {
+            changes.added.push(relative.clone());
+        }
+        else if differs(&path,entry)? {
+            changes.modified.push(relative.clone());
+        }
+        seen.insert(relative);
+        Ok(())
+    })?;
+    if host.is_dir() {
+        for entry in walk(host).flatten() {
+            if entry.file_type().map(|x| x.is_file()).unwrap_or(false) {
+                if let Ok(relative) = entry.path().strip_prefix(host) {
+                    if !seen.contains(relative) {
+                        changes.deleted.push(relative.to_path_buf());
+                    }
+                }
+            }
+        }
+    }
+    Ok(changes)
+}
+
+/// Checks that `relative` stays inside the canonical directory `root`, also
+/// through symlinked directories which already exist on the host.
+fn check_contained(root: &Path, relative: &Path) -> Result<(),String> {
+    let error = format!("Refusing to write \"{}\" outside of \"{}\"",relative.display(),root.display());
+    if relative.components().any(|x| !matches!(x,Component::Normal(_))) {
+        return Err(error);
+    }
+    let mut existing = root.join(relative);
+    existing.pop();
+    while !existing.exists() && existing.pop() {}
+    match existing.canonicalize() {
+        Ok(x) if x.starts_with(root) => Ok(()),
+        _ => Err(error),
+    }
+}
+
+/// Writes the `changes` contained in `archive` to the host directory `host`,
+/// which is created if necessary. Every path is checked before the host is
+/// changed, so a bad archive leaves it untouched.
+pub fn apply(archive: &[u8], host: &Path, changes: &Changes) -> Result<(),String> {
+    std::fs::create_dir_all(host).map_err(|e| format!("Could not create \"{}\": {}",host.display(),e))?;
+    let root = host.canonicalize().map_err(|e| format!("Could not open \"{}\": {}",host.display(),e))?;
+    for path in changes.added.iter().chain(changes.modified.iter()).chain(changes.deleted.iter()) {
+        check_contained(&root,path)?;
+    }
+    for_each_entry(archive,host,|_,_| Ok(()))?;
+    for path in changes.deleted.iter() {
+        std::fs::remove_file(root.join(path)).map_err(|e| format!("Could not delete \"{}\": {}",path.display(),e))?;
+    }
+    let wanted = changes.added.iter().chain(changes.modified.iter()).collect::<HashSet<&PathBuf>>();
+    for_each_entry(archive,host,|relative,entry| {
+        if wanted.contains(&relative) {
+            let destination = root.join(&relative);
+            if let Some(parent) = destination.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| format!("Could not create \"{}\": {}",parent.display(),e))?;
+                check_contained(&root,&relative)?;
+            }
+            entry.unpack(&destination).map_err(|e| format!("Could not write \"{}\": {}",relative.display(),e))?;
+        }
+        Ok(())
+    })
+}
+
+/// Returns true if `dir` is part of a git repository with uncommitted changes.
+pub fn has_uncommitted_changes(dir: &Path) -> bool {
+    match Command::new("git").arg("-C").arg(dir).args(["status","--porcelain"]).output() {
+        Ok(output) => output.status.success() && !output.stdout.is_empty(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory below the temporary directory, unique per test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dde-workspace-{}-{}",name,std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// An archive of the directory `work` as docker returns it. Names are
+    /// written as they are, so that unsafe paths can be tested.
+    fn archive(files: &[(&str,&str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name,content) in files.iter() {
+            let mut header = tar::Header::new_gnu();
+            let path = format!("work/{}",name);
+            header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_cksum();
+            builder.append(&header,content.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn write(dir: &Path, name: &str, content: &str) {
+        std::fs::create_dir_all(dir.join(name).parent().unwrap()).unwrap();
+        std::fs::write(dir.join(name),content).unwrap();
+    }
+
+    fn read(dir: &Path, name: &str) -> String {
+        std::fs::read_to_string(dir.join(name)).unwrap()
+    }
+
+    #[test]
+    fn diff_and_apply() {
+        let host = temp_dir("apply");
+        write(&host,"same.txt","same");
+        write(&host,"changed.txt","old");
+        write(&host,"gone.txt","gone");
+        let data = archive(&[("same.txt","same"),("changed.txt","new"),("sub/added.txt","added")]);
+        let changes = diff(&data,&host).unwrap();
+        assert_eq!(changes.added,vec![PathBuf::from("sub/added.txt")]);
+        assert_eq!(changes.modified,vec![PathBuf::from("changed.txt")]);
+        assert_eq!(changes.deleted,vec![PathBuf::from("gone.txt")]);
+        apply(&data,&host,&changes).unwrap();
+        assert_eq!(read(&host,"changed.txt"),"new");
+        assert_eq!(read(&host,"sub/added.txt"),"added");
+        assert!(!host.join("gone.txt").exists());
+        assert!(diff(&data,&host).unwrap().is_empty());
+        std::fs::remove_dir_all(&host).unwrap();
+    }
+
+    #[test]
+    fn apply_creates_the_host_directory() {
+        let host = temp_dir("new").join("export");
+        let data = archive(&[("a.txt","a")]);
+        let changes = diff(&data,&host).unwrap();
+        assert_eq!(changes.added,vec![PathBuf::from("a.txt")]);
+        apply(&data,&host,&changes).unwrap();
+        assert_eq!(read(&host,"a.txt"),"a");
+        std::fs::remove_dir_all(host.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn rejects_paths_leaving_the_host_directory() {
+        let host = temp_dir("escape");
+        write(&host,"keep.txt","keep");
+        assert!(diff(&archive(&[("../evil.txt","evil")]),&host).is_err());
+
+        // Nothing is deleted if any path of the changes is unsafe.
+        let data = archive(&[("a.txt","a")]);
+        let changes = Changes { added: vec![PathBuf::from("../evil.txt")], deleted: vec![PathBuf::from("keep.txt")], ..Changes::default() };
+        assert!(apply(&data,&host,&changes).is_err());
+        assert!(host.join("keep.txt").exists());
+
+        let changes = Changes { deleted: vec![PathBuf::from("keep.txt")], ..Changes::default() };
+        assert!(apply(&archive(&[("../evil.txt","evil")]),&host,&changes).is_err());
+        assert!(host.join("keep.txt").exists());
+        std::fs::remove_dir_all(&host).unwrap();
+    }
+
+    #[test]
+    fn rejects_symlinked_directories_leaving_the_host_directory() {
+        let host = temp_dir("symlink");
+        let outside = temp_dir("symlink-outside");
+        std::fs::create_dir_all(&host).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside,host.join("link")).unwrap();
+        let data = archive(&[("link/evil.txt","evil")]);
+        let changes = Changes { added: vec![PathBuf::from("link/evil.txt")], ..Changes::default() };
+        assert!(apply(&data,&host,&changes).is_err());
+        assert!(!outside.join("evil.txt").exists());
+        std::fs::remove_dir_all(&host).unwrap();
+        std::fs::remove_dir_all(&outside).unwrap();
+    }
+}